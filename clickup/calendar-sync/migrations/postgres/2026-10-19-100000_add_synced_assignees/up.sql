@@ -0,0 +1,3 @@
+-- Who the sync assigned to the task last time, as a JSON array of ClickUp user IDs
+-- Used to work out which assignees to add/remove on update without touching people assigned by hand
+ALTER TABLE clickup_ical_mapping ADD COLUMN synced_assignees TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,3 @@
+-- If the event has disappeared from the calendar but we kept the task (e.g. archived it), how we did so, as JSON of the deletion policy
+-- NULL for events that are still in the calendar
+ALTER TABLE clickup_ical_mapping ADD COLUMN tombstone TEXT;
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE clickup_ical_mapping DROP COLUMN last_synced;
@@ -0,0 +1,3 @@
+-- What the sync last wrote to the name, description & dates of the task, as JSON
+-- Used to detect when someone has edited the task in ClickUp since
+ALTER TABLE clickup_ical_mapping ADD COLUMN last_synced TEXT;
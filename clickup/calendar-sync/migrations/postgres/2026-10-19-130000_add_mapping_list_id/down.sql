@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+-- Only one task per event can be mapped again, so keep the mapping to the first task made for each event
+DELETE FROM clickup_ical_mapping a USING clickup_ical_mapping b WHERE a.calendar_id = b.calendar_id AND a.mapping_id > b.mapping_id;
+ALTER TABLE clickup_ical_mapping DROP CONSTRAINT uk_calendar_id_list_id;
+ALTER TABLE clickup_ical_mapping ADD CONSTRAINT uk_calendar_id UNIQUE (calendar_id);
+ALTER TABLE clickup_ical_mapping DROP COLUMN list_id;
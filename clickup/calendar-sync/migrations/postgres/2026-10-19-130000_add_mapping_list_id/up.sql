@@ -0,0 +1,5 @@
+-- Events can be synced to more than one list, so there is now a mapping per (event, list) rather than per event
+-- '' for mappings from before this, which are all in CLICKUP_TARGET_LIST_ID (the sync fills these in when it starts)
+ALTER TABLE clickup_ical_mapping ADD COLUMN list_id TEXT NOT NULL DEFAULT '';
+ALTER TABLE clickup_ical_mapping DROP CONSTRAINT uk_calendar_id;
+ALTER TABLE clickup_ical_mapping ADD CONSTRAINT uk_calendar_id_list_id UNIQUE (calendar_id, list_id);
@@ -0,0 +1,7 @@
+-- OAuth tokens from the `authorize` command, encrypted with CLICKUP_TOKEN_KEY
+-- Only the newest is used, so re-authorising rotates the token without redeploying
+CREATE TABLE clickup_oauth_token (
+	token_id SERIAL PRIMARY KEY,
+	encrypted_token TEXT NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+-- Whether someone deleted the task by hand in ClickUp, so the event is no longer synced
+ALTER TABLE clickup_ical_mapping ADD COLUMN user_deleted BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- ID of the meeting notes document made in ClickUp Docs for the event, if any
+ALTER TABLE clickup_ical_mapping ADD COLUMN meeting_doc TEXT;
@@ -0,0 +1,2 @@
+-- Fingerprint of the event the task was last synced from, so runs can skip events that haven't changed
+ALTER TABLE clickup_ical_mapping ADD COLUMN fingerprint TEXT;
@@ -0,0 +1,7 @@
+-- Token buckets for rate limiting requests to ClickUp, shared by every process using the same token (see rate_limit.rs)
+-- Each bucket is named after a hash of its token, and updated_at is in milliseconds since the epoch
+CREATE TABLE clickup_rate_limit (
+	bucket TEXT PRIMARY KEY,
+	tokens DOUBLE PRECISION NOT NULL,
+	updated_at BIGINT NOT NULL
+);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE clickup_rate_limit;
+DROP TABLE clickup_oauth_token;
+DROP TABLE clickup_ical_mapping;
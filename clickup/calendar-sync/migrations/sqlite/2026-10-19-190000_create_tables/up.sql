@@ -0,0 +1,39 @@
+-- The same tables as the Postgres migrations end up with (see migrations/postgres), for builds with the `sqlite` feature
+-- New columns & tables need a migration here as well as there, so the two stay the same as src/schema.rs
+
+-- Map each (event, list) to its task in that list
+CREATE TABLE clickup_ical_mapping (
+	mapping_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	clickup_id TEXT NOT NULL,
+	calendar_id TEXT NOT NULL,
+	-- JSON array of the ClickUp user IDs the sync assigned to the task last time
+	synced_assignees TEXT NOT NULL DEFAULT '[]',
+	-- How the task was retired when its event disappeared from the calendar (JSON of the deletion policy), NULL if it's still there
+	tombstone TEXT,
+	-- What the sync last wrote to the name, description & dates of the task, as JSON
+	last_synced TEXT,
+	list_id TEXT NOT NULL DEFAULT '',
+	-- Whether someone deleted the task by hand in ClickUp, so the event is no longer synced
+	user_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+	-- ID of the meeting notes document made in ClickUp Docs for the event, if any
+	meeting_doc TEXT,
+	-- Fingerprint of the event the task was last synced from, so runs can skip events that haven't changed
+	fingerprint TEXT,
+
+	CONSTRAINT uk_clickup_id UNIQUE (clickup_id),
+	CONSTRAINT uk_calendar_id_list_id UNIQUE (calendar_id, list_id)
+);
+
+-- OAuth tokens from the `authorize` command, encrypted with CLICKUP_TOKEN_KEY
+CREATE TABLE clickup_oauth_token (
+	token_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	encrypted_token TEXT NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Token buckets for rate limiting requests to ClickUp, shared by every process using the same token (see rate_limit.rs)
+CREATE TABLE clickup_rate_limit (
+	bucket TEXT PRIMARY KEY NOT NULL,
+	tokens DOUBLE NOT NULL,
+	updated_at BIGINT NOT NULL
+);
@@ -0,0 +1,17 @@
+-- A row per sync run, for the `status` command (see history.rs)
+-- Times are in UTC. finished_at is NULL whilst the run is going (or if it was killed), and error is set if it failed
+CREATE TABLE sync_runs (
+	run_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	started_at TIMESTAMP NOT NULL,
+	finished_at TIMESTAMP,
+	-- Name of the calendar synced from, NULL if it wasn't downloaded (or has no name)
+	source TEXT,
+	-- Tasks created, updated, deleted, skipped & failed, and requests sent to ClickUp (see sync.rs)
+	created INTEGER NOT NULL DEFAULT 0,
+	updated INTEGER NOT NULL DEFAULT 0,
+	deleted INTEGER NOT NULL DEFAULT 0,
+	skipped INTEGER NOT NULL DEFAULT 0,
+	failed INTEGER NOT NULL DEFAULT 0,
+	requests INTEGER NOT NULL DEFAULT 0,
+	error TEXT
+);
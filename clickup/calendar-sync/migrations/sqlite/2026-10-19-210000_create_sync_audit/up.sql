@@ -0,0 +1,17 @@
+-- A row per change a sync run made to a task in ClickUp, so the run can be undone (see audit.rs)
+-- operation is create, update, delete, retire or restore. The payloads are JSON of the task (or for retire & restore,
+-- of the deletion policy) before & after the change, NULL where there's nothing to record (e.g. before a create)
+CREATE TABLE sync_audit (
+	audit_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	run_id INTEGER NOT NULL REFERENCES sync_runs (run_id),
+	recorded_at TIMESTAMP NOT NULL,
+	operation TEXT NOT NULL,
+	list_id TEXT NOT NULL,
+	task_id TEXT NOT NULL,
+	event_uid TEXT NOT NULL,
+	payload_before TEXT,
+	payload_after TEXT,
+	-- Set once the `undo` command has undone the change
+	undone BOOLEAN NOT NULL DEFAULT FALSE
+);
+CREATE INDEX sync_audit_run_id ON sync_audit (run_id);
@@ -0,0 +1,84 @@
+/// Matching existing ClickUp tasks up to events, to rebuild mappings instead of creating duplicate tasks
+///
+/// This is used by the `adopt` command, e.g. after the database is lost, or when pointing the sync at a list
+/// where people have already made tasks for events by hand. Each event without a mapping is matched to a task:
+/// 1. By the UID marker custom field, if one is configured & the task has it set
+/// 2. Otherwise, by name (case insensitive) and date, for tasks without a UID marker
+///
+/// Subtasks (e.g. those added from templates) are never matched, as they'd share their parent's name & date.
+/// Tasks that already have a mapping are never matched again. If more than one task matches an event,
+/// or more than one event matches a task, we can't tell which is which, so those are reported rather than adopted.
+use chrono::{DateTime, NaiveDate};
+use std::collections::{HashMap, HashSet};
+
+use crate::{clickup::ClickUpTask, docsoc_ical::ParsedEvent};
+
+/// What `plan_adoption` worked out
+#[derive(Debug, Default, PartialEq)]
+pub struct AdoptionPlan {
+	/// (event UID, task ID) pairs to write mappings for
+	pub adopted: Vec<(String, String)>,
+	/// Events that matched more than one task (or whose task matched other events too), with the IDs of the candidate tasks
+	pub ambiguous: Vec<(String, Vec<String>)>,
+	/// UIDs of events that matched no task, which the next sync will create tasks for
+	pub unmatched: Vec<String>,
+}
+
+/// Match events without mappings to tasks without mappings
+/// ### Arguments
+/// * `events` - Events from the calendar
+/// * `tasks` - All tasks in the target list (including subtasks, which are skipped)
+/// * `uid_field` - ID of the custom field holding the UID of the event, if configured
+/// * `mapped_events` - UIDs of events that already have mappings
+/// * `mapped_tasks` - IDs of tasks that already have mappings
+pub fn plan_adoption(events: &[ParsedEvent], tasks: &[ClickUpTask], uid_field: Option<&str>, mapped_events: &HashSet<String>, mapped_tasks: &HashSet<String>) -> AdoptionPlan {
+	let tasks = tasks.iter().filter(|task| task.parent.is_none() && !mapped_tasks.contains(&task.id)).collect::<Vec<_>>();
+
+	// Find the candidate tasks for each event
+	let candidates = events.iter().filter(|event| !mapped_events.contains(&event.uid)).map(|event| {
+		let by_uid = tasks.iter().filter(|task| uid_of(task, uid_field) == Some(event.uid.as_str())).collect::<Vec<_>>();
+		let matches = if by_uid.is_empty() {
+			// Tasks with someone else's UID aren't for this event, whatever their name
+			tasks.iter().filter(|task| uid_of(task, uid_field).is_none() && name_and_date_match(event, task)).collect()
+		} else {
+			by_uid
+		};
+		(event, matches.into_iter().map(|task| task.id.clone()).collect::<Vec<_>>())
+	}).collect::<Vec<_>>();
+
+	// Count how many events want each task
+	let mut claims = HashMap::<&str, usize>::new();
+	for task_id in candidates.iter().flat_map(|(_, matches)| matches) {
+		*claims.entry(task_id).or_default() += 1;
+	}
+
+	let mut plan = AdoptionPlan::default();
+	for (event, matches) in &candidates {
+		match matches.as_slice() {
+			[] => plan.unmatched.push(event.uid.clone()),
+			[task_id] if claims[task_id.as_str()] == 1 => plan.adopted.push((event.uid.clone(), task_id.clone())),
+			_ => plan.ambiguous.push((event.uid.clone(), matches.clone())),
+		}
+	}
+	plan
+}
+
+/// The UID marker on a task, if it has one
+fn uid_of<'a>(task: &'a ClickUpTask, uid_field: Option<&str>) -> Option<&'a str> {
+	uid_field.and_then(|field| task.custom_field_text(field)).filter(|uid| !uid.is_empty())
+}
+
+/// Whether a task has the same name as an event, and a start or due date on the day the event starts
+fn name_and_date_match(event: &ParsedEvent, task: &ClickUpTask) -> bool {
+	let Some(event_date) = event.start_time.map(|time| time.date()) else {
+		return false;
+	};
+	task.name.trim().eq_ignore_ascii_case(event.summary.trim())
+		&& [&task.start_date, &task.due_date].into_iter().any(|date| task_date(date) == Some(event_date))
+}
+
+/// The day of a date from ClickUp (a string of milliseconds since the epoch)
+fn task_date(millis: &Option<String>) -> Option<NaiveDate> {
+	let millis = millis.as_deref()?.parse::<i64>().ok()?;
+	Some(DateTime::from_timestamp_millis(millis)?.date_naive())
+}
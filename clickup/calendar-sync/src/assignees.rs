@@ -0,0 +1,115 @@
+/// Working out who should be assigned to the task for an event
+///
+/// Assignees come from three places:
+/// 1. Rules in the config, matching on the category or summary of the event
+/// 2. The default assignees in the config (for the task's list, or for every list), used if no rule matches
+/// 3. Optionally, the attendees & organiser of the event, matched by email to members of the ClickUp workspace
+///
+/// People in the config can be given as ClickUp user IDs, emails or usernames, and are looked up in the
+/// workspace members once when we start up.
+use log::warn;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+	clickup::TeamMember,
+	config::AssigneesConfig,
+	docsoc_ical::ParsedEvent,
+	matcher::EventMatcher,
+};
+
+/// A rule from the config, with its regex compiled and its assignees looked up
+#[derive(Debug)]
+struct AssigneeRule {
+	/// Which events the rule applies to
+	matcher: EventMatcher,
+	/// ClickUp user IDs to assign
+	assignees: BTreeSet<i64>,
+}
+
+impl AssigneeRule {
+	fn matches(&self, event: &ParsedEvent) -> bool {
+		self.matcher.matches(event)
+	}
+}
+
+/// Works out the ClickUp user IDs to assign to the task for each event
+#[derive(Debug)]
+pub struct AssigneeResolver {
+	/// Members of the workspace, used to match attendees by email
+	members: Vec<TeamMember>,
+	/// Rules from the config, in order
+	rules: Vec<AssigneeRule>,
+	/// Assignees to use if no rule matches
+	default: BTreeSet<i64>,
+	/// Assignees to use if no rule matches for tasks in particular lists instead, by list ID
+	list_defaults: HashMap<String, BTreeSet<i64>>,
+	/// Whether to assign attendees & the organiser of the event if they are members of the workspace
+	match_attendees: bool,
+}
+
+impl AssigneeResolver {
+	/// Build the resolver from the config, looking up everyone mentioned in it in the workspace members
+	/// ### Arguments
+	/// * `config` - The assignees section of the config
+	/// * `members` - Members of the ClickUp workspace
+	/// ### Returns
+	/// The resolver, or an error if a summary regex is invalid. People that can't be found are warned about & skipped.
+	pub fn new(config: &AssigneesConfig, members: Vec<TeamMember>) -> Result<Self, regex::Error> {
+		let lookup = |people: &[String]| -> BTreeSet<i64> {
+			people.iter().filter_map(|person| {
+				let id = find_member(&members, person);
+				if id.is_none() {
+					warn!("No member {:?} in the ClickUp workspace, not assigning them", person);
+				}
+				id
+			}).collect()
+		};
+
+		let rules = config.rules.iter().map(|rule| Ok(AssigneeRule {
+			matcher: EventMatcher::new(rule.category.as_deref(), rule.summary.as_deref())?,
+			assignees: lookup(&rule.assignees),
+		})).collect::<Result<Vec<_>, regex::Error>>()?;
+		let default = lookup(&config.default);
+		let list_defaults = config.lists.iter().map(|(list_id, people)| (list_id.clone(), lookup(people))).collect();
+
+		Ok(Self { rules, default, list_defaults, match_attendees: config.match_attendees, members })
+	}
+
+	/// Work out who should be assigned to the task for an event
+	/// ### Arguments
+	/// * `list_id` - ID of the list the task is in
+	/// * `event` - The event
+	/// ### Returns
+	/// The ClickUp user IDs of the assignees
+	pub fn assignees_for(&self, list_id: &str, event: &ParsedEvent) -> BTreeSet<i64> {
+		let mut assignees = BTreeSet::new();
+
+		let mut any_rule_matched = false;
+		for rule in self.rules.iter().filter(|rule| rule.matches(event)) {
+			any_rule_matched = true;
+			assignees.extend(&rule.assignees);
+		}
+		if !any_rule_matched {
+			assignees.extend(self.list_defaults.get(list_id).unwrap_or(&self.default));
+		}
+
+		if self.match_attendees {
+			// Only match by email here - attendees are never IDs or usernames
+			assignees.extend(event.attendees.iter().chain(event.organizer.iter())
+				.filter_map(|email| self.members.iter().find(|member| member.email.as_deref().is_some_and(|member_email| member_email.eq_ignore_ascii_case(email))))
+				.map(|member| member.id));
+		}
+
+		assignees
+	}
+}
+
+/// Find a member of the workspace by user ID, email or username
+fn find_member(members: &[TeamMember], person: &str) -> Option<i64> {
+	let person = person.trim();
+	members.iter().find(|member| {
+		member.id.to_string() == person
+			|| member.email.as_deref().is_some_and(|email| email.eq_ignore_ascii_case(person))
+			|| member.username.as_deref().is_some_and(|username| username.eq_ignore_ascii_case(person))
+	}).map(|member| member.id)
+}
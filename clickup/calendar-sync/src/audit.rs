@@ -0,0 +1,291 @@
+/// The audit log of what each sync run did to the tasks in ClickUp, and undoing a run
+///
+/// When the mappings are kept in the database, every task the sync creates, updates, deletes, retires (according to the
+/// deletion policy) or restores is recorded in the sync_audit table against the run (see history.rs), along with what the
+/// task was like before & after. The `undo --run <id>` command then works back through the changes a run made, newest first:
+/// tasks it created are deleted, tasks it updated have the fields it changed put back, tasks it deleted are made again from
+/// what they were like, and tasks it retired or restored are restored or retired again. The mappings are put back to match.
+///
+/// Only what the sync writes is put back (not comments, meeting notes or items from templates), and a task that a later run
+/// has changed is left alone, as undoing the older change would throw away the newer one (undo the later run first).
+/// Undoing a run doesn't stop the next run from doing the same again, so fix the calendar (or pause the sync) first.
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::{BTreeSet, HashMap, HashSet}, error::Error};
+
+use crate::{clickup::{ClickUpApiInstance, ClickUpError, ClickUpTask}, config::DeletionPolicy, models::*, ownership::SyncedFields, store::MappingStore, sync::SyncContext};
+
+/// What a task was like, before or after the sync changed it
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskSnapshot {
+	/// The fields the sync writes
+	#[serde(flatten)]
+	pub fields: SyncedFields,
+	/// People assigned to the task (for what the sync wrote, only the people the sync assigned)
+	pub assignees: BTreeSet<i64>,
+	/// Status of the task, if known
+	#[serde(default)]
+	pub status: Option<String>,
+	/// Tags on the task, if known
+	#[serde(default)]
+	pub tags: Vec<String>,
+}
+
+impl TaskSnapshot {
+	/// What the sync wrote to a task
+	/// ### Arguments
+	/// * `fields` - The fields the sync wrote
+	/// * `assignees` - The people the sync assigned
+	pub fn synced(fields: SyncedFields, assignees: BTreeSet<i64>) -> Self {
+		Self { fields, assignees, status: None, tags: vec![] }
+	}
+
+	/// What a task in ClickUp is like, from fetching it
+	pub fn of_task(task: &ClickUpTask) -> Self {
+		// ClickUp sends dates as strings of milliseconds
+		let date = |date: &Option<String>| date.as_deref().and_then(|date| date.parse().ok());
+		Self {
+			fields: SyncedFields {
+				name: task.name.clone(),
+				description: task.description.clone().unwrap_or_default(),
+				start_date: date(&task.start_date),
+				due_date: date(&task.due_date),
+				location: None,
+			},
+			assignees: task.assignees.iter().map(|member| member.id).collect(),
+			status: task.status.as_ref().map(|status| status.status.clone()),
+			tags: task.tags.iter().map(|tag| tag.name.clone()).collect(),
+		}
+	}
+}
+
+/// A change the sync made to a task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditOperation {
+	/// Created the task (for a new event, or in place of one deleted by hand)
+	Create { after: TaskSnapshot },
+	/// Updated the task. `before` is None if what the sync wrote before wasn't recorded (e.g. the task was adopted)
+	Update { before: Option<TaskSnapshot>, after: TaskSnapshot },
+	/// Deleted the task. `before` is None if it couldn't be fetched first
+	Delete { before: Option<TaskSnapshot> },
+	/// Retired the task with a deletion policy other than deleting it, as its event left the calendar
+	Retire { policy: DeletionPolicy },
+	/// Restored a task that had been retired with this deletion policy, as its event came back
+	Restore { policy: DeletionPolicy },
+}
+
+/// A change the sync made to a task, to record in the audit log (see `MappingStore::record`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+	/// The iCal UID of the event the task is for
+	pub event_uid: String,
+	/// ID of the list the task is in
+	pub list_id: String,
+	/// The ClickUp task ID
+	pub task_id: String,
+	/// What was done to the task
+	pub operation: AuditOperation,
+}
+
+/// Serialise a payload for the audit log
+fn to_json<T: Serialize>(payload: &T) -> String {
+	serde_json::to_string(payload).expect("Failed to serialise audit payload")
+}
+
+/// Deserialise a payload from the audit log, if there is one
+fn from_json<T: DeserializeOwned>(payload: &Option<String>) -> Result<Option<T>, serde_json::Error> {
+	payload.as_deref().map(serde_json::from_str).transpose()
+}
+
+impl AuditEntry {
+	/// A change to a task
+	/// ### Arguments
+	/// * `event_uid` - The iCal UID of the event the task is for
+	/// * `list_id` - ID of the list the task is in
+	/// * `task_id` - The ClickUp task ID
+	/// * `operation` - What was done to the task
+	pub fn new(event_uid: &str, list_id: &str, task_id: &str, operation: AuditOperation) -> Self {
+		Self { event_uid: event_uid.to_string(), list_id: list_id.to_string(), task_id: task_id.to_string(), operation }
+	}
+
+	/// A change to the task of a mapping
+	pub fn for_mapping(mapping: &CalendarMapping, operation: AuditOperation) -> Self {
+		Self::new(&mapping.calendar_id, &mapping.list_id, &mapping.clickup_id, operation)
+	}
+
+	/// How the change is stored in the audit log
+	/// ### Returns
+	/// The name of the operation, and JSON of the task (or deletion policy) before & after the change, where there's something to record
+	pub fn columns(&self) -> (&'static str, Option<String>, Option<String>) {
+		match &self.operation {
+			AuditOperation::Create { after } => ("create", None, Some(to_json(after))),
+			AuditOperation::Update { before, after } => ("update", before.as_ref().map(to_json), Some(to_json(after))),
+			AuditOperation::Delete { before } => ("delete", before.as_ref().map(to_json), None),
+			AuditOperation::Retire { policy } => ("retire", None, Some(to_json(policy))),
+			AuditOperation::Restore { policy } => ("restore", Some(to_json(policy)), None),
+		}
+	}
+
+	/// Read a change back from the audit log
+	pub fn from_record(record: &AuditRecord) -> Result<Self, Box<dyn Error>> {
+		let missing = || format!("Change {} is missing its payload", record.audit_id);
+		let operation = match record.operation.as_str() {
+			"create" => AuditOperation::Create { after: from_json(&record.payload_after)?.ok_or_else(missing)? },
+			"update" => AuditOperation::Update {
+				before: from_json(&record.payload_before)?,
+				after: from_json(&record.payload_after)?.ok_or_else(missing)?,
+			},
+			"delete" => AuditOperation::Delete { before: from_json(&record.payload_before)? },
+			"retire" => AuditOperation::Retire { policy: from_json(&record.payload_after)?.ok_or_else(missing)? },
+			"restore" => AuditOperation::Restore { policy: from_json(&record.payload_before)?.ok_or_else(missing)? },
+			other => return Err(format!("Change {} has unknown operation {:?}", record.audit_id, other).into()),
+		};
+		Ok(Self::new(&record.event_uid, &record.list_id, &record.task_id, operation))
+	}
+}
+
+/// What undoing a run did, by the ID of each change (`AuditRecord::audit_id`)
+#[derive(Debug, Default)]
+pub struct UndoReport {
+	/// Changes that were undone
+	pub undone: Vec<i32>,
+	/// Changes that were left alone, and why
+	pub skipped: Vec<(i32, String)>,
+	/// Changes ClickUp gave an error for, and the error (these can be tried again by undoing the run again)
+	pub failed: Vec<(i32, String)>,
+}
+
+/// How undoing a single change went, if ClickUp didn't give an error
+enum Undo {
+	Done,
+	Skipped(String),
+}
+
+/// Undo the changes a sync run made to tasks
+/// ### Arguments
+/// * `clickup_api` - The instance of the ClickUp API to use
+/// * `context` - How the sync is set up, from the config
+/// * `store` - Where the mappings are kept, and each change is marked as undone as soon as it is (nothing new is recorded in the audit log while undoing)
+/// * `records` - The changes the run made that haven't been undone yet, oldest first
+/// * `changed_later` - IDs of the tasks later runs have changed, which are left alone
+/// ### Returns
+/// What was undone, or an error if the mappings couldn't be loaded or saved
+pub fn undo_run(clickup_api: &ClickUpApiInstance, context: &SyncContext, store: &mut dyn MappingStore, records: &[AuditRecord], changed_later: &HashSet<String>) -> Result<UndoReport, Box<dyn Error>> {
+	let mut mappings = store.load()?.into_iter()
+		.map(|mapping| ((mapping.calendar_id.clone(), mapping.list_id.clone()), mapping))
+		.collect::<HashMap<_, _>>();
+	let mut report = UndoReport::default();
+
+	// Newest first, so each change is undone on the task as that change left it
+	for record in records.iter().rev() {
+		if changed_later.contains(&record.task_id) {
+			report.skipped.push((record.audit_id, format!("task {} has been changed by a later run, undo that first", record.task_id)));
+			continue;
+		}
+		let entry = match AuditEntry::from_record(record) {
+			Ok(entry) => entry,
+			Err(err) => {
+				report.skipped.push((record.audit_id, err.to_string()));
+				continue;
+			}
+		};
+		match undo_change(clickup_api, context, store, &mut mappings, &entry) {
+			// Saved straight away, so if undoing stops partway, undoing the run again doesn't undo this twice
+			Ok(Undo::Done) => {
+				store.mark_undone(record.audit_id)?;
+				store.flush()?;
+				report.undone.push(record.audit_id);
+			}
+			Ok(Undo::Skipped(reason)) => report.skipped.push((record.audit_id, reason)),
+			// Errors from ClickUp only stop this change, but the mappings not saving stops everything
+			Err(err) => match err.downcast::<ClickUpError>() {
+				Ok(err) => report.failed.push((record.audit_id, err.to_string())),
+				Err(err) => return Err(err),
+			},
+		}
+	}
+	store.flush()?;
+	Ok(report)
+}
+
+/// Carry on if ClickUp made the change, or skip it if the task has been deleted since
+fn unless_gone(result: Result<(), ClickUpError>, task_id: &str) -> Result<Option<Undo>, ClickUpError> {
+	match result {
+		Ok(()) => Ok(None),
+		Err(err) if err.is_not_found() => Ok(Some(Undo::Skipped(format!("task {} has been deleted", task_id)))),
+		Err(err) => Err(err),
+	}
+}
+
+/// Undo a single change, in ClickUp and then in the mappings
+/// ### Arguments
+/// * `clickup_api` - The instance of the ClickUp API to use
+/// * `context` - How the sync is set up, from the config
+/// * `store` - Where to save the mappings
+/// * `mappings` - All the mappings, by (event UID, list ID)
+/// * `entry` - The change to undo
+fn undo_change(clickup_api: &ClickUpApiInstance, context: &SyncContext, store: &mut dyn MappingStore, mappings: &mut HashMap<(String, String), CalendarMapping>, entry: &AuditEntry) -> Result<Undo, Box<dyn Error>> {
+	let key = (entry.event_uid.clone(), entry.list_id.clone());
+	let is_the_task = |mapping: &&mut CalendarMapping| mapping.clickup_id == entry.task_id;
+
+	match &entry.operation {
+		AuditOperation::Create { .. } => {
+			match clickup_api.delete_task(&entry.task_id) {
+				Ok(()) => {}
+				Err(err) if err.is_not_found() => warn!("Task {} had already been deleted", entry.task_id),
+				Err(err) => return Err(err.into()),
+			}
+			if mappings.get(&key).is_some_and(|mapping| mapping.clickup_id == entry.task_id) {
+				let mapping = mappings.remove(&key).into_iter().collect::<Vec<_>>();
+				store.delete(&mapping)?;
+			}
+		}
+		AuditOperation::Update { before: None, .. } => {
+			return Ok(Undo::Skipped("what the sync wrote to the task before wasn't recorded".to_string()));
+		}
+		AuditOperation::Update { before: Some(before), after } => {
+			if let Some(skip) = unless_gone(context.revert_task(clickup_api, &entry.task_id, before, after), &entry.task_id)? {
+				return Ok(skip);
+			}
+			if let Some(mapping) = mappings.get_mut(&key).filter(is_the_task) {
+				mapping.last_synced = Some(synced_fields_to_json(&before.fields));
+				mapping.synced_assignees = assignees_to_json(&before.assignees);
+				// So the next run syncs the task from the calendar, rather than taking it as up to date
+				mapping.fingerprint = None;
+				store.update(mapping)?;
+			}
+		}
+		AuditOperation::Delete { before: None } => {
+			return Ok(Undo::Skipped("the task wasn't fetched before it was deleted, so can't be made again".to_string()));
+		}
+		AuditOperation::Delete { before: Some(before) } => {
+			if let Some(mapping) = mappings.get(&key) {
+				return Ok(Undo::Skipped(format!("the event has a task again ({})", mapping.clickup_id)));
+			}
+			let task_id = context.recreate_task(clickup_api, &entry.list_id, &entry.event_uid, before)?;
+			// Like an adopted task, as the sync didn't write the task as it is now
+			let mapping = store.insert(&entry.event_uid, &entry.list_id, &task_id, &BTreeSet::new(), None, None)?;
+			mappings.insert(key, mapping);
+		}
+		AuditOperation::Retire { policy } => {
+			if let Some(skip) = unless_gone(clickup_api.restore_task(&entry.task_id, &entry.list_id, policy), &entry.task_id)? {
+				return Ok(skip);
+			}
+			if let Some(mapping) = mappings.get_mut(&key).filter(is_the_task) {
+				mapping.tombstone = None;
+				store.update(mapping)?;
+			}
+		}
+		AuditOperation::Restore { policy } => {
+			if let Some(skip) = unless_gone(clickup_api.retire_task_with(&entry.task_id, policy), &entry.task_id)? {
+				return Ok(skip);
+			}
+			if let Some(mapping) = mappings.get_mut(&key).filter(is_the_task) {
+				mapping.tombstone = Some(tombstone_to_json(policy));
+				store.update(mapping)?;
+			}
+		}
+	}
+	info!("Undid {} of task {} for event ID {}", entry.columns().0, entry.task_id, entry.event_uid);
+	Ok(Undo::Done)
+}
@@ -0,0 +1,174 @@
+/// Authenticating with ClickUp as an OAuth app rather than with someone's personal access token
+///
+/// A personal token ties the sync to one committee member's account, so stops working when they leave. Instead, a ClickUp
+/// OAuth app can be authorised once with the `authorize` command: it prints a link to ClickUp's consent page, listens
+/// locally for ClickUp to redirect back with a code, swaps the code for a token, and stores the token in the database.
+/// The token is encrypted at rest with AES-256-GCM, using a key from the CLICKUP_TOKEN_KEY env var.
+/// Running `authorize` again (e.g. as whoever now looks after the sync) replaces the token, and the sync picks up
+/// the new one on its next run, so there's nothing to redeploy.
+use std::{
+	collections::HashMap,
+	env,
+	error::Error,
+	io::{self, BufRead, BufReader, Write},
+	net::{SocketAddr, TcpListener, TcpStream},
+};
+
+use aes_gcm::{aead::{Aead, AeadCore, KeyInit, OsRng}, Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use log::{debug, warn};
+use reqwest::Url;
+
+/// ClickUp's page where people authorise OAuth apps
+const CLICKUP_AUTHORIZE_URL: &str = "https://app.clickup.com/api";
+
+/// Length of the nonce AES-GCM needs, which we store in front of each encrypted token
+const NONCE_LEN: usize = 12;
+
+/// Encrypts & decrypts tokens for storing in the database
+pub struct TokenCipher {
+	cipher: Aes256Gcm,
+}
+
+impl TokenCipher {
+	/// Set up encryption with a key
+	/// ### Arguments
+	/// * `key` - 32 random bytes, base64 encoded (e.g. from `openssl rand -base64 32`)
+	pub fn new(key: &str) -> Result<Self, Box<dyn Error>> {
+		let key = BASE64.decode(key.trim()).map_err(|err| format!("Token key isn't valid base64: {}", err))?;
+		if key.len() != 32 {
+			return Err(format!("Token key must be 32 bytes, not {}", key.len()).into());
+		}
+		Ok(Self { cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)) })
+	}
+
+	/// Set up encryption with the key in the CLICKUP_TOKEN_KEY env var
+	pub fn from_env() -> Result<Self, Box<dyn Error>> {
+		Self::new(&env::var("CLICKUP_TOKEN_KEY").map_err(|_| "CLICKUP_TOKEN_KEY env var must be set to use OAuth")?)
+	}
+
+	/// Encrypt a token
+	/// ### Returns
+	/// The nonce followed by the encrypted token, base64 encoded
+	pub fn encrypt(&self, token: &str) -> String {
+		// A fresh nonce each time, as reusing one with the same key breaks AES-GCM
+		let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+		let encrypted = self.cipher.encrypt(&nonce, token.as_bytes()).expect("Failed to encrypt token");
+		BASE64.encode([nonce.as_slice(), &encrypted].concat())
+	}
+
+	/// Decrypt a token encrypted with `encrypt`
+	/// ### Returns
+	/// The token, or an error if it was encrypted with a different key or has been tampered with
+	pub fn decrypt(&self, encrypted: &str) -> Result<String, Box<dyn Error>> {
+		let bytes = BASE64.decode(encrypted)?;
+		if bytes.len() < NONCE_LEN {
+			return Err("Stored token is too short to be encrypted".into());
+		}
+		let (nonce, encrypted) = bytes.split_at(NONCE_LEN);
+		let token = self.cipher.decrypt(Nonce::from_slice(nonce), encrypted)
+			.map_err(|_| "Failed to decrypt stored token (has CLICKUP_TOKEN_KEY changed?)")?;
+		Ok(String::from_utf8(token)?)
+	}
+}
+
+/// A ClickUp OAuth app, see https://clickup.com/api/developer-portal/authentication/#oauth-flow
+pub struct OAuthApp {
+	/// Client ID of the app
+	pub client_id: String,
+	/// Client secret of the app
+	pub client_secret: String,
+	/// Where ClickUp sends people back to after authorising, e.g. http://localhost:8910/callback
+	/// (must match the redirect URL set on the app in ClickUp)
+	pub redirect_uri: String,
+}
+
+impl OAuthApp {
+	/// Load the app from the CLICKUP_OAUTH_CLIENT_ID, CLICKUP_OAUTH_CLIENT_SECRET & CLICKUP_OAUTH_REDIRECT_URI env vars
+	pub fn from_env() -> Result<Self, Box<dyn Error>> {
+		let var = |name: &str| env::var(name).map_err(|_| format!("{} env var must be set to use OAuth", name));
+		Ok(Self {
+			client_id: var("CLICKUP_OAUTH_CLIENT_ID")?,
+			client_secret: var("CLICKUP_OAUTH_CLIENT_SECRET")?,
+			redirect_uri: var("CLICKUP_OAUTH_REDIRECT_URI")?,
+		})
+	}
+
+	/// The link to ClickUp's page for authorising the app
+	pub fn authorize_url(&self) -> String {
+		Url::parse_with_params(CLICKUP_AUTHORIZE_URL, &[("client_id", &self.client_id), ("redirect_uri", &self.redirect_uri)])
+			.expect("Invalid authorize URL")
+			.to_string()
+	}
+}
+
+/// Listens for ClickUp to redirect the browser back to us with the authorisation code
+pub struct CallbackListener {
+	listener: TcpListener,
+	/// Path of the redirect URI, e.g. /callback
+	path: String,
+}
+
+impl CallbackListener {
+	/// Start listening on the host & port of the redirect URI
+	/// ### Arguments
+	/// * `redirect_uri` - The redirect URI of the app, e.g. http://localhost:8910/callback
+	pub fn bind(redirect_uri: &str) -> Result<Self, Box<dyn Error>> {
+		let url = Url::parse(redirect_uri)?;
+		let host = url.host_str().ok_or("Redirect URI must have a host")?;
+		let port = url.port_or_known_default().ok_or("Redirect URI must have a port")?;
+		Ok(Self { listener: TcpListener::bind((host, port))?, path: url.path().to_string() })
+	}
+
+	/// The address we're listening on
+	pub fn local_addr(&self) -> io::Result<SocketAddr> {
+		self.listener.local_addr()
+	}
+
+	/// Wait for the browser to come back from ClickUp
+	/// ### Returns
+	/// The authorisation code, or an error if the person didn't authorise the app
+	pub fn wait_for_code(&self) -> Result<String, Box<dyn Error>> {
+		for stream in self.listener.incoming() {
+			let mut stream = stream?;
+			let target = read_request_target(&stream)?;
+			debug!("OAuth callback listener got a request for {}", target);
+			let url = Url::parse("http://localhost")?.join(&target)?;
+
+			// e.g. the browser asking for /favicon.ico
+			if url.path() != self.path {
+				respond(&mut stream, "404 Not Found", "Not found")?;
+				continue;
+			}
+
+			let params = url.query_pairs().collect::<HashMap<_, _>>();
+			if let Some(code) = params.get("code") {
+				respond(&mut stream, "200 OK", "ClickUp calendar sync authorised! You can close this tab.")?;
+				return Ok(code.to_string());
+			}
+			respond(&mut stream, "400 Bad Request", "Authorisation failed, see the sync's logs")?;
+			let error = params.get("error").map(|error| error.to_string()).unwrap_or_else(|| "no code in callback".to_string());
+			return Err(format!("ClickUp didn't authorise the app: {}", error).into());
+		}
+		warn!("OAuth callback listener stopped");
+		Err("Stopped listening before ClickUp called back".into())
+	}
+}
+
+/// Read an HTTP request, returning the target from its request line (e.g. /callback?code=abc)
+fn read_request_target(stream: &TcpStream) -> io::Result<String> {
+	let mut reader = BufReader::new(stream);
+	let mut request_line = String::new();
+	reader.read_line(&mut request_line)?;
+	// Read the rest of the headers, so the browser isn't cut off whilst still sending them
+	let mut line = String::new();
+	while reader.read_line(&mut line)? > 0 && line.trim() != "" {
+		line.clear();
+	}
+	Ok(request_line.split_whitespace().nth(1).unwrap_or("/").to_string())
+}
+
+/// Send a plain text response & close the connection
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+	write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)
+}
@@ -0,0 +1,48 @@
+/// Describing what changed about a task when we update it, so it can be posted as a comment on the task
+///
+/// We already keep what we last wrote to each task (`SyncedFields`), so comparing that with what we've just written
+/// gives a field by field list of changes, e.g. "Start: Tue 1 Oct 18:00 → Wed 2 Oct 18:00".
+use chrono::DateTime;
+
+use crate::ownership::SyncedFields;
+
+/// List what changed between two versions of a task, one line per field
+/// ### Arguments
+/// * `before` - What we wrote to the task last time
+/// * `after` - What we've written to the task now
+/// ### Returns
+/// A line for each field that changed, or nothing if nothing did
+pub fn describe_changes(before: &SyncedFields, after: &SyncedFields) -> Vec<String> {
+	let mut changes = vec![];
+	if before.name != after.name {
+		changes.push(format!("Name: {} → {}", before.name, after.name));
+	}
+	if before.start_date != after.start_date {
+		changes.push(format!("Start: {} → {}", format_date(before.start_date), format_date(after.start_date)));
+	}
+	if before.due_date != after.due_date {
+		changes.push(format!("End: {} → {}", format_date(before.due_date), format_date(after.due_date)));
+	}
+	if before.location != after.location {
+		let location = |location: &Option<String>| location.clone().unwrap_or_else(|| "none".to_string());
+		changes.push(format!("Location: {} → {}", location(&before.location), location(&after.location)));
+	}
+	// Descriptions are too long to show both versions of
+	if before.description.trim() != after.description.trim() {
+		changes.push("Description updated".to_string());
+	}
+	changes
+}
+
+/// The comment to post for a list of changes
+pub fn change_comment(changes: &[String]) -> String {
+	let lines = changes.iter().map(|change| format!("- {}", change)).collect::<Vec<_>>();
+	format!("Updated from the calendar:\n{}", lines.join("\n"))
+}
+
+/// Format a date we wrote to a task (milliseconds since the epoch, in the calendar's time) for people to read
+fn format_date(millis: Option<i64>) -> String {
+	millis.and_then(DateTime::from_timestamp_millis)
+		.map(|date| date.naive_utc().format("%a %-d %b %H:%M").to_string())
+		.unwrap_or_else(|| "none".to_string())
+}
@@ -1,19 +1,62 @@
 /// ClickUp API wrapper (specifcally v2.0 of the ClickUp API)
-use chrono::Timelike; // needed for time().hour() so we can check if an event is all day
-use log::{debug, error, info, warn}; // logging
-use reqwest::{blocking::Client, header}; // for making requests to ClickUp
-use std::{env, num::NonZeroU32}; // env vars and NonZeroU32 for rate limiting
+use log::{debug, info, warn}; // logging
+use reqwest::{blocking::{Client, RequestBuilder, Response}, header, StatusCode}; // for making requests to ClickUp
+use std::{cell::{Cell, OnceCell, RefCell}, collections::BTreeSet, env, error::Error, fmt, num::NonZeroU32, thread, time::{Duration, SystemTime, UNIX_EPOCH}}; // env vars, errors, NonZeroU32 for rate limiting & time for rate limit resets
 use futures::executor::block_on; // janky way to wait for rate limiter by blocking the thread whilst we check if we can send a request
 
-// Import our own modules: docsoc_ical types and models
-use crate::{docsoc_ical::ParsedEvent, models::*};
+// Our own modules for the parts of the sync that need to know about the API
+use crate::{config::DeletionPolicy, ownership::TaskField, rate_limit::SharedBucket};
 
 // For serializing and deserializing JSON when we send & receive data from ClickUp
 use serde::{Serialize, Deserialize};
 
+// For hashing the access token into the key of the shared rate limit
+use sha2::{Digest, Sha256};
+
 // We use this crate to rate limit requests to ClickUp
 use governor::{clock::{QuantaClock, QuantaInstant}, middleware::NoOpMiddleware, state::{InMemoryState, NotKeyed}, Quota, RateLimiter};
 
+/// Base URL of the real ClickUp API, used unless CLICKUP_API_BASE_URL is set (e.g. to point at a fake ClickUp server for testing)
+pub const DEFAULT_CLICKUP_API_BASE_URL: &str = "https://api.clickup.com/api/v2";
+
+/// Errors that can occur when talking to ClickUp
+#[derive(Debug)]
+pub enum ClickUpError {
+	/// We couldn't send the request or read the response (e.g. network down, or ClickUp sent back JSON we didn't expect)
+	Request(reqwest::Error),
+	/// ClickUp responded, but with a non-success status code
+	Status {
+		/// HTTP status code ClickUp responded with
+		status: StatusCode,
+		/// Body of the response, which usually has ClickUp's "err" and "ECODE" fields in to explain what went wrong
+		body: String,
+	},
+}
+
+impl fmt::Display for ClickUpError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ClickUpError::Request(err) => write!(f, "Request to ClickUp failed: {}", err),
+			ClickUpError::Status { status, body } => write!(f, "ClickUp responded with {}: {}", status, body),
+		}
+	}
+}
+
+impl Error for ClickUpError {}
+
+impl ClickUpError {
+	/// Whether ClickUp said the thing we asked for doesn't exist (e.g. someone deleted the task)
+	pub fn is_not_found(&self) -> bool {
+		matches!(self, ClickUpError::Status { status: StatusCode::NOT_FOUND, .. })
+	}
+}
+
+impl From<reqwest::Error> for ClickUpError {
+	fn from(err: reqwest::Error) -> Self {
+		ClickUpError::Request(err)
+	}
+}
+
 /// Payload for creating/updating a task in ClickUp, adapted from the ClickUp API docs
 /// Not all fields are used, only the ones we need
 /// serde is used to automatically serialize and deserialize this struct to/from JSON
@@ -21,14 +64,239 @@ use governor::{clock::{QuantaClock, QuantaInstant}, middleware::NoOpMiddleware,
 /// 
 /// Check the ClickUp API docs for more info: https://clickup.com/api
 #[derive(Serialize, Deserialize, Debug)]
-struct CreateTaskPayload {
-	name: String,
-	description: String,
-	tags: Vec<String>,
-	due_date: Option<i64>,
-	due_date_time: bool,
-	start_date: Option<i64>,
-	start_date_time: bool,
+pub(crate) struct CreateTaskPayload {
+	pub(crate) name: String,
+	pub(crate) description: String,
+	pub(crate) tags: Vec<String>,
+	pub(crate) due_date: Option<i64>,
+	pub(crate) due_date_time: bool,
+	pub(crate) start_date: Option<i64>,
+	pub(crate) start_date_time: bool,
+	/// Custom fields to set (only used when creating a task, as ClickUp doesn't let you set them when updating)
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub(crate) custom_fields: Vec<CustomFieldValue>,
+	/// Assignees to set, or None to leave them alone
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) assignees: Option<AssigneesPayload>,
+	/// Status to set, or None to leave it alone
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) status: Option<String>,
+}
+
+impl CreateTaskPayload {
+	/// Serialise the payload, leaving out the given fields so that ClickUp leaves them alone
+	pub(crate) fn without(&self, skip: &[TaskField]) -> serde_json::Value {
+		let mut body = serde_json::to_value(self).expect("Failed to serialise task payload");
+		if let Some(body) = body.as_object_mut() {
+			for key in skip.iter().flat_map(|field| field.payload_keys()) {
+				body.remove(*key);
+			}
+		}
+		body
+	}
+}
+
+/// Assignees of a task, which ClickUp takes in a different form when creating vs updating a task
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum AssigneesPayload {
+	/// When creating a task, the user IDs to assign
+	Create(BTreeSet<i64>),
+	/// When updating a task, the user IDs to add & remove (anyone else already assigned stays assigned)
+	Update { add: BTreeSet<i64>, rem: BTreeSet<i64> },
+}
+
+/// Value of a custom field on a task, as sent to ClickUp when creating a task
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CustomFieldValue {
+	/// ID of the custom field
+	pub(crate) id: String,
+	/// Value of the field, in whatever form that type of field takes
+	pub(crate) value: serde_json::Value,
+}
+
+/// Definition of a custom field available on a list, see https://clickup.com/api/clickupreference/operation/GetAccessibleCustomFields/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomFieldDefinition {
+	/// ID of the custom field (a UUID)
+	pub id: String,
+	/// Name of the field as shown in ClickUp
+	pub name: String,
+	/// Type of the field, e.g. short_text, url, drop_down, labels, date, number
+	#[serde(rename = "type")]
+	pub field_type: String,
+	/// Type specific config, of which we only care about the options of dropdowns & labels
+	#[serde(default)]
+	pub type_config: CustomFieldTypeConfig,
+}
+
+/// Type specific config of a custom field
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CustomFieldTypeConfig {
+	/// Options for dropdown and label fields
+	#[serde(default)]
+	pub options: Vec<CustomFieldOption>,
+}
+
+/// An option of a dropdown or label custom field
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomFieldOption {
+	/// ID of the option (a UUID)
+	pub id: String,
+	/// Name of the option (ClickUp calls this "label" for label fields, and "name" for dropdowns)
+	#[serde(alias = "label")]
+	pub name: Option<String>,
+}
+
+impl CustomFieldDefinition {
+	/// Find the ID of the dropdown/label option with the given name (case insensitive)
+	pub fn option_id(&self, name: &str) -> Option<String> {
+		self.type_config.options.iter()
+			.find(|option| option.name.as_deref().is_some_and(|option_name| option_name.eq_ignore_ascii_case(name)))
+			.map(|option| option.id.clone())
+	}
+}
+
+/// A list in ClickUp, of which we only care about the statuses, see https://clickup.com/api/clickupreference/operation/GetList/
+#[derive(Serialize, Deserialize, Debug)]
+struct ListResponse {
+	#[serde(default)]
+	statuses: Vec<ListStatus>,
+}
+
+/// A status tasks in a list can have
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListStatus {
+	/// Name of the status, e.g. "to do"
+	pub status: String,
+	/// Type of the status, e.g. open, custom, closed
+	#[serde(rename = "type", default)]
+	pub status_type: String,
+}
+
+/// A member of a ClickUp workspace (ClickUp calls workspaces "teams" in the API)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TeamMember {
+	/// ClickUp user ID
+	pub id: i64,
+	/// Username (display name) of the user
+	pub username: Option<String>,
+	/// Email of the user
+	pub email: Option<String>,
+}
+
+/// Response from ClickUp when listing the workspaces we have access to, see https://clickup.com/api/clickupreference/operation/GetAuthorizedTeams/
+#[derive(Serialize, Deserialize, Debug)]
+struct TeamsResponse {
+	teams: Vec<Team>,
+}
+
+/// A workspace, as returned when listing workspaces
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Team {
+	/// ID of the workspace (the number after app.clickup.com/ in URLs)
+	pub id: String,
+	/// Name of the workspace
+	#[serde(default)]
+	pub name: String,
+	#[serde(default)]
+	members: Vec<TeamMemberWrapper>,
+}
+
+impl Team {
+	/// The members of the workspace
+	pub fn members(&self) -> impl Iterator<Item = &TeamMember> {
+		self.members.iter().map(|member| &member.user)
+	}
+}
+
+/// ClickUp wraps each member of a workspace in an object with the user in
+#[derive(Serialize, Deserialize, Debug)]
+struct TeamMemberWrapper {
+	user: TeamMember,
+}
+
+/// A space in a workspace, see https://clickup.com/api/clickupreference/operation/GetSpaces/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Space {
+	/// ID of the space
+	pub id: String,
+	/// Name of the space
+	pub name: String,
+}
+
+/// Response from ClickUp when listing the spaces in a workspace
+#[derive(Serialize, Deserialize, Debug)]
+struct SpacesResponse {
+	spaces: Vec<Space>,
+}
+
+/// A folder in a space, along with the lists in it, see https://clickup.com/api/clickupreference/operation/GetFolders/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Folder {
+	/// ID of the folder
+	pub id: String,
+	/// Name of the folder
+	pub name: String,
+	/// The lists in the folder
+	#[serde(default)]
+	pub lists: Vec<ListSummary>,
+}
+
+/// Response from ClickUp when listing the folders in a space
+#[derive(Serialize, Deserialize, Debug)]
+struct FoldersResponse {
+	folders: Vec<Folder>,
+}
+
+/// A list, as returned when listing the lists in a space or folder, see https://clickup.com/api/clickupreference/operation/GetFolderlessLists/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListSummary {
+	/// ID of the list (what goes in CLICKUP_TARGET_LIST_ID or a route)
+	pub id: String,
+	/// Name of the list
+	pub name: String,
+}
+
+/// Response from ClickUp when listing the lists directly in a space (not in a folder)
+#[derive(Serialize, Deserialize, Debug)]
+struct ListsResponse {
+	lists: Vec<ListSummary>,
+}
+
+/// A tag in a space, see https://clickup.com/api/clickupreference/operation/GetSpaceTags/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpaceTag {
+	/// Name of the tag
+	pub name: String,
+	/// Colour of the tag's text, e.g. "#ffffff"
+	pub tag_fg: Option<String>,
+	/// Colour of the tag's background, e.g. "#e50000"
+	pub tag_bg: Option<String>,
+}
+
+/// Response from ClickUp when listing the tags in a space
+#[derive(Serialize, Deserialize, Debug)]
+struct SpaceTagsResponse {
+	tags: Vec<SpaceTag>,
+}
+
+/// Response from ClickUp when listing the members of a list, see https://clickup.com/api/clickupreference/operation/GetListMembers/
+#[derive(Serialize, Deserialize, Debug)]
+struct ListMembersResponse {
+	members: Vec<TeamMember>,
+}
+
+/// Response from ClickUp when listing the custom fields of a list
+#[derive(Serialize, Deserialize, Debug)]
+struct CustomFieldsResponse {
+	fields: Vec<CustomFieldDefinition>,
+}
+
+/// Response from ClickUp when swapping an OAuth code for a token, see https://clickup.com/api/clickupreference/operation/GetAccessToken/
+#[derive(Serialize, Deserialize, Debug)]
+struct AccessTokenResponse {
+	access_token: String,
 }
 
 /// Response from ClickUp when creating a task
@@ -38,29 +306,239 @@ struct CreateTaskResponse {
 	id: String,
 }
 
+/// A task as returned by ClickUp when we GET it
+/// Again, only the fields we care about - see https://clickup.com/api/clickupreference/operation/GetTask/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClickUpTask {
+	/// ClickUp task id
+	pub id: String,
+	/// Name (title) of the task
+	pub name: String,
+	/// Description of the task (ClickUp sends null if there isn't one)
+	#[serde(default)]
+	pub description: Option<String>,
+	/// Start date of the task, as a string of milliseconds since the epoch (ClickUp sends dates as strings!)
+	#[serde(default)]
+	pub start_date: Option<String>,
+	/// Due date of the task, as a string of milliseconds since the epoch
+	#[serde(default)]
+	pub due_date: Option<String>,
+	/// ID of the workspace the task is in
+	#[serde(default)]
+	pub team_id: Option<String>,
+	/// Values of the custom fields on the task
+	#[serde(default)]
+	pub custom_fields: Vec<TaskCustomField>,
+	/// Status of the task
+	#[serde(default)]
+	pub status: Option<TaskStatus>,
+	/// Tags on the task
+	#[serde(default)]
+	pub tags: Vec<TaskTag>,
+	/// The list the task is in
+	#[serde(default)]
+	pub list: Option<TaskList>,
+	/// Whether the task is archived
+	#[serde(default)]
+	pub archived: bool,
+	/// People assigned to the task
+	#[serde(default)]
+	pub assignees: Vec<TeamMember>,
+	/// ID of the task this is a subtask of, if it is one
+	#[serde(default)]
+	pub parent: Option<String>,
+	/// Subtasks of the task (only sent when asked for, see `get_task_with_subtasks`)
+	#[serde(default)]
+	pub subtasks: Vec<ClickUpTask>,
+	/// Checklists on the task
+	#[serde(default)]
+	pub checklists: Vec<Checklist>,
+}
+
+/// A checklist on a task
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checklist {
+	/// ID of the checklist
+	pub id: String,
+	/// Name of the checklist
+	pub name: String,
+	/// Items on the checklist
+	#[serde(default)]
+	pub items: Vec<ChecklistItem>,
+}
+
+/// An item on a checklist
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChecklistItem {
+	/// ID of the item
+	pub id: String,
+	/// Name of the item
+	pub name: String,
+	/// Whether the item has been ticked off
+	#[serde(default)]
+	pub resolved: bool,
+}
+
+/// A task template in a workspace
+#[derive(Deserialize, Debug)]
+pub struct TaskTemplate {
+	/// ID of the template, e.g. "t-86b1a2b3c"
+	pub id: String,
+	/// Name of the template
+	#[serde(default)]
+	pub name: String,
+}
+
+/// Response from ClickUp when listing the task templates in a workspace
+#[derive(Deserialize, Debug)]
+struct TaskTemplatesResponse {
+	templates: Vec<TaskTemplate>,
+}
+
+/// A document in ClickUp Docs, as returned when we create one
+#[derive(Deserialize, Debug)]
+struct Doc {
+	/// ID of the document
+	id: String,
+}
+
+/// Response from ClickUp when creating a checklist
+#[derive(Deserialize, Debug)]
+struct ChecklistResponse {
+	checklist: Checklist,
+}
+
+/// The list a task is in
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskList {
+	/// ID of the list
+	pub id: String,
+}
+
+impl ClickUpTask {
+	/// Whether the task has been done (its status is a "done" or "closed" one)
+	pub fn is_done(&self) -> bool {
+		self.status.as_ref()
+			.and_then(|status| status.status_type.as_deref())
+			.is_some_and(|status_type| status_type == "done" || status_type == "closed")
+	}
+
+	/// The value of a custom field on the task as text, if it's set to some text
+	pub fn custom_field_text(&self, field_id: &str) -> Option<&str> {
+		self.custom_fields.iter()
+			.find(|field| field.id == field_id)
+			.and_then(|field| field.value.as_ref()?.as_str())
+	}
+}
+
+/// The status of a task
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskStatus {
+	/// Name of the status
+	pub status: String,
+	/// Type of the status: "open", "custom", "done" or "closed"
+	#[serde(rename = "type", default)]
+	pub status_type: Option<String>,
+}
+
+/// A tag on a task
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskTag {
+	/// Name of the tag
+	pub name: String,
+}
+
+/// The value of a custom field on a task
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskCustomField {
+	/// ID of the custom field
+	pub id: String,
+	/// Value of the field, whose shape depends on the type of field (missing if the field isn't set)
+	#[serde(default)]
+	pub value: Option<serde_json::Value>,
+}
+
+/// A page of tasks from GET /list/{list_id}/task
+#[derive(Deserialize, Debug)]
+struct TasksResponse {
+	tasks: Vec<ClickUpTask>,
+	/// Whether this is the last page (ClickUp sends up to 100 tasks per page)
+	#[serde(default)]
+	last_page: bool,
+}
+
 /// Wrapper around the ClickUp API
 pub struct ClickUpApiInstance {
 	/// The ID of the list in ClickUp to which we want to sync events
 	pub target_list_id: String,
+	/// Base URL of the ClickUp API that all endpoints are appended to, without a trailing slash
+	base_url: String,
 	/// The reqwest client we use to make requests to ClickUp (this ensure we dont have to set the auth headers every time)
 	client: Client,
 	/// Rate limiter to ensure we don't exceed the rate limit of the ClickUp API (usually 100 per minute, configurable via env var)
 	/// Note that when setting this up we take 20 off the limit the user sets to be safe
 	limiter: RateLimiter<NotKeyed, InMemoryState, QuantaClock, NoOpMiddleware<QuantaInstant>>,
+	/// The same limit (after taking 20 off), for the shared rate limit
+	rate_limit: u32,
+	/// Which bucket of the shared rate limit is ours: a hash of the access token, as ClickUp limits each token separately
+	rate_limit_key: String,
+	/// Rate limit shared with other processes using the same token, used instead of `limiter` if set (see `share_rate_limit`)
+	shared_limiter: RefCell<Option<Box<dyn SharedBucket>>>,
+	/// The workspaces we have access to with their members, fetched the first time we need them (see `get_teams`)
+	teams: OnceCell<Vec<Team>>,
+	/// Requests made so far this run
+	requests_sent: Cell<u32>,
 }
 impl ClickUpApiInstance {
 
 	/// Create a new instance of the ClickUp API wrapper
 	/// ### Arguments
-	/// * `access_token` - The access token for the ClickUp API (usually a personal access token - see https://clickup.com/api/developer-portal/authentication/)
+	/// * `access_token` - The access token for the ClickUp API (usually a personal access token - see https://clickup.com/api/developer-portal/authentication/),
+	///   or "Bearer " followed by an OAuth token (see auth.rs)
 	/// * `target_list_id` - The ID of the list in ClickUp to which we want to sync events. This can be found in the URL when viewing the list in ClickUp (right click list > Copy link)
-	/// 	E.g. for https://app.clickup.com/9015711748/v/li/901505370673 the list ID is 901505370673
+	///   E.g. for https://app.clickup.com/9015711748/v/li/901505370673 the list ID is 901505370673
+	///
+	/// The API base URL is read from the CLICKUP_API_BASE_URL env var, defaulting to the real ClickUp API
 	pub fn new(access_token: String, target_list_id: String) -> Self {
+		let base_url = env::var("CLICKUP_API_BASE_URL").unwrap_or_else(|_| DEFAULT_CLICKUP_API_BASE_URL.to_string());
+		Self::with_base_url(access_token, target_list_id, base_url)
+	}
+
+	/// Create a new instance of the ClickUp API wrapper that talks to the ClickUp API at `base_url`
+	/// (e.g. a fake ClickUp server in tests) rather than reading it from the environment
+	/// ### Arguments
+	/// * `access_token` - See `new`
+	/// * `target_list_id` - See `new`
+	/// * `base_url` - Base URL of the API, e.g. https://api.clickup.com/api/v2
+	pub fn with_base_url(access_token: String, target_list_id: String, base_url: String) -> Self {
+		Self::build(Some(access_token), target_list_id, base_url)
+	}
+
+	/// Create an instance of the ClickUp API wrapper without an access token, for getting one (see `exchange_oauth_code`).
+	/// Every other request will be refused by ClickUp.
+	///
+	/// The API base URL is read from the CLICKUP_API_BASE_URL env var, like `new`
+	pub fn unauthenticated() -> Self {
+		let base_url = env::var("CLICKUP_API_BASE_URL").unwrap_or_else(|_| DEFAULT_CLICKUP_API_BASE_URL.to_string());
+		Self::unauthenticated_with_base_url(base_url)
+	}
+
+	/// Create an instance of the ClickUp API wrapper without an access token that talks to the ClickUp API at `base_url`
+	/// ### Arguments
+	/// * `base_url` - See `with_base_url`
+	pub fn unauthenticated_with_base_url(base_url: String) -> Self {
+		Self::build(None, String::new(), base_url)
+	}
+
+	/// Set up the client & rate limiter, with the auth header if given a token
+	fn build(access_token: Option<String>, target_list_id: String, base_url: String) -> Self {
 		// Setup headers common to all requests (specifically the auth header)
 		let mut headers = header::HeaderMap::new();
-		let mut access_token_header = header::HeaderValue::from_str(&access_token).unwrap();
-		access_token_header.set_sensitive(true);
-		headers.insert(header::AUTHORIZATION, access_token_header);
+		if let Some(access_token) = &access_token {
+			let mut access_token_header = header::HeaderValue::from_str(access_token).unwrap();
+			access_token_header.set_sensitive(true);
+			headers.insert(header::AUTHORIZATION, access_token_header);
+		}
 
 		// Create a new reqwest client with the headers set
 		let client = Client::builder()
@@ -80,118 +558,649 @@ impl ClickUpApiInstance {
 
 		// Create a rate limiter with the limit
 		let limiter = RateLimiter::direct(Quota::per_minute(NonZeroU32::new(limit).unwrap()));
+		let rate_limit_key = format!("{:x}", Sha256::digest(access_token.unwrap_or_default()))[..16].to_string();
 
 		// Finally, init the obj
-		Self { target_list_id, client, limiter }
-	}
-
-	/// Private function to take a ParsedEvent (that we created when parsing the ical) and convert it into a CreateTaskPayload
-	fn mk_task_payload(&self, event: &ParsedEvent) -> CreateTaskPayload {
-
-		// NOTE: QUIRK: Google calendar encodes all day events as starting at 00:00:00 and ending at 00:00:00 the next day UTC
-		// Unfortunately, if you're in a non UTC timezone, such as BST, and you set these exact times as the start and end times in ClickUp
-		// ClickUp will show the event in its UI based on your timezone.
-		// For BST specifically, this results in the event showing as 1am to 1am the next day,
-		// which then shows up as a 2 day event in ClickUp (misleading visually!)
-		// 
-		// To fix this, we check if the event is all day and if it is, we tell clickup to ignore the times and roll the end date back by 1 day
-		// as ClickUp defaults to the end of a day if no time is provided
-
-		let mut is_all_day = false;
-		let mut actual_due_date = event.end_time.and_then(|time| Some(time.and_utc().timestamp_millis()));
-		// If end_time & start_time both happen to midnight, set is_all_day to true
-		if let Some(start_time) = event.start_time {
-			if let Some(end_time) = event.end_time {
-				if start_time.time().hour() == 0 && end_time.time().hour() == 0 {
-					is_all_day = true;
-				}
-			}
+		let base_url = base_url.trim_end_matches('/').to_string();
+		Self {
+			target_list_id,
+			base_url,
+			client,
+			limiter,
+			rate_limit: limit,
+			rate_limit_key,
+			shared_limiter: RefCell::new(None),
+			teams: OnceCell::new(),
+			requests_sent: Cell::new(0),
 		}
+	}
 
-		// If it's an all day event, set the due date to 1 day before the end time
-		if is_all_day {
-			const ONE_DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
-			actual_due_date = event.end_time.and_then(|time| Some(time.and_utc().timestamp_millis() - ONE_DAY_MILLIS));
-		}
+	/// How many requests this run has made so far
+	pub fn requests_sent(&self) -> u32 {
+		self.requests_sent.get()
+	}
 
-		// Create the payload
-		CreateTaskPayload {
-			name: event.summary.clone(),
-			description: event.description.clone(),
-			tags: vec![],
-			start_date: event.start_time.and_then(|time| Some(time.and_utc().timestamp_millis())),
-			start_date_time: event.start_time.is_some() && !is_all_day,
-			due_date: actual_due_date,
-			due_date_time: event.end_time.is_some() && !is_all_day,
-		}
+	/// Share the rate limit with every other process using the same token, by drawing from a shared token bucket
+	/// instead of this process's own rate limiter
+	/// ### Arguments
+	/// * `bucket` - Where the shared bucket is kept, e.g. `db::DbRateLimit` or `rate_limit::FileRateLimit`
+	pub fn share_rate_limit(&mut self, bucket: Box<dyn SharedBucket>) {
+		self.shared_limiter = RefCell::new(Some(bucket));
 	}
 
+	/// Build the full URL for an endpoint of the ClickUp API
+	/// ### Arguments
+	/// * `path` - Path of the endpoint, starting with a slash, e.g. /task/abc123
+	fn url(&self, path: &str) -> String {
+		format!("{}{}", self.base_url, path)
+	}
 
-	/// Create a clickup task for a given event
+	/// Build the full URL for an endpoint of the v3 ClickUp API, which lives next to v2 under the base URL
 	/// ### Arguments
-	/// * `event` - The parsed event from the iCal file we are mapping
-	/// ### Returns
-	/// The ClickUp tsk ID of the newly created task to be stored in the database
-	pub fn create_task(&self, event: &ParsedEvent) -> String {
-		debug!("Creating task for: {:?}", event);
+	/// * `path` - Path of the endpoint, starting with a slash, e.g. /workspaces/123/docs
+	fn v3_url(&self, path: &str) -> String {
+		let v3_base_url = self.base_url.strip_suffix("/v2").map(|base| format!("{}/v3", base)).unwrap_or_else(|| self.base_url.clone());
+		format!("{}{}", v3_base_url, path)
+	}
+
+	/// Block until we're allowed to send a request, going by the shared rate limit if there is one
+	///
+	/// If the shared rate limit can't be used (e.g. the database is down) we fall back to our own for the rest of the run, so the sync still runs.
+	fn wait_for_rate_limit(&self) {
+		let mut shared_limiter = self.shared_limiter.borrow_mut();
+		if let Some(bucket) = shared_limiter.as_mut() {
+			loop {
+				match bucket.try_take(&self.rate_limit_key, self.rate_limit) {
+					Ok(None) => return,
+					Ok(Some(wait)) => {
+						debug!("Shared rate limit reached, waiting {:?}", wait);
+						thread::sleep(wait);
+					}
+					Err(err) => {
+						warn!("Failed to use the shared rate limit, using this process's own for the rest of the run: {}", err);
+						*shared_limiter = None;
+						break;
+					}
+				}
+			}
+		}
+
 		// HACK: block the thread until we can send a request
 		// Ideally you'd want to use async/await here but I couldn't be bothered to do it properly as thi app is single threaded and doesn't use 
 		// async/await for simplicity
 		block_on(self.limiter.until_ready());
+	}
+
+	/// Send a request to ClickUp, waiting for the rate limiter first
+	///
+	/// If ClickUp tells us we've hit the rate limit anyway (429, e.g. because something else is using the same token)
+	/// we wait until the reset time in the X-RateLimit-Reset header and try once more.
+	/// ### Returns
+	/// The response if ClickUp responded with a success status code, or a ClickUpError otherwise
+	fn send(&self, request: RequestBuilder) -> Result<Response, ClickUpError> {
+		// Keep a copy around in case we need to retry (all our bodies are JSON so this always works)
+		let retry = request.try_clone();
+
+		self.wait_for_rate_limit();
+		self.requests_sent.set(self.requests_sent.get() + 1);
+		let mut response = request.send()?;
+
+		if response.status() == StatusCode::TOO_MANY_REQUESTS {
+			if let Some(retry) = retry {
+				let wait = rate_limit_reset_wait(&response);
+				warn!("Hit the ClickUp rate limit, waiting {:?} before retrying", wait);
+				thread::sleep(wait);
+				self.wait_for_rate_limit();
+				self.requests_sent.set(self.requests_sent.get() + 1);
+				response = retry.send()?;
+			}
+		}
+
+		if let Some(remaining) = response.headers().get("X-RateLimit-Remaining") {
+			debug!("ClickUp rate limit remaining: {:?}", remaining);
+		}
 
-		// Send the POST request to ClickUp to create a task
-		let post_req = self.client.post(format!("https://api.clickup.com/api/v2/list/{}/task", self.target_list_id))
-			.header(header::CONTENT_TYPE, "application/json")
-			.json(&self.mk_task_payload(event))
-			.send()
-			.expect("Failed to send POST request to ClickUp");
+		if response.status().is_success() {
+			Ok(response)
+		} else {
+			Err(ClickUpError::Status {
+				status: response.status(),
+				body: response.text().unwrap_or_default(),
+			})
+		}
+	}
+
+	/// Fetch a task from ClickUp
+	/// ### Arguments
+	/// * `id` - The ClickUp task ID
+	pub fn get_task(&self, id: &str) -> Result<ClickUpTask, ClickUpError> {
+		debug!("Fetching task with ID {}", id);
+
+		let get_req = self.send(self.client.get(self.url(&format!("/task/{}", id))))?;
+
+		Ok(get_req.json::<ClickUpTask>()?)
+	}
+
+	/// Fetch a task from ClickUp, along with its subtasks
+	/// ### Arguments
+	/// * `id` - The ClickUp task ID
+	pub fn get_task_with_subtasks(&self, id: &str) -> Result<ClickUpTask, ClickUpError> {
+		debug!("Fetching task with ID {} and its subtasks", id);
+
+		let get_req = self.send(self.client.get(self.url(&format!("/task/{}", id))).query(&[("include_subtasks", "true")]))?;
+
+		Ok(get_req.json::<ClickUpTask>()?)
+	}
+
+	/// Fetch every task in a list, including closed tasks & subtasks
+	/// ### Arguments
+	/// * `list_id` - The ClickUp list ID
+	/// * `archived` - Whether to fetch the archived tasks in the list instead of the unarchived ones
+	pub fn get_list_tasks(&self, list_id: &str, archived: bool) -> Result<Vec<ClickUpTask>, ClickUpError> {
+		let mut tasks = vec![];
+		// ClickUp pages the tasks, so keep going until it says we're on the last page
+		for page in 0.. {
+			debug!("Fetching page {} of tasks in list {}", page, list_id);
+			let get_req = self.send(
+				self.client.get(self.url(&format!("/list/{}/task", list_id)))
+					.query(&[("page", page.to_string().as_str()), ("include_closed", "true"), ("subtasks", "true"), ("archived", &archived.to_string())])
+			)?;
+			let res = get_req.json::<TasksResponse>()?;
+			let done = res.last_page || res.tasks.is_empty();
+			tasks.extend(res.tasks);
+			if done {
+				break;
+			}
+		}
+		Ok(tasks)
+	}
+
+	/// Create a task in a list
+	/// ### Arguments
+	/// * `list_id` - The ClickUp list ID
+	/// * `body` - The task, as ClickUp takes it (see `CreateTaskPayload`)
+	/// ### Returns
+	/// The ClickUp task ID of the new task
+	pub fn post_task(&self, list_id: &str, body: &serde_json::Value) -> Result<String, ClickUpError> {
+		let post_req = self.send(
+			self.client.post(self.url(&format!("/list/{}/task", list_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(body)
+		)?;
 
 		// Read task_id from response
-		let res = post_req.json::<CreateTaskResponse>().expect("Failed to parse response!");
+		Ok(post_req.json::<CreateTaskResponse>()?.id)
+	}
+
+	/// Create a task in a list from a ClickUp task template (ClickUp only takes a name when doing this)
+	/// ### Arguments
+	/// * `list_id` - The ClickUp list ID
+	/// * `template_id` - The ID of the task template
+	/// * `name` - Name of the new task
+	/// ### Returns
+	/// The ClickUp task ID of the new task
+	pub fn post_task_from_template(&self, list_id: &str, template_id: &str, name: &str) -> Result<String, ClickUpError> {
+		debug!("Creating task in list {} from task template {}", list_id, template_id);
+		let post_req = self.send(
+			self.client.post(self.url(&format!("/list/{}/taskTemplate/{}", list_id, template_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "name": name }))
+		)?;
+		Ok(post_req.json::<CreateTaskResponse>()?.id)
+	}
 
-		info!("Created task for {:?} with ID {}", event.summary, res.id);
+	/// Change the fields of a task (ClickUp leaves any fields not in the body alone)
+	/// ### Arguments
+	/// * `id` - The ClickUp task ID
+	/// * `body` - The fields to change, as ClickUp takes them
+	pub fn put_task(&self, id: &str, body: &serde_json::Value) -> Result<(), ClickUpError> {
+		self.send(
+			self.client.put(self.url(&format!("/task/{}", id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(body)
+		)?;
+		Ok(())
+	}
 
-		return res.id;
+	/// Make a document in ClickUp Docs, in a list
+	///
+	/// NOTE: Docs are only in the v3 API, which lives next to v2 under the base URL
+	/// ### Arguments
+	/// * `workspace_id` - The ID of the workspace the list is in
+	/// * `list_id` - The ClickUp list ID
+	/// * `name` - Name of the document
+	/// ### Returns
+	/// The ID of the new document
+	pub fn create_doc(&self, workspace_id: &str, list_id: &str, name: &str) -> Result<String, ClickUpError> {
+		let doc = self.send(
+			self.client.post(self.v3_url(&format!("/workspaces/{}/docs", workspace_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				// Parent type 6 is a list
+				.json(&serde_json::json!({ "name": name, "parent": { "id": list_id, "type": 6 }, "create_page": false }))
+		)?.json::<Doc>()?;
+		Ok(doc.id)
 	}
 
-	/// Update a task in ClickUp with the details of a given event
+	/// Add a page to a document in ClickUp Docs
 	/// ### Arguments
-	/// * `mapping` - The mapping between the event and the ClickUp task, taken from teh DB
-	/// * `event` - The parsed event from the iCal file we are mapping
-	pub fn update_task(&self, mapping: &CalendarMapping, event: &ParsedEvent) {
-		debug!("Updating task for: {:?}", event);
-		// HACK: See above
-		block_on(self.limiter.until_ready());
+	/// * `workspace_id` - The ID of the workspace the document is in
+	/// * `doc_id` - The ID of the document
+	/// * `name` - Name of the page
+	/// * `content` - What's on the page, in markdown
+	pub fn create_doc_page(&self, workspace_id: &str, doc_id: &str, name: &str, content: &str) -> Result<(), ClickUpError> {
+		self.send(
+			self.client.post(self.v3_url(&format!("/workspaces/{}/docs/{}/pages", workspace_id, doc_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "name": name, "content": content, "content_format": "text/md" }))
+		)?;
+		Ok(())
+	}
 
-		// Send the PUT request to ClickUp to update a task
-		let put_req = self.client.put(format!("https://api.clickup.com/api/v2/task/{}", mapping.clickup_id))
-			.header(header::CONTENT_TYPE, "application/json")
-			.json(&self.mk_task_payload(event))
-			.send()
-			.expect("Failed to send PUT request to ClickUp");
-		if put_req.status().is_success() {
-			info!("Updated task for {:?} with ID {}", event.summary, mapping.clickup_id);
+	/// Post a comment on a task
+	/// ### Arguments
+	/// * `task_id` - The ClickUp task ID
+	/// * `text` - The comment
+	/// * `mentions` - IDs of users to @mention at the end of the comment
+	pub fn post_comment(&self, task_id: &str, text: &str, mentions: &[i64]) -> Result<(), ClickUpError> {
+		debug!("Commenting on task {}: {}", task_id, text);
+		let payload = if mentions.is_empty() {
+			serde_json::json!({ "comment_text": text, "notify_all": false })
 		} else {
-			error!("Failed to update task for {:?} with ID {}", event.summary, mapping.clickup_id);
-			error!("Response: {:?}", put_req.text());
+			// Mentions need the rich text form of the comment, made of parts
+			let mut parts = vec![serde_json::json!({ "text": format!("{}\n", text) })];
+			for id in mentions {
+				parts.push(serde_json::json!({ "type": "tag", "user": { "id": id } }));
+				parts.push(serde_json::json!({ "text": " " }));
+			}
+			serde_json::json!({ "comment": parts, "notify_all": false })
+		};
+		self.send(
+			self.client.post(self.url(&format!("/task/{}/comment", task_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&payload)
+		)?;
+		Ok(())
+	}
+
+	/// Make a subtask under a task
+	/// ### Arguments
+	/// * `list_id` - The list the parent task is in
+	/// * `parent_id` - The ClickUp task ID of the parent task
+	/// * `name` - Name of the subtask
+	/// * `due_date` - Due date of the subtask in milliseconds since the epoch, if any
+	pub fn create_subtask(&self, list_id: &str, parent_id: &str, name: &str, due_date: Option<i64>) -> Result<(), ClickUpError> {
+		info!("Adding subtask {:?} to task {}", name, parent_id);
+		self.send(
+			self.client.post(self.url(&format!("/list/{}/task", list_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "name": name, "parent": parent_id, "due_date": due_date, "due_date_time": due_date.is_some() }))
+		)?;
+		Ok(())
+	}
+
+	/// Make a checklist on a task
+	/// ### Returns
+	/// The ID of the new checklist
+	pub fn create_checklist(&self, task_id: &str, name: &str) -> Result<String, ClickUpError> {
+		info!("Adding checklist {:?} to task {}", name, task_id);
+		let res = self.send(
+			self.client.post(self.url(&format!("/task/{}/checklist", task_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "name": name }))
+		)?;
+		Ok(res.json::<ChecklistResponse>()?.checklist.id)
+	}
+
+	/// Add an item to a checklist
+	pub fn create_checklist_item(&self, checklist_id: &str, name: &str) -> Result<(), ClickUpError> {
+		debug!("Adding checklist item {:?} to checklist {}", name, checklist_id);
+		self.send(
+			self.client.post(self.url(&format!("/checklist/{}/checklist_item", checklist_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "name": name }))
+		)?;
+		Ok(())
+	}
+
+	/// Rename an item on a checklist
+	pub fn rename_checklist_item(&self, checklist_id: &str, item_id: &str, name: &str) -> Result<(), ClickUpError> {
+		self.send(
+			self.client.put(self.url(&format!("/checklist/{}/checklist_item/{}", checklist_id, item_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "name": name }))
+		)?;
+		Ok(())
+	}
+
+	/// Get the members of all the workspaces we have access to (see `get_teams`)
+	pub fn get_team_members(&self) -> Result<Vec<TeamMember>, ClickUpError> {
+		Ok(self.get_teams()?.iter().flat_map(Team::members).cloned().collect())
+	}
+
+	/// Swap the code ClickUp gives an OAuth app when someone authorises it for an access token (see auth.rs)
+	///
+	/// This doesn't need the instance to have a token of its own (see `unauthenticated`)
+	/// ### Arguments
+	/// * `client_id` - Client ID of the app
+	/// * `client_secret` - Client secret of the app
+	/// * `code` - The code ClickUp sent to the redirect URI
+	pub fn exchange_oauth_code(&self, client_id: &str, client_secret: &str, code: &str) -> Result<String, ClickUpError> {
+		let post_req = self.send(
+			self.client.post(self.url("/oauth/token"))
+				.query(&[("client_id", client_id), ("client_secret", client_secret), ("code", code)])
+		)?;
+		Ok(post_req.json::<AccessTokenResponse>()?.access_token)
+	}
+
+	/// Get the task templates in a workspace
+	/// ### Arguments
+	/// * `team_id` - The ID of the workspace
+	pub fn get_task_templates(&self, team_id: &str) -> Result<Vec<TaskTemplate>, ClickUpError> {
+		let mut templates = vec![];
+		// ClickUp pages the templates, so keep going until we get an empty page
+		for page in 0.. {
+			let get_req = self.send(self.client.get(self.url(&format!("/team/{}/taskTemplate", team_id))).query(&[("page", page)]))?;
+			let res = get_req.json::<TaskTemplatesResponse>()?;
+			if res.templates.is_empty() {
+				break;
+			}
+			templates.extend(res.templates);
+		}
+		Ok(templates)
+	}
+
+	/// Get the workspaces we have access to, with their members
+	///
+	/// These are cached after the first call, as they rarely change & we look people up a lot
+	pub fn get_teams(&self) -> Result<&[Team], ClickUpError> {
+		if let Some(teams) = self.teams.get() {
+			return Ok(teams);
+		}
+
+		let get_req = self.send(self.client.get(self.url("/team")))?;
+		let teams = get_req.json::<TeamsResponse>()?.teams;
+		Ok(self.teams.get_or_init(|| teams))
+	}
+
+	/// Get the (unarchived) spaces in a workspace
+	/// ### Arguments
+	/// * `team_id` - The ID of the workspace
+	pub fn get_spaces(&self, team_id: &str) -> Result<Vec<Space>, ClickUpError> {
+		let get_req = self.send(self.client.get(self.url(&format!("/team/{}/space", team_id))).query(&[("archived", "false")]))?;
+		Ok(get_req.json::<SpacesResponse>()?.spaces)
+	}
+
+	/// Get the (unarchived) folders in a space, along with the lists in each
+	/// ### Arguments
+	/// * `space_id` - The ID of the space
+	pub fn get_folders(&self, space_id: &str) -> Result<Vec<Folder>, ClickUpError> {
+		let get_req = self.send(self.client.get(self.url(&format!("/space/{}/folder", space_id))).query(&[("archived", "false")]))?;
+		Ok(get_req.json::<FoldersResponse>()?.folders)
+	}
+
+	/// Get the (unarchived) lists directly in a space, that aren't in a folder
+	/// ### Arguments
+	/// * `space_id` - The ID of the space
+	pub fn get_folderless_lists(&self, space_id: &str) -> Result<Vec<ListSummary>, ClickUpError> {
+		let get_req = self.send(self.client.get(self.url(&format!("/space/{}/list", space_id))).query(&[("archived", "false")]))?;
+		Ok(get_req.json::<ListsResponse>()?.lists)
+	}
+
+	/// Get the (unarchived) lists in a folder
+	/// ### Arguments
+	/// * `folder_id` - The ID of the folder
+	pub fn get_folder_lists(&self, folder_id: &str) -> Result<Vec<ListSummary>, ClickUpError> {
+		let get_req = self.send(self.client.get(self.url(&format!("/folder/{}/list", folder_id))).query(&[("archived", "false")]))?;
+		Ok(get_req.json::<ListsResponse>()?.lists)
+	}
+
+	/// Create a list, in a folder if given or otherwise directly in the space
+	/// ### Arguments
+	/// * `space_id` - The ID of the space to create the list in, if not in a folder
+	/// * `folder_id` - The ID of the folder to create the list in
+	/// * `name` - Name of the list
+	/// ### Returns
+	/// The new list
+	pub fn create_list(&self, space_id: &str, folder_id: Option<&str>, name: &str) -> Result<ListSummary, ClickUpError> {
+		let path = match folder_id {
+			Some(folder_id) => format!("/folder/{}/list", folder_id),
+			None => format!("/space/{}/list", space_id),
+		};
+		let post_req = self.send(
+			self.client.post(self.url(&path))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "name": name }))
+		)?;
+		Ok(post_req.json::<ListSummary>()?)
+	}
+
+	/// Create a custom field on a list
+	/// ### Arguments
+	/// * `list_id` - The ClickUp list ID
+	/// * `name` - Name of the field
+	/// * `field_type` - Type of the field, e.g. short_text
+	/// * `options` - Options of a drop_down or labels field (ignored for other types)
+	pub fn create_custom_field(&self, list_id: &str, name: &str, field_type: &str, options: &[String]) -> Result<(), ClickUpError> {
+		// Label fields call the name of each option its "label"
+		let option_key = if field_type == "labels" { "label" } else { "name" };
+		let mut payload = serde_json::json!({ "name": name, "type": field_type });
+		if !options.is_empty() {
+			payload["type_config"] = serde_json::json!({
+				"options": options.iter().map(|option| serde_json::json!({ option_key: option })).collect::<Vec<_>>()
+			});
 		}
+		self.send(
+			self.client.post(self.url(&format!("/list/{}/field", list_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&payload)
+		)?;
+		Ok(())
+	}
+
+	/// Get the tags in a space
+	/// ### Arguments
+	/// * `space_id` - The ID of the space
+	pub fn get_space_tags(&self, space_id: &str) -> Result<Vec<SpaceTag>, ClickUpError> {
+		let get_req = self.send(self.client.get(self.url(&format!("/space/{}/tag", space_id))))?;
+		Ok(get_req.json::<SpaceTagsResponse>()?.tags)
+	}
+
+	/// Create a tag in a space
+	/// ### Arguments
+	/// * `space_id` - The ID of the space
+	/// * `tag` - The tag to create
+	pub fn create_space_tag(&self, space_id: &str, tag: &SpaceTag) -> Result<(), ClickUpError> {
+		self.send(
+			self.client.post(self.url(&format!("/space/{}/tag", space_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "tag": tag }))
+		)?;
+		Ok(())
+	}
+
+	/// Change the colours of a tag in a space
+	/// ### Arguments
+	/// * `space_id` - The ID of the space
+	/// * `tag` - The tag, with its new colours
+	pub fn edit_space_tag(&self, space_id: &str, tag: &SpaceTag) -> Result<(), ClickUpError> {
+		let mut url = reqwest::Url::parse(&self.url(&format!("/space/{}/tag", space_id))).expect("Invalid ClickUp API URL");
+		url.path_segments_mut().expect("Invalid ClickUp API URL").push(&tag.name);
+		// Editing uses different names for the colours to everywhere else
+		self.send(
+			self.client.put(url)
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "tag": { "name": tag.name, "fg_color": tag.tag_fg, "bg_color": tag.tag_bg } }))
+		)?;
+		Ok(())
+	}
+
+	/// Get the people who can see a list
+	/// ### Arguments
+	/// * `list_id` - The ClickUp list ID
+	pub fn get_list_members(&self, list_id: &str) -> Result<Vec<TeamMember>, ClickUpError> {
+		let get_req = self.send(self.client.get(self.url(&format!("/list/{}/member", list_id))))?;
+		Ok(get_req.json::<ListMembersResponse>()?.members)
+	}
+
+	/// Get the statuses tasks in a list can have
+	/// ### Arguments
+	/// * `list_id` - The ClickUp list ID
+	pub fn get_list_statuses(&self, list_id: &str) -> Result<Vec<ListStatus>, ClickUpError> {
+		let get_req = self.send(self.client.get(self.url(&format!("/list/{}", list_id))))?;
+		Ok(get_req.json::<ListResponse>()?.statuses)
+	}
+
+	/// Get the custom fields available on a list
+	/// ### Arguments
+	/// * `list_id` - The ClickUp list ID
+	pub fn get_custom_fields(&self, list_id: &str) -> Result<Vec<CustomFieldDefinition>, ClickUpError> {
+		let get_req = self.send(self.client.get(self.url(&format!("/list/{}/field", list_id))))?;
+		Ok(get_req.json::<CustomFieldsResponse>()?.fields)
+	}
+
+	/// Set the value of a custom field on a task
+	/// ### Arguments
+	/// * `task_id` - The ClickUp task ID
+	/// * `field_id` - The ID of the custom field
+	/// * `value` - The value of the field, in the form that type of field takes
+	pub fn set_custom_field(&self, task_id: &str, field_id: &str, value: &serde_json::Value) -> Result<(), ClickUpError> {
+		debug!("Setting custom field {} on task {} to {}", field_id, task_id, value);
+		self.send(
+			self.client.post(self.url(&format!("/task/{}/field/{}", task_id, field_id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "value": value }))
+		)?;
+		Ok(())
 	}
 
+	/// Clear the value of a custom field on a task
+	/// ### Arguments
+	/// * `task_id` - The ClickUp task ID
+	/// * `field_id` - The ID of the custom field
+	pub fn remove_custom_field(&self, task_id: &str, field_id: &str) -> Result<(), ClickUpError> {
+		debug!("Clearing custom field {} on task {}", field_id, task_id);
+		self.send(self.client.delete(self.url(&format!("/task/{}/field/{}", task_id, field_id))))?;
+		Ok(())
+	}
 
-	pub fn delete_task(&self, id: &String) {
+	/// Delete a task in ClickUp
+	/// ### Arguments
+	/// * `id` - The ClickUp task ID
+	pub fn delete_task(&self, id: &str) -> Result<(), ClickUpError> {
 		debug!("Deleting task with ID {}", id);
-		// HACK: See above
-		block_on(self.limiter.until_ready());
 
-		let delete_req = self.client.delete(format!("https://api.clickup.com/api/v2/task/{}", id))
-			.send()
-			.expect("Failed to send DELETE request to ClickUp");
-		
-		if delete_req.status().is_success() {
-			warn!("Deleted task with ID {}", id);
-		} else {
-			error!("Failed to delete task with ID {}", id);
-			error!("Response: {:?}", delete_req.text());
+		self.send(self.client.delete(self.url(&format!("/task/{}", id))))?;
+
+		warn!("Deleted task with ID {}", id);
+		Ok(())
+	}
+
+	/// Retire a task according to the given deletion policy, rather than the one in the config
+	/// (for undoing a run that restored the task, see audit.rs)
+	/// ### Arguments
+	/// * `id` - The ClickUp task ID
+	/// * `policy` - The deletion policy to apply
+	pub fn retire_task_with(&self, id: &str, policy: &DeletionPolicy) -> Result<(), ClickUpError> {
+		match policy {
+			DeletionPolicy::Delete => self.delete_task(id),
+			DeletionPolicy::Archive => self.set_archived(id, true),
+			DeletionPolicy::Status { status } => self.set_status(id, status),
+			DeletionPolicy::Tag { tag } => self.add_tag(id, tag),
+			DeletionPolicy::Move { list_id } => self.move_task(id, list_id),
+		}
+	}
+
+	/// Undo retiring a task, for when its event comes back into the calendar
+	/// ### Arguments
+	/// * `id` - The ClickUp task ID
+	/// * `list_id` - The ID of the list the task belongs in
+	/// * `tombstone` - The deletion policy that was applied to the task when it was retired
+	pub fn restore_task(&self, id: &str, list_id: &str, tombstone: &DeletionPolicy) -> Result<(), ClickUpError> {
+		match tombstone {
+			DeletionPolicy::Delete => {},
+			DeletionPolicy::Archive => self.set_archived(id, false)?,
+			DeletionPolicy::Status { .. } => {
+				// We don't know what the status was before, so put it back to the first open status of the list
+				// (if status rules are configured, the update that follows will set the right one anyway)
+				let list_statuses = self.get_list_statuses(list_id)?;
+				if let Some(open) = list_statuses.iter().find(|status| status.status_type == "open").or(list_statuses.first()) {
+					self.set_status(id, &open.status)?;
+				}
+			}
+			DeletionPolicy::Tag { tag } => self.remove_tag(id, tag)?,
+			DeletionPolicy::Move { .. } => self.move_task(id, list_id)?,
+		}
+		info!("Restored task with ID {}", id);
+		Ok(())
+	}
+
+	/// Archive or unarchive a task
+	fn set_archived(&self, id: &str, archived: bool) -> Result<(), ClickUpError> {
+		self.send(
+			self.client.put(self.url(&format!("/task/{}", id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "archived": archived }))
+		)?;
+		Ok(())
+	}
+
+	/// Set the status of a task
+	fn set_status(&self, id: &str, status: &str) -> Result<(), ClickUpError> {
+		self.send(
+			self.client.put(self.url(&format!("/task/{}", id)))
+				.header(header::CONTENT_TYPE, "application/json")
+				.json(&serde_json::json!({ "status": status }))
+		)?;
+		Ok(())
+	}
+
+	/// Add a tag to a task (the tag is created in the space if it doesn't exist)
+	pub fn add_tag(&self, id: &str, tag: &str) -> Result<(), ClickUpError> {
+		self.send(self.client.post(self.tag_url(id, tag)))?;
+		Ok(())
+	}
+
+	/// Remove a tag from a task
+	pub fn remove_tag(&self, id: &str, tag: &str) -> Result<(), ClickUpError> {
+		self.send(self.client.delete(self.tag_url(id, tag)))?;
+		Ok(())
+	}
+
+	/// URL for adding/removing a tag on a task, with the tag name escaped (tags can have spaces in)
+	fn tag_url(&self, id: &str, tag: &str) -> reqwest::Url {
+		let mut url = reqwest::Url::parse(&self.url(&format!("/task/{}/tag", id))).expect("Invalid ClickUp API URL");
+		url.path_segments_mut().expect("Invalid ClickUp API URL").push(tag);
+		url
+	}
+
+	/// Move a task to a different list
+	///
+	/// NOTE: The v2 API can't move tasks, so this uses the v3 API, which lives next to v2 under the base URL
+	/// ### Arguments
+	/// * `id` - The ClickUp task ID
+	/// * `list_id` - The ID of the list to move the task to
+	pub fn move_task(&self, id: &str, list_id: &str) -> Result<(), ClickUpError> {
+		// v3 needs the workspace ID, which is on the task
+		let workspace_id = self.get_task(id)?.team_id.unwrap_or_default();
+		self.send(self.client.put(self.v3_url(&format!("/workspaces/{}/tasks/{}/home_list/{}", workspace_id, id, list_id))))?;
+		Ok(())
+	}
+}
+
+/// Work out how long to wait before retrying a request that hit the rate limit,
+/// using the X-RateLimit-Reset header (a unix timestamp in seconds) ClickUp sends back
+///
+/// Falls back to waiting a minute (the length of the ClickUp rate limit window) if the header is missing
+fn rate_limit_reset_wait(response: &Response) -> Duration {
+	let reset_at = response.headers().get("X-RateLimit-Reset")
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.parse::<u64>().ok());
+
+	match reset_at {
+		Some(reset_at) => {
+			let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+			Duration::from_secs(reset_at.saturating_sub(now))
 		}
+		None => Duration::from_secs(60),
 	}
 }
\ No newline at end of file
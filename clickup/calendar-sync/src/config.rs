@@ -0,0 +1,451 @@
+/// Configuration for the sync that is too structured to go in env vars (e.g. lists of rules),
+/// loaded from a TOML file whose path is given by the CLICKUP_SYNC_CONFIG env var.
+///
+/// Everything in here is optional - with no config file the tool behaves as it always has.
+/// See sync-config.example.toml for an example.
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, error::Error, fs, path::PathBuf};
+
+/// Top level of the config file
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+	/// Custom fields on the target list to fill in from each event
+	pub custom_fields: Vec<CustomFieldConfig>,
+	/// Who to assign to the task for each event
+	pub assignees: AssigneesConfig,
+	/// Rules for setting the status of tasks based on where the event is in its lifecycle, in order (first match wins)
+	pub statuses: Vec<StatusRuleConfig>,
+	/// What to do with the task when its event disappears from the calendar
+	pub deletion: DeletionPolicy,
+	/// Which fields of the task the sync owns, and what to do when someone edits them in ClickUp
+	pub ownership: OwnershipConfig,
+	/// How the description of the task is written
+	pub description: DescriptionConfig,
+	/// Where on the task we record which event it is for
+	pub markers: MarkersConfig,
+	/// Which lists each event is synced to. If there are none, every event goes to CLICKUP_TARGET_LIST_ID.
+	pub routes: Vec<RouteConfig>,
+	/// What to do when a task the sync made has been deleted by hand in ClickUp
+	pub missing_tasks: MissingTasksConfig,
+	/// Whether to comment on tasks to say what changed when they're updated
+	pub comments: CommentsConfig,
+	/// Subtasks & checklist items to add to the tasks for matching events (e.g. "book room" two weeks before every social)
+	pub templates: Vec<TemplateConfig>,
+	/// ClickUp task templates to create tasks from, instead of making bare tasks
+	pub task_template: TaskTemplateConfig,
+	/// Marking tasks as made by the sync, and saying where they came from
+	pub provenance: ProvenanceConfig,
+	/// Meeting notes documents to make in ClickUp Docs for matching events
+	pub meeting_notes: MeetingNotesConfig,
+	/// How many requests to ClickUp each run may make
+	pub budget: BudgetConfig,
+	/// Whether to share the ClickUp rate limit with other processes using the same token
+	pub rate_limit: RateLimitConfig,
+	/// The list to set up with the `provision` command, if any
+	pub provision: Option<ProvisionConfig>,
+}
+
+/// Mapping of event data into a custom field on the target list
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CustomFieldConfig {
+	/// ID or name of the custom field in ClickUp
+	pub field: String,
+	/// Template for the value of the field, filled in from the event (see `ParsedEvent::render`), e.g. "{location}"
+	/// The result is converted to the type of the field, so for a date field this should render to a date
+	pub value: String,
+}
+
+/// Who to assign to the task for each event (see assignees.rs)
+/// People are given by ClickUp user ID, email or username
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssigneesConfig {
+	/// People to assign to tasks that no rule matches
+	pub default: Vec<String>,
+	/// People to assign to tasks in particular lists that no rule matches instead, by list ID
+	pub lists: HashMap<String, Vec<String>>,
+	/// Rules for assigning people based on the event. Everyone from every matching rule is assigned.
+	pub rules: Vec<AssigneeRuleConfig>,
+	/// Whether to also assign the attendees & organiser of the event, if their emails match members of the ClickUp workspace
+	pub match_attendees: bool,
+}
+
+impl AssigneesConfig {
+	/// Whether any assignment is configured at all (if not, we leave assignees alone entirely)
+	pub fn is_enabled(&self) -> bool {
+		!self.default.is_empty() || !self.lists.is_empty() || !self.rules.is_empty() || self.match_attendees
+	}
+}
+
+/// A rule for assigning people to tasks. If both `category` and `summary` are given, both must match.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AssigneeRuleConfig {
+	/// Category the event must have (case insensitive)
+	pub category: Option<String>,
+	/// Regex the summary of the event must match, e.g. "(?i)careers"
+	pub summary: Option<String>,
+	/// People to assign
+	pub assignees: Vec<String>,
+}
+
+/// A rule for syncing events to a list. If both `category` and `summary` are given, both must match,
+/// and if neither is given every event matches.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RouteConfig {
+	/// ID of the ClickUp list to sync matching events to
+	pub list_id: String,
+	/// Category the event must have (case insensitive)
+	pub category: Option<String>,
+	/// Regex the summary of the event must match, e.g. "(?i)careers"
+	pub summary: Option<String>,
+}
+
+/// Subtasks & checklist items to add to the task for matching events. If both `category` and `summary` are given,
+/// both must match, and if neither is given every event matches. Items from every matching template are added.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateConfig {
+	/// Category the event must have (case insensitive)
+	pub category: Option<String>,
+	/// Regex the summary of the event must match, e.g. "(?i)social"
+	pub summary: Option<String>,
+	/// Subtasks to make under the task
+	#[serde(default)]
+	pub subtasks: Vec<TemplateItemConfig>,
+	/// Items to add to a checklist on the task
+	#[serde(default)]
+	pub checklist: Vec<TemplateItemConfig>,
+}
+
+/// ClickUp task templates to create tasks from. The task gets everything from the template (description, checklists,
+/// custom fields etc.), and then the fields from the calendar are written over the top.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskTemplateConfig {
+	/// ID of the task template to create tasks from, e.g. "t-86b1a2b3c", or none to make bare tasks
+	pub id: Option<String>,
+	/// Task templates to use for tasks in particular lists instead, by list ID
+	pub lists: HashMap<String, String>,
+}
+
+/// Meeting notes documents to make in ClickUp Docs for matching events (see meeting_notes.rs)
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MeetingNotesConfig {
+	/// ID or name of a URL custom field to put the link to the document in. If not given, the link is posted as a comment on the task.
+	pub link_field: Option<String>,
+	/// Rules for which events get a document, in order (first match wins)
+	pub rules: Vec<MeetingNotesRuleConfig>,
+}
+
+/// A rule for making a meeting notes document. If both `category` and `summary` are given, both must match.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MeetingNotesRuleConfig {
+	/// Category the event must have (case insensitive), e.g. "Meeting"
+	pub category: Option<String>,
+	/// Regex the summary of the event must match, e.g. "(?i)committee meeting"
+	pub summary: Option<String>,
+	/// Name of the document, filled in from the event (see `ParsedEvent::render`), e.g. "Minutes: {summary} {start}"
+	pub title: String,
+	/// Contents of the document in markdown, filled in from the event, e.g. "Date: {start}\nAttendees: {attendees}"
+	pub template: String,
+}
+
+/// A subtask or checklist item in a template
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateItemConfig {
+	/// Name of the subtask or checklist item, e.g. "Book room"
+	pub name: String,
+	/// When it's due, relative to the start (or end, see `from`) of the event, e.g. "-14d", "+3d" or "-2h". None for no due date.
+	pub due: Option<String>,
+	/// Whether `due` is relative to the start or end of the event
+	#[serde(default)]
+	pub from: DueAnchor,
+}
+
+/// What the due date of a template item is relative to
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DueAnchor {
+	/// The start of the event
+	#[default]
+	Start,
+	/// The end of the event
+	End,
+}
+
+/// A rule for setting the status of a task based on the event, e.g. "this week" if it starts within 7 days
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StatusRuleConfig {
+	/// Status to set (must be one of the statuses of every list we sync to)
+	pub status: String,
+	/// When the rule applies
+	pub when: StatusCondition,
+	/// Number of days, for `starts_within`
+	pub days: Option<i64>,
+}
+
+/// When a status rule applies
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusCondition {
+	/// The event has ended
+	Ended,
+	/// The event has started but not yet ended
+	Started,
+	/// The event starts within the given number of `days`
+	StartsWithin,
+	/// Always (use as the last rule for a default status, e.g. "upcoming")
+	Always,
+}
+
+/// What to do with a task when its event disappears from the calendar
+///
+/// Apart from `Delete`, the mapping is kept (tombstoned) so that if the event comes back, it is linked back up to the same task.
+/// This is also stored with the tombstoned mapping, so we know how to undo it even if the config changes in the meantime.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum DeletionPolicy {
+	/// Permanently delete the task (the default, and how the sync has always worked)
+	#[default]
+	Delete,
+	/// Archive the task
+	Archive,
+	/// Set the status of the task, e.g. to "cancelled"
+	Status {
+		/// Status to set (must be one of the statuses of every list we sync to)
+		status: String,
+	},
+	/// Add a tag to the task, e.g. "cancelled"
+	Tag {
+		/// Tag to add
+		tag: String,
+	},
+	/// Move the task to another list, e.g. a graveyard list
+	Move {
+		/// ID of the list to move the task to
+		list_id: String,
+	},
+}
+
+/// Which fields of tasks the sync owns, and how to handle people editing them in ClickUp (see ownership.rs)
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct OwnershipConfig {
+	/// Name (title) of the task
+	pub name: FieldOwnership,
+	/// Description of the task
+	pub description: FieldOwnership,
+	/// Start & due dates of the task
+	pub dates: FieldOwnership,
+	/// Whether to fetch the task before updating it, to check if anyone has changed the fields we own since we last wrote them
+	pub detect_drift: bool,
+	/// What to do when someone has changed a field we own (only used with `detect_drift`)
+	pub on_conflict: ConflictPolicy,
+}
+
+/// When the sync writes a field of a task
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldOwnership {
+	/// Write it when creating the task and on every sync
+	#[default]
+	Always,
+	/// Only write it when creating the task, after which it belongs to whoever edits it in ClickUp
+	OnCreate,
+	/// Never write it (except the name when creating a task, as ClickUp needs one)
+	Never,
+}
+
+/// What to do when someone has changed a field of a task the sync owns
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+	/// Log the conflict and leave their edit alone
+	#[default]
+	Keep,
+	/// Log the conflict and overwrite their edit with the calendar
+	Overwrite,
+}
+
+/// Commenting on tasks to say what changed when they're updated (see changes.rs)
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommentsConfig {
+	/// Whether to post a comment listing what changed (e.g. the start time) each time a task is updated
+	pub on_update: bool,
+	/// Whether to @mention the people assigned to the task in the comment, so they get notified
+	pub mention_assignees: bool,
+}
+
+/// How many requests to ClickUp each run may make. Once the budget is used up the run stops, and the rest of the work
+/// is done next run (see sync.rs for the order work is done in).
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct BudgetConfig {
+	/// Most requests a run should make, or None for no limit. The operation (e.g. creating a task) that uses up the budget is
+	/// finished first, so a run can go a few requests over.
+	pub max_requests: Option<u32>,
+}
+
+/// Where the ClickUp rate limit is kept, so it can be shared by every process using the same token (see rate_limit.rs)
+/// The limit itself is still set by CLICKUP_RATE_LIMIT_PER_MIN, which every process should agree on.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(tag = "store", rename_all = "snake_case")]
+pub enum RateLimitConfig {
+	/// In this process's memory, so it isn't shared (the default)
+	#[default]
+	Memory,
+	/// In the database (DATABASE_URL), shared by processes on any host (or, with SQLite, the same host)
+	#[serde(alias = "postgres")]
+	Database,
+	/// In a lock file, shared by processes on the same host
+	File {
+		/// Where the file is, e.g. /var/lock/clickup-rate-limit
+		path: PathBuf,
+	},
+}
+
+/// Marking tasks as made by the sync, and saying where they came from (see provenance.rs)
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProvenanceConfig {
+	/// Whether to add a footer to the description of each task, saying which calendar it came from & that the sync manages it
+	pub footer: bool,
+	/// Name of the calendar for the footer, if not the name in the iCal file (X-WR-CALNAME)
+	pub calendar_name: Option<String>,
+	/// Tag to put on every task the sync makes, marking it as the sync's. Tasks without the tag (or the UID marker field) are never deleted.
+	pub marker_tag: Option<String>,
+}
+
+/// What to do when a task the sync made has been deleted by hand in ClickUp
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct MissingTasksConfig {
+	/// What to do with the event (unlink if not given, or recreate in stateless mode, where unlink can't be used)
+	pub policy: Option<MissingTaskPolicy>,
+	/// Whether to list the tasks in each list at the start of each sync, to find missing & moved tasks up front
+	/// rather than one by one when updating them
+	pub verify: bool,
+}
+
+/// What to do with an event whose task has been deleted by hand
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingTaskPolicy {
+	/// Mark the mapping as deleted by hand, and stop syncing the event (until it is deleted from the calendar)
+	#[default]
+	Unlink,
+	/// Make a new task for the event
+	Recreate,
+}
+
+/// How the description of tasks is written (see description.rs)
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DescriptionConfig {
+	/// Whether to only write the description from the calendar between the start & end markers,
+	/// keeping anything people write outside them in ClickUp
+	pub managed_block: bool,
+	/// Line marking the start of the part of the description from the calendar
+	pub start_marker: String,
+	/// Line marking the end of the part of the description from the calendar
+	pub end_marker: String,
+}
+
+impl Default for DescriptionConfig {
+	fn default() -> Self {
+		Self {
+			managed_block: false,
+			start_marker: "--- synced from calendar ---".to_string(),
+			end_marker: "--- end of synced section ---".to_string(),
+		}
+	}
+}
+
+/// Where on the task we record which event it is for, so tasks can be matched back up to events
+/// without the database (e.g. by `adopt`)
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarkersConfig {
+	/// ID or name of a text custom field on the target list to write the iCal UID of the event into
+	pub uid_field: Option<String>,
+	/// ID or name of a text custom field on the target list to write a fingerprint of the event into,
+	/// so that in stateless mode tasks whose events haven't changed can be skipped
+	pub fingerprint_field: Option<String>,
+	/// Keep the mappings on the tasks themselves (using the fields above) instead of in the database,
+	/// rebuilding them each run by listing the tasks in the target list. Needs `uid_field`.
+	pub stateless: bool,
+}
+
+/// A list to create or update with the `provision` command, so each year's calendar list can be set up the same way.
+/// Provisioning only ever adds things (or fixes tag colours), so running it again changes nothing.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProvisionConfig {
+	/// ID of the space the list goes in (tags are set up on the space too)
+	pub space_id: String,
+	/// ID of the folder in the space to put the list in, or none to put it directly in the space
+	pub folder_id: Option<String>,
+	/// Name of the list, e.g. "Events 2025/26". If a list with this name is already there it is used, otherwise one is made.
+	pub list_name: String,
+	/// Statuses the list must have. ClickUp's API can't add statuses, so any missing ones are reported to add by hand.
+	pub statuses: Vec<String>,
+	/// Tags the space should have
+	pub tags: Vec<TagConfig>,
+	/// Custom fields the list should have
+	pub custom_fields: Vec<CustomFieldSpec>,
+}
+
+/// A tag to make in a space
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TagConfig {
+	/// Name of the tag (ClickUp lower cases tag names)
+	pub name: String,
+	/// Colour of the tag's text, e.g. "#ffffff"
+	pub fg_color: Option<String>,
+	/// Colour of the tag's background, e.g. "#e50000"
+	pub bg_color: Option<String>,
+}
+
+/// A custom field to make on a list
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CustomFieldSpec {
+	/// Name of the field
+	pub name: String,
+	/// Type of the field, e.g. short_text, url, drop_down, labels, date, number, checkbox
+	#[serde(rename = "type")]
+	pub field_type: String,
+	/// Options of a drop_down or labels field
+	#[serde(default)]
+	pub options: Vec<String>,
+}
+
+/// Load the config file from the path in the CLICKUP_SYNC_CONFIG env var,
+/// or the default (empty) config if it isn't set
+pub fn load_config() -> Result<SyncConfig, Box<dyn Error>> {
+	match env::var("CLICKUP_SYNC_CONFIG") {
+		Ok(path) if !path.is_empty() => {
+			info!("Loading sync config from {}", path);
+			let contents = fs::read_to_string(&path)
+				.map_err(|err| format!("Failed to read config file {}: {}", path, err))?;
+			parse_config(&contents)
+		}
+		_ => Ok(SyncConfig::default()),
+	}
+}
+
+/// Parse the contents of a config file
+pub fn parse_config(contents: &str) -> Result<SyncConfig, Box<dyn Error>> {
+	Ok(toml::from_str(contents)?)
+}
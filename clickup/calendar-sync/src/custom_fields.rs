@@ -0,0 +1,106 @@
+/// Filling in ClickUp custom fields from event data
+///
+/// The config says which custom field gets which template, e.g. the "Location" field gets "{location}".
+/// When we start up we match these to the actual custom fields on the target list, so that we know each field's
+/// ID and type, and then for each event we render the template and convert the text into the right type of value
+/// for the field (e.g. a dropdown needs the ID of the option, not its name).
+use chrono::NaiveDateTime;
+use log::warn;
+use serde_json::{json, Value};
+
+use crate::{
+	clickup::CustomFieldDefinition,
+	config::CustomFieldConfig,
+	docsoc_ical::{ParsedEvent, EVENT_DATE_FORMAT},
+};
+
+/// Types of custom field we know how to fill in
+const SUPPORTED_TYPES: [&str; 10] = ["short_text", "text", "url", "email", "phone", "drop_down", "labels", "date", "number", "checkbox"];
+
+/// A custom field from the config, matched to its definition on the target list
+#[derive(Debug, Clone)]
+pub struct CustomFieldMapping {
+	/// The field in ClickUp
+	pub definition: CustomFieldDefinition,
+	/// Template to render the value of the field from
+	pub template: String,
+}
+
+/// Match up the custom fields in the config with the custom fields on the target list
+/// ### Arguments
+/// * `config` - The custom fields from the config, referencing fields by ID or name
+/// * `definitions` - The custom fields available on the target list, from ClickUp
+/// ### Returns
+/// A mapping for each field in the config, or an error if a field can't be found or is of a type we can't fill in
+pub fn resolve_custom_fields(config: &[CustomFieldConfig], definitions: &[CustomFieldDefinition]) -> Result<Vec<CustomFieldMapping>, String> {
+	config.iter().map(|field_config| {
+		// Prefer an exact ID match, then fall back to matching by name
+		let definition = definitions.iter().find(|def| def.id == field_config.field)
+			.or_else(|| definitions.iter().find(|def| def.name.eq_ignore_ascii_case(&field_config.field)))
+			.ok_or_else(|| format!("No custom field with ID or name {:?} on the list", field_config.field))?;
+
+		if !SUPPORTED_TYPES.contains(&definition.field_type.as_str()) {
+			return Err(format!("Custom field {:?} is of type {}, which we can't fill in", definition.name, definition.field_type));
+		}
+
+		Ok(CustomFieldMapping {
+			definition: definition.clone(),
+			template: field_config.value.clone(),
+		})
+	}).collect()
+}
+
+impl CustomFieldMapping {
+	/// Work out the value of the field for an event, ready to send to ClickUp
+	/// ### Returns
+	/// The value, or None if the field should be empty (either because the template rendered to nothing,
+	/// or because the text couldn't be converted to the type of the field, in which case we log a warning)
+	pub fn value_for(&self, event: &ParsedEvent) -> Option<Value> {
+		let text = event.render(&self.template);
+		let text = text.trim();
+		if text.is_empty() {
+			return None;
+		}
+
+		let value = convert_value(&self.definition, text);
+		if value.is_none() {
+			warn!("Couldn't convert {:?} into a value for the {} field {:?} for event {:?}", text, self.definition.field_type, self.definition.name, event.summary);
+		}
+		value
+	}
+}
+
+/// Convert text into a value for a custom field of the given type, see https://clickup.com/api/developer-portal/customfields/
+fn convert_value(definition: &CustomFieldDefinition, text: &str) -> Option<Value> {
+	match definition.field_type.as_str() {
+		"short_text" | "text" | "url" | "email" | "phone" => Some(json!(text)),
+		// Dropdowns take the ID of the option
+		"drop_down" => definition.option_id(text).map(|id| json!(id)),
+		// Labels take a list of option IDs, and we let people give multiple labels separated by commas
+		"labels" => {
+			let ids = text.split(',')
+				.map(str::trim)
+				.filter(|label| !label.is_empty())
+				.map(|label| {
+					let id = definition.option_id(label);
+					if id.is_none() {
+						warn!("No label {:?} on custom field {:?}", label, definition.name);
+					}
+					id
+				})
+				.collect::<Option<Vec<_>>>()?;
+			Some(json!(ids))
+		}
+		// Dates are milliseconds since the epoch, and we accept them in the format we render dates in, or just a date
+		"date" => NaiveDateTime::parse_from_str(text, EVENT_DATE_FORMAT).ok()
+			.or_else(|| chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))
+			.map(|date| json!(date.and_utc().timestamp_millis())),
+		"number" => text.parse::<f64>().ok().map(|number| json!(number)),
+		"checkbox" => match text.to_ascii_lowercase().as_str() {
+			"true" | "yes" | "1" => Some(json!(true)),
+			"false" | "no" | "0" => Some(json!(false)),
+			_ => None,
+		},
+		_ => None,
+	}
+}
@@ -1,30 +1,98 @@
 /// Common functions for database operations
-use diesel::pg::{Pg, PgConnection};
+///
+/// Mappings are kept in Postgres, or in SQLite when built with the `sqlite` feature. Everything here works with either,
+/// through `DbConnection`; the few places the SQL differs are marked with `cfg(feature = "sqlite")`.
 use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::upsert::excluded;
 use dotenvy::dotenv;
-use std::{env, error::Error};
-use log::debug;
+use std::{collections::{BTreeSet, HashMap, HashSet}, env, error::Error, mem, time::Duration};
+use log::{debug, error};
+use chrono::{NaiveDateTime, Utc};
+
+use crate::{audit::AuditEntry, auth::TokenCipher, models::*, ownership::SyncedFields, rate_limit::{now_millis, Bucket, SharedBucket}, schema::clickup_ical_mapping::dsl::*, store::MappingStore, sync::SyncStats};
 
 // Allow us to embed migrations in the binary
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 
 
-/// Establish a connection to the database using the DATABASE_URL env var
-pub fn establish_connection() -> PgConnection {
+#[cfg(not(any(feature = "postgres", feature = "sqlite")))]
+compile_error!("Build with the `postgres` or `sqlite` feature, to choose which database to keep mappings in");
+
+/// Connection to the database the tool was built for: Postgres, unless built with the `sqlite` feature
+#[cfg(not(feature = "sqlite"))]
+pub type DbConnection = diesel::pg::PgConnection;
+/// Connection to the database the tool was built for: SQLite, as it was built with the `sqlite` feature
+#[cfg(feature = "sqlite")]
+pub type DbConnection = diesel::sqlite::SqliteConnection;
+
+/// The kind of database `DbConnection` connects to
+pub type DbBackend = <DbConnection as Connection>::Backend;
+
+/// Pool of connections to the database, shared by everything that uses the database in a run
+pub type DbPool = Pool<ConnectionManager<DbConnection>>;
+
+/// Most connections the pool opens, unless DATABASE_POOL_SIZE says otherwise.
+/// The sync is single threaded and never holds onto a connection, so one is enough (which suits hosted Postgres with connection limits).
+const DEFAULT_POOL_SIZE: u32 = 1;
+
+/// How long a connection to SQLite waits for another process to finish writing (e.g. to the rate limit) before giving up
+#[cfg(feature = "sqlite")]
+const SQLITE_BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sets up each new connection to SQLite to wait for other processes' writes, rather than failing straight away
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+struct SqliteSetup;
+
+#[cfg(feature = "sqlite")]
+impl diesel::r2d2::CustomizeConnection<DbConnection, diesel::r2d2::Error> for SqliteSetup {
+	fn on_acquire(&self, connection: &mut DbConnection) -> Result<(), diesel::r2d2::Error> {
+		diesel::sql_query(format!("PRAGMA busy_timeout = {}", SQLITE_BUSY_TIMEOUT.as_millis()))
+			.execute(connection)
+			.map_err(diesel::r2d2::Error::QueryError)?;
+		Ok(())
+	}
+}
+
+/// Set up a pool of connections to the database using the DATABASE_URL env var (and DATABASE_POOL_SIZE, if set)
+/// (for SQLite, DATABASE_URL is the path to the database file, which is made if it doesn't exist)
+pub fn connection_pool() -> Result<DbPool, Box<dyn Error>> {
 	debug!("Connecting to database...");
 
+	let database_url = database_url()?;
+	let pool_size = match env::var("DATABASE_POOL_SIZE") {
+		Ok(pool_size) => pool_size.parse().map_err(|_| "DATABASE_POOL_SIZE must be a number")?,
+		Err(_) => DEFAULT_POOL_SIZE,
+	};
+	connection_pool_for(&database_url, pool_size)
+}
+
+/// URL of the database from the DATABASE_URL env var (or .env file)
+fn database_url() -> Result<String, Box<dyn Error>> {
 	dotenv().ok();
+	Ok(env::var("DATABASE_URL").map_err(|_| "DATABASE_URL must be set")?)
+}
 
-	let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-	PgConnection::establish(&database_url)
-			.unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+/// Set up a pool of connections to a database
+/// ### Arguments
+/// * `database_url` - URL of the database (for SQLite, the path to the database file)
+/// * `pool_size` - Most connections the pool opens
+pub fn connection_pool_for(database_url: &str, pool_size: u32) -> Result<DbPool, Box<dyn Error>> {
+	let builder = Pool::builder().max_size(pool_size);
+	#[cfg(feature = "sqlite")]
+	let builder = builder.connection_customizer(Box::new(SqliteSetup));
+	Ok(builder.build(ConnectionManager::new(database_url))?)
 }
 
 
-// Embed migrations in binary
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations/");
+// Embed migrations in binary (each database has its own, for the same schema)
+#[cfg(not(feature = "sqlite"))]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations/postgres/");
+#[cfg(feature = "sqlite")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations/sqlite/");
 /// Run the necessary migrations on the database (ran on boot)
-pub fn run_migrations(connection: &mut impl MigrationHarness<Pg>) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+pub fn run_migrations(connection: &mut impl MigrationHarness<DbBackend>) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
 
     // This will run the necessary migrations.
     //
@@ -33,4 +101,408 @@ pub fn run_migrations(connection: &mut impl MigrationHarness<Pg>) -> Result<(),
     connection.run_pending_migrations(MIGRATIONS)?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Encrypt & save a new OAuth token (see auth.rs), replacing any older ones
+/// ### Arguments
+/// * `connection` - Connection to the database
+/// * `cipher` - What to encrypt the token with (from CLICKUP_TOKEN_KEY)
+/// * `token` - The token
+pub fn save_oauth_token(connection: &mut DbConnection, cipher: &TokenCipher, token: &str) -> Result<(), diesel::result::Error> {
+	use crate::schema::clickup_oauth_token::dsl as oauth;
+
+	let token = cipher.encrypt(token);
+	connection.transaction(|connection| {
+		let new_token_id = diesel::insert_into(oauth::clickup_oauth_token)
+			.values(oauth::encrypted_token.eq(&token))
+			.returning(oauth::token_id)
+			.get_result::<i32>(connection)?;
+		// Old tokens are no use once we have a new one, so don't leave them lying around
+		diesel::delete(oauth::clickup_oauth_token.filter(oauth::token_id.ne(new_token_id))).execute(connection)?;
+		Ok(())
+	})
+}
+
+/// Load & decrypt the newest OAuth token, if the `authorize` command has been run
+/// ### Arguments
+/// * `connection` - Connection to the database
+/// * `cipher` - What the token was encrypted with (from CLICKUP_TOKEN_KEY)
+/// ### Returns
+/// The token, or an error if it can't be decrypted (e.g. CLICKUP_TOKEN_KEY has changed since it was saved)
+pub fn load_oauth_token(connection: &mut DbConnection, cipher: &TokenCipher) -> Result<Option<String>, Box<dyn Error>> {
+	use crate::schema::clickup_oauth_token::dsl as oauth;
+
+	let encrypted = oauth::clickup_oauth_token
+		.select(oauth::encrypted_token)
+		.order(oauth::token_id.desc())
+		.first::<String>(connection)
+		.optional()?;
+	encrypted.map(|encrypted| cipher.decrypt(&encrypted)).transpose()
+}
+
+/// Record that a sync run has started (see history.rs)
+/// ### Returns
+/// The ID of the run, to record how it went with `finish_sync_run`
+pub fn start_sync_run(connection: &mut DbConnection) -> Result<i32, diesel::result::Error> {
+	use crate::schema::sync_runs::dsl as runs;
+
+	diesel::insert_into(runs::sync_runs)
+		.values(runs::started_at.eq(Utc::now().naive_utc()))
+		.returning(runs::run_id)
+		.get_result(connection)
+}
+
+/// Record how a sync run went
+/// ### Arguments
+/// * `connection` - Connection to the database
+/// * `id` - The ID of the run, from `start_sync_run`
+/// * `calendar` - Name of the calendar synced from, if it was downloaded
+/// * `stats` - What the run did (as far as it got, if it failed partway)
+/// * `run_error` - Why the run failed, or None if it succeeded
+pub fn finish_sync_run(connection: &mut DbConnection, id: i32, calendar: Option<&str>, stats: &SyncStats, run_error: Option<&str>) -> Result<(), diesel::result::Error> {
+	use crate::schema::sync_runs::dsl as runs;
+
+	diesel::update(runs::sync_runs.find(id))
+		.set((
+			runs::finished_at.eq(Utc::now().naive_utc()),
+			runs::source.eq(calendar),
+			runs::created.eq(stats.created as i32),
+			runs::updated.eq(stats.updated as i32),
+			runs::deleted.eq(stats.deleted as i32),
+			runs::skipped.eq(stats.skipped as i32),
+			runs::failed.eq(stats.failed as i32),
+			runs::requests.eq(stats.requests as i32),
+			runs::error.eq(run_error),
+		))
+		.execute(connection)?;
+	Ok(())
+}
+
+/// Load the most recent sync runs, newest first
+/// ### Arguments
+/// * `connection` - Connection to the database
+/// * `limit` - How many runs to load
+pub fn recent_sync_runs(connection: &mut DbConnection, limit: i64) -> Result<Vec<SyncRun>, diesel::result::Error> {
+	use crate::schema::sync_runs::dsl as runs;
+
+	runs::sync_runs
+		.order(runs::run_id.desc())
+		.limit(limit)
+		.select(SyncRun::as_select())
+		.load(connection)
+}
+
+/// Load the newest sync run that finished without an error, if there is one
+pub fn last_successful_sync_run(connection: &mut DbConnection) -> Result<Option<SyncRun>, diesel::result::Error> {
+	use crate::schema::sync_runs::dsl as runs;
+
+	runs::sync_runs
+		.filter(runs::finished_at.is_not_null().and(runs::error.is_null()))
+		.order(runs::run_id.desc())
+		.select(SyncRun::as_select())
+		.first(connection)
+		.optional()
+}
+
+/// Load the changes a sync run made to tasks that haven't been undone yet, oldest first (see audit.rs)
+/// ### Arguments
+/// * `connection` - Connection to the database
+/// * `run` - The ID of the run
+pub fn load_audit_records(connection: &mut DbConnection, run: i32) -> Result<Vec<AuditRecord>, diesel::result::Error> {
+	use crate::schema::sync_audit::dsl as audit;
+
+	audit::sync_audit
+		.filter(audit::run_id.eq(run).and(audit::undone.eq(false)))
+		.order(audit::audit_id.asc())
+		.select(AuditRecord::as_select())
+		.load(connection)
+}
+
+/// The IDs of the tasks runs after the given one have changed (and not been undone), which undoing the run leaves alone
+/// ### Arguments
+/// * `connection` - Connection to the database
+/// * `run` - The ID of the run
+pub fn tasks_changed_after(connection: &mut DbConnection, run: i32) -> Result<HashSet<String>, diesel::result::Error> {
+	use crate::schema::sync_audit::dsl as audit;
+
+	Ok(audit::sync_audit
+		.filter(audit::run_id.gt(run).and(audit::undone.eq(false)))
+		.select(audit::task_id)
+		.distinct()
+		.load::<String>(connection)?
+		.into_iter()
+		.collect())
+}
+
+/// Mark changes as undone, so undoing their run again doesn't undo them twice
+/// ### Arguments
+/// * `connection` - Connection to the database
+/// * `ids` - The IDs of the changes (`AuditRecord::audit_id`)
+pub fn mark_audit_records_undone(connection: &mut DbConnection, ids: &[i32]) -> Result<(), diesel::result::Error> {
+	use crate::schema::sync_audit::dsl as audit;
+
+	diesel::update(audit::sync_audit.filter(audit::audit_id.eq_any(ids)))
+		.set(audit::undone.eq(true))
+		.execute(connection)?;
+	Ok(())
+}
+
+/// Changes to mappings are saved in batches of this many (if the sync doesn't flush them first)
+pub const BATCH_SIZE: usize = 50;
+
+/// Keeps mappings in the clickup_ical_mapping table
+///
+/// Changes are held onto and saved in batches, each in a transaction, when the sync flushes them (see `MappingStore::flush`)
+/// or there are `BATCH_SIZE` of them. Mappings are saved by event UID & list ID (which are unique), so new mappings don't
+/// need saving to get an ID before they can be changed.
+///
+/// If told which sync run it's for (see `set_run`), the changes the sync makes to tasks are recorded in the audit log
+/// (the sync_audit table, see audit.rs), saved in the same transactions as the mappings.
+pub struct DbStore {
+	pool: DbPool,
+	/// The list mappings from before we synced to more than one list are for (CLICKUP_TARGET_LIST_ID)
+	default_list_id: String,
+	/// Mappings inserted or changed since they were last saved, by (event UID, list ID)
+	unsaved: HashMap<(String, String), CalendarMapping>,
+	/// (event UID, list ID) of the mappings deleted since changes were last saved
+	deleted: Vec<(String, String)>,
+	/// The sync run changes to tasks are recorded against, if they're being recorded
+	run: Option<i32>,
+	/// Changes to tasks recorded since changes were last saved, with the run & when they were made
+	audit: Vec<(i32, NaiveDateTime, AuditEntry)>,
+	/// IDs of the changes in the audit log undone since changes were last saved
+	undone: Vec<i32>,
+}
+
+impl DbStore {
+	/// Keep mappings in the database the pool connects to
+	/// ### Arguments
+	/// * `pool` - The pool of connections to the database
+	/// * `default_list_id` - The target list, which mappings from before we synced to more than one list are for
+	pub fn new(pool: DbPool, default_list_id: &str) -> Self {
+		Self { pool, default_list_id: default_list_id.to_string(), unsaved: HashMap::new(), deleted: vec![], run: None, audit: vec![], undone: vec![] }
+	}
+
+	/// Record the changes the sync makes to tasks in the audit log, against the given run
+	/// ### Arguments
+	/// * `run` - The ID of the run, from `start_sync_run`
+	pub fn set_run(&mut self, run: i32) {
+		self.run = Some(run);
+	}
+
+	/// Hold onto a new or changed mapping until the next batch is saved
+	fn save_later(&mut self, mapping: CalendarMapping) -> Result<(), Box<dyn Error>> {
+		let key = (mapping.calendar_id.clone(), mapping.list_id.clone());
+		self.deleted.retain(|deleted| *deleted != key);
+		self.unsaved.insert(key, mapping);
+		if self.unsaved.len() + self.deleted.len() >= BATCH_SIZE {
+			self.flush()?;
+		}
+		Ok(())
+	}
+}
+
+impl MappingStore for DbStore {
+	fn load(&mut self) -> Result<Vec<CalendarMapping>, Box<dyn Error>> {
+		let mut connection = self.pool.get()?;
+		// Fill in the list of mappings from before we synced to more than one list
+		diesel::update(clickup_ical_mapping.filter(list_id.eq("")))
+			.set(list_id.eq(&self.default_list_id))
+			.execute(&mut connection)?;
+		Ok(clickup_ical_mapping.load::<CalendarMapping>(&mut connection)?)
+	}
+
+	fn insert(&mut self, event_uid: &str, task_list_id: &str, task_id: &str, assignees: &BTreeSet<i64>, synced: Option<&SyncedFields>, event_fingerprint: Option<&str>) -> Result<CalendarMapping, Box<dyn Error>> {
+		let mapping = CalendarMapping {
+			// Not known until it's saved, and not needed as mappings are saved by event UID & list ID
+			mapping_id: 0,
+			clickup_id: task_id.to_string(),
+			calendar_id: event_uid.to_string(),
+			synced_assignees: assignees_to_json(assignees),
+			tombstone: None,
+			last_synced: synced.map(synced_fields_to_json),
+			list_id: task_list_id.to_string(),
+			user_deleted: false,
+			meeting_doc: None,
+			fingerprint: event_fingerprint.map(str::to_string),
+		};
+		self.save_later(mapping.clone())?;
+		Ok(mapping)
+	}
+
+	fn update(&mut self, mapping: &CalendarMapping) -> Result<(), Box<dyn Error>> {
+		self.save_later(mapping.clone())
+	}
+
+	fn delete(&mut self, mappings: &[CalendarMapping]) -> Result<(), Box<dyn Error>> {
+		for mapping in mappings {
+			let key = (mapping.calendar_id.clone(), mapping.list_id.clone());
+			self.unsaved.remove(&key);
+			self.deleted.push(key);
+		}
+		if self.unsaved.len() + self.deleted.len() >= BATCH_SIZE {
+			self.flush()?;
+		}
+		Ok(())
+	}
+
+	fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+		if self.unsaved.is_empty() && self.deleted.is_empty() && self.audit.is_empty() && self.undone.is_empty() {
+			return Ok(());
+		}
+		let unsaved = mem::take(&mut self.unsaved).into_values().collect::<Vec<_>>();
+		let deleted = mem::take(&mut self.deleted);
+		let audit = mem::take(&mut self.audit);
+		let undone = mem::take(&mut self.undone);
+
+		let mut connection = self.pool.get()?;
+		connection.transaction(|connection| {
+			// Deleted first, as a task that was another mapping's can be adopted (and task IDs are unique)
+			for (event_uid, task_list_id) in &deleted {
+				diesel::delete(clickup_ical_mapping.filter(calendar_id.eq(event_uid).and(list_id.eq(task_list_id))))
+					.execute(connection)?;
+			}
+			// One statement each, as SQLite can't upsert several rows in one (they're still saved together by the transaction)
+			for mapping in &unsaved {
+				diesel::insert_into(clickup_ical_mapping)
+					.values((
+						calendar_id.eq(&mapping.calendar_id),
+						list_id.eq(&mapping.list_id),
+						clickup_id.eq(&mapping.clickup_id),
+						synced_assignees.eq(&mapping.synced_assignees),
+						tombstone.eq(&mapping.tombstone),
+						last_synced.eq(&mapping.last_synced),
+						user_deleted.eq(mapping.user_deleted),
+						meeting_doc.eq(&mapping.meeting_doc),
+						fingerprint.eq(&mapping.fingerprint),
+					))
+					.on_conflict((calendar_id, list_id))
+					.do_update()
+					.set((
+						clickup_id.eq(excluded(clickup_id)),
+						synced_assignees.eq(excluded(synced_assignees)),
+						tombstone.eq(excluded(tombstone)),
+						last_synced.eq(excluded(last_synced)),
+						user_deleted.eq(excluded(user_deleted)),
+						meeting_doc.eq(excluded(meeting_doc)),
+						fingerprint.eq(excluded(fingerprint)),
+					))
+					.execute(connection)?;
+			}
+			for (run, recorded, entry) in &audit {
+				use crate::schema::sync_audit::dsl as audit_log;
+
+				let (operation, before, after) = entry.columns();
+				diesel::insert_into(audit_log::sync_audit)
+					.values((
+						audit_log::run_id.eq(run),
+						audit_log::recorded_at.eq(recorded),
+						audit_log::operation.eq(operation),
+						audit_log::list_id.eq(&entry.list_id),
+						audit_log::task_id.eq(&entry.task_id),
+						audit_log::event_uid.eq(&entry.event_uid),
+						audit_log::payload_before.eq(before),
+						audit_log::payload_after.eq(after),
+					))
+					.execute(connection)?;
+			}
+			if !undone.is_empty() {
+				mark_audit_records_undone(connection, &undone)?;
+			}
+			Ok::<_, diesel::result::Error>(())
+		})?;
+		debug!("Saved {} mappings, deleted {}, recorded {} changes to tasks and undid {}", unsaved.len(), deleted.len(), audit.len(), undone.len());
+		Ok(())
+	}
+
+	fn audited(&self) -> bool {
+		self.run.is_some()
+	}
+
+	fn record(&mut self, entry: AuditEntry) -> Result<(), Box<dyn Error>> {
+		if let Some(run) = self.run {
+			self.audit.push((run, Utc::now().naive_utc(), entry));
+		}
+		Ok(())
+	}
+
+	fn mark_undone(&mut self, audit_id: i32) -> Result<(), Box<dyn Error>> {
+		self.undone.push(audit_id);
+		Ok(())
+	}
+}
+
+impl Drop for DbStore {
+	fn drop(&mut self) {
+		// In case whatever was using the store forgot, as otherwise we'd lose track of the tasks we made
+		if let Err(err) = self.flush() {
+			error!("Failed to save mappings: {}", err);
+		}
+	}
+}
+
+/// Keeps the rate limit in the clickup_rate_limit table, so every process using the same ClickUp token shares it (see rate_limit.rs)
+///
+/// It takes a connection before every request to ClickUp, so it should have a pool of its own (see `connect`): with the
+/// sync's pool of one connection, anything holding that connection while making a request would wait on itself.
+pub struct DbRateLimit {
+	pool: DbPool,
+}
+
+impl DbRateLimit {
+	/// Keep the rate limit in the database the pool connects to (which must have had the migrations run)
+	pub fn new(pool: DbPool) -> Self {
+		Self { pool }
+	}
+
+	/// Keep the rate limit in the database from DATABASE_URL, over a connection of its own
+	pub fn connect() -> Result<Self, Box<dyn Error>> {
+		Ok(Self::new(connection_pool_for(&database_url()?, 1)?))
+	}
+}
+
+/// Run a transaction that other processes can't change the rate limit during: Postgres locks the rows it reads `for_update`
+#[cfg(not(feature = "sqlite"))]
+fn rate_limit_transaction<T>(connection: &mut DbConnection, f: impl FnOnce(&mut DbConnection) -> QueryResult<T>) -> QueryResult<T> {
+	connection.transaction(f)
+}
+
+/// Run a transaction that other processes can't change the rate limit during: SQLite has no row locks, so this locks
+/// the whole database for writing from the start (other processes wait for it, see `SQLITE_BUSY_TIMEOUT`)
+#[cfg(feature = "sqlite")]
+fn rate_limit_transaction<T>(connection: &mut DbConnection, f: impl FnOnce(&mut DbConnection) -> QueryResult<T>) -> QueryResult<T> {
+	connection.immediate_transaction(f)
+}
+
+/// Read a bucket, locking it until the transaction ends so other processes wait for us to take our token
+fn read_bucket(connection: &mut DbConnection, key: &str) -> QueryResult<(f64, i64)> {
+	use crate::schema::clickup_rate_limit::dsl as rate_limit;
+
+	let query = rate_limit::clickup_rate_limit.find(key).select((rate_limit::tokens, rate_limit::updated_at));
+	// SQLite has already locked the database (see `rate_limit_transaction`)
+	#[cfg(not(feature = "sqlite"))]
+	let query = query.for_update();
+	query.get_result(connection)
+}
+
+impl SharedBucket for DbRateLimit {
+	fn try_take(&mut self, key: &str, limit: u32) -> Result<Option<Duration>, Box<dyn Error>> {
+		use crate::schema::clickup_rate_limit::dsl as rate_limit;
+
+		Ok(rate_limit_transaction(&mut *self.pool.get()?, |connection| {
+			let now = now_millis();
+			diesel::insert_into(rate_limit::clickup_rate_limit)
+				.values((rate_limit::bucket.eq(key), rate_limit::tokens.eq(limit as f64), rate_limit::updated_at.eq(now)))
+				.on_conflict_do_nothing()
+				.execute(connection)?;
+			let (tokens, updated_at) = read_bucket(connection, key)?;
+
+			let mut bucket = Bucket { tokens, updated_at };
+			let wait = bucket.take(limit, now);
+			diesel::update(rate_limit::clickup_rate_limit.find(key))
+				.set((rate_limit::tokens.eq(bucket.tokens), rate_limit::updated_at.eq(bucket.updated_at)))
+				.execute(connection)?;
+			Ok(wait)
+		})?)
+	}
+}
@@ -0,0 +1,56 @@
+/// Keeping the part of a task description people write in ClickUp
+///
+/// With the managed block turned on, the description from the calendar is written between two marker lines, e.g.
+/// ```text
+/// Notes from the committee go here, and are kept!
+/// --- synced from calendar ---
+/// Description from Google Calendar
+/// --- end of synced section ---
+/// ```
+/// On update, only the text between the markers is replaced, so anything written before or after them stays.
+use crate::config::DescriptionConfig;
+
+/// The markers around the part of the description we manage
+#[derive(Debug, Clone)]
+pub struct ManagedBlock {
+	start_marker: String,
+	end_marker: String,
+}
+
+impl ManagedBlock {
+	/// The managed block from the config, or None if it's turned off
+	pub fn from_config(config: &DescriptionConfig) -> Option<Self> {
+		config.managed_block.then(|| Self {
+			start_marker: config.start_marker.clone(),
+			end_marker: config.end_marker.clone(),
+		})
+	}
+
+	/// Wrap text from the calendar in the markers, for a new description
+	pub fn wrap(&self, text: &str) -> String {
+		format!("{}\n{}\n{}", self.start_marker, text.trim(), self.end_marker)
+	}
+
+	/// Find the byte range of the text between the markers in a description
+	fn find(&self, description: &str) -> Option<(usize, usize)> {
+		let start = description.find(&self.start_marker)? + self.start_marker.len();
+		let end = start + description[start..].find(&self.end_marker)?;
+		Some((start, end))
+	}
+
+	/// Get the text between the markers in a description, if it has them
+	pub fn extract<'a>(&self, description: &'a str) -> Option<&'a str> {
+		self.find(description).map(|(start, end)| description[start..end].trim())
+	}
+
+	/// Put fresh text from the calendar into an existing description, keeping everything outside the markers
+	///
+	/// If the description doesn't have the markers (e.g. it was written before the managed block was turned on),
+	/// the whole description was ours, so it is replaced.
+	pub fn merge(&self, existing: &str, text: &str) -> String {
+		match self.find(existing) {
+			Some((start, end)) => format!("{}\n{}\n{}", &existing[..start], text.trim(), &existing[end..]),
+			None => self.wrap(text),
+		}
+	}
+}
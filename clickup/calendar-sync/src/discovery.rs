@@ -0,0 +1,144 @@
+/// Browsing the ClickUp workspaces the access token can see, to find the IDs to put in the env vars & config
+///
+/// ClickUp organises things as workspaces (called teams in the API) > spaces > folders > lists, where lists can also
+/// be directly in a space. For each list we fetch its statuses, custom fields and members, which is what the config refers to.
+use std::fmt::Write;
+
+use log::info;
+use serde::Serialize;
+
+use crate::clickup::{ClickUpApiInstance, ClickUpError, CustomFieldDefinition, ListStatus, ListSummary, TeamMember};
+
+/// A workspace and everything in it
+#[derive(Serialize, Debug)]
+pub struct WorkspaceTree {
+	/// ID of the workspace
+	pub id: String,
+	/// Name of the workspace
+	pub name: String,
+	/// The spaces in the workspace
+	pub spaces: Vec<SpaceTree>,
+}
+
+/// A space and everything in it
+#[derive(Serialize, Debug)]
+pub struct SpaceTree {
+	/// ID of the space
+	pub id: String,
+	/// Name of the space
+	pub name: String,
+	/// The folders in the space
+	pub folders: Vec<FolderTree>,
+	/// Lists directly in the space, rather than in a folder
+	pub lists: Vec<ListDetails>,
+}
+
+/// A folder and the lists in it
+#[derive(Serialize, Debug)]
+pub struct FolderTree {
+	/// ID of the folder
+	pub id: String,
+	/// Name of the folder
+	pub name: String,
+	/// The lists in the folder
+	pub lists: Vec<ListDetails>,
+}
+
+/// A list, with everything about it the config can refer to
+#[derive(Serialize, Debug)]
+pub struct ListDetails {
+	/// ID of the list
+	pub id: String,
+	/// Name of the list
+	pub name: String,
+	/// Statuses tasks in the list can have
+	pub statuses: Vec<ListStatus>,
+	/// Custom fields available on the list
+	pub custom_fields: Vec<CustomFieldDefinition>,
+	/// People who can see the list
+	pub members: Vec<TeamMember>,
+}
+
+/// Fetch every workspace, space, folder & list the access token can see
+///
+/// This is a lot of requests for a big workspace (3 per list), so is rate limited like everything else & can take a while.
+/// ### Arguments
+/// * `clickup_api` - The instance of the ClickUp API to use
+pub fn discover(clickup_api: &ClickUpApiInstance) -> Result<Vec<WorkspaceTree>, ClickUpError> {
+	let mut workspaces = vec![];
+	for team in clickup_api.get_teams()? {
+		info!("Browsing workspace {:?}...", team.name);
+		let mut spaces = vec![];
+		for space in clickup_api.get_spaces(&team.id)? {
+			let mut folders = vec![];
+			for folder in clickup_api.get_folders(&space.id)? {
+				folders.push(FolderTree {
+					lists: folder.lists.iter().map(|list| list_details(clickup_api, list)).collect::<Result<_, _>>()?,
+					id: folder.id,
+					name: folder.name,
+				});
+			}
+			let lists = clickup_api.get_folderless_lists(&space.id)?.iter()
+				.map(|list| list_details(clickup_api, list))
+				.collect::<Result<_, _>>()?;
+			spaces.push(SpaceTree { id: space.id, name: space.name, folders, lists });
+		}
+		workspaces.push(WorkspaceTree { id: team.id.clone(), name: team.name.clone(), spaces });
+	}
+	Ok(workspaces)
+}
+
+/// Fetch the statuses, custom fields & members of a list
+fn list_details(clickup_api: &ClickUpApiInstance, list: &ListSummary) -> Result<ListDetails, ClickUpError> {
+	Ok(ListDetails {
+		id: list.id.clone(),
+		name: list.name.clone(),
+		statuses: clickup_api.get_list_statuses(&list.id)?,
+		custom_fields: clickup_api.get_custom_fields(&list.id)?,
+		members: clickup_api.get_list_members(&list.id)?,
+	})
+}
+
+/// Render workspaces as an indented tree for people to read, with the ID of everything in brackets
+pub fn render_tree(workspaces: &[WorkspaceTree]) -> String {
+	let mut out = String::new();
+	for workspace in workspaces {
+		writeln!(out, "Workspace {} [{}]", workspace.name, workspace.id).unwrap();
+		for space in &workspace.spaces {
+			writeln!(out, "  Space {} [{}]", space.name, space.id).unwrap();
+			for folder in &space.folders {
+				writeln!(out, "    Folder {} [{}]", folder.name, folder.id).unwrap();
+				for list in &folder.lists {
+					render_list(&mut out, list, "      ");
+				}
+			}
+			for list in &space.lists {
+				render_list(&mut out, list, "    ");
+			}
+		}
+	}
+	out
+}
+
+/// Render a list & its details at the given indent
+fn render_list(out: &mut String, list: &ListDetails, indent: &str) {
+	writeln!(out, "{}List {} [{}]", indent, list.name, list.id).unwrap();
+	let statuses = list.statuses.iter().map(|status| status.status.as_str()).collect::<Vec<_>>();
+	writeln!(out, "{}  Statuses: {}", indent, statuses.join(", ")).unwrap();
+	if !list.custom_fields.is_empty() {
+		writeln!(out, "{}  Custom fields:", indent).unwrap();
+		for field in &list.custom_fields {
+			writeln!(out, "{}    {} ({}) [{}]", indent, field.name, field.field_type, field.id).unwrap();
+		}
+	}
+	if !list.members.is_empty() {
+		writeln!(out, "{}  Members:", indent).unwrap();
+		for member in &list.members {
+			let name = member.username.as_deref().unwrap_or("?");
+			match &member.email {
+				Some(email) => writeln!(out, "{}    {} <{}> [{}]", indent, name, email, member.id).unwrap(),
+				None => writeln!(out, "{}    {} [{}]", indent, name, member.id).unwrap(),
+			}
+		}
+	}
+}
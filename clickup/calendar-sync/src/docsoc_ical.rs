@@ -16,23 +16,24 @@ fn parse_date_str(date_str: &str) -> Option<NaiveDateTime> {
         NaiveDateTime::parse_from_str(date_str, "%Y%m%dT%H%M%SZ").ok()
     } else {
         // Parse string without timezone
-        let naive = if date_str.len() > 8 {
+        if date_str.len() > 8 {
             // Includes time
             NaiveDateTime::parse_from_str(date_str, "%Y%m%d%H%M%S").ok()
         } else {
             // Date only
             NaiveDate::parse_from_str(date_str, "%Y%m%d")
                 .ok()
-                .map(|date| date.and_hms(0, 0, 0))
-        };
-
-        naive
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        }
     }
 }
 
+/// Format we use when putting dates from events into text (e.g. in templates)
+pub const EVENT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
 /// Parsed event from the iCal file
 /// Helper obejct to pass it around
-#[derive(Debug)] // Allow printing for debugging
+#[derive(Debug, Default, Clone)] // Allow printing for debugging
 pub struct ParsedEvent {
     /// event id (known as UID in ical)
     pub uid: String,
@@ -44,6 +45,89 @@ pub struct ParsedEvent {
     pub summary: String,
     /// description of the event (description from Google Calendar)
     pub description: String,
+    /// location of the event, e.g. the room (LOCATION in ical)
+    pub location: Option<String>,
+    /// link associated with the event, e.g. a sign up page (URL in ical)
+    pub url: Option<String>,
+    /// email of the organiser of the event, without the mailto: (ORGANIZER in ical)
+    pub organizer: Option<String>,
+    /// categories of the event (CATEGORIES in ical, which can be repeated and comma separated)
+    pub categories: Vec<String>,
+    /// emails of the attendees of the event, without the mailto: (ATTENDEE in ical)
+    pub attendees: Vec<String>,
+}
+
+impl ParsedEvent {
+    /// Get a property of the event as text, by the name used for it in templates
+    /// (one of uid, summary, description, location, url, organizer, categories, attendees, start, end)
+    ///
+    /// Lists (categories & attendees) are joined with ", " and dates use EVENT_DATE_FORMAT.
+    /// Returns None for unknown property names, and an empty string for properties the event doesn't have.
+    pub fn property(&self, name: &str) -> Option<String> {
+        let format_date = |date: Option<NaiveDateTime>| {
+            date.map(|date| date.format(EVENT_DATE_FORMAT).to_string())
+        };
+        let value = match name {
+            "uid" => Some(self.uid.clone()),
+            "summary" => Some(self.summary.clone()),
+            "description" => Some(self.description.clone()),
+            "location" => self.location.clone(),
+            "url" => self.url.clone(),
+            "organizer" => self.organizer.clone(),
+            "categories" => Some(self.categories.join(", ")),
+            "attendees" => Some(self.attendees.join(", ")),
+            "start" => format_date(self.start_time),
+            "end" => format_date(self.end_time),
+            _ => return None,
+        };
+        Some(value.unwrap_or_default())
+    }
+
+    /// Fill in a template with properties of the event
+    ///
+    /// Properties are referenced by wrapping their name (see `property()`) in braces,
+    /// e.g. "{summary} in {location}". Unknown placeholders are left as they are.
+    pub fn render(&self, template: &str) -> String {
+        let mut rendered = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            rendered.push_str(&rest[..open]);
+            let after_open = &rest[open + 1..];
+            match after_open
+                .find('}')
+                .and_then(|close| Some((close, self.property(&after_open[..close])?)))
+            {
+                Some((close, value)) => {
+                    rendered.push_str(&value);
+                    rest = &after_open[close + 1..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = after_open;
+                }
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+/// Strip the mailto: off the front of an ORGANIZER or ATTENDEE
+fn strip_mailto(value: &str) -> String {
+    let value = value.trim();
+    match value.get(..7) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("mailto:") => value[7..].to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// Get the name of the calendar in an iCal file (X-WR-CALNAME, which Google Calendar sets), if it has one
+pub fn calendar_name(ical_content: &str) -> Option<String> {
+    IcalParser::new(ical_content.as_bytes())
+        .filter_map(Result::ok)
+        .flat_map(|calendar| calendar.properties)
+        .find(|property| property.name == "X-WR-CALNAME")
+        .and_then(|property| property.value)
 }
 
 /// Parse an iCal file into an iterator of ParsedEvent objects for us to then map to ClickUp
@@ -118,6 +202,36 @@ pub fn parse_ical(ical_content: &str) -> impl Iterator<Item = ParsedEvent> + '_
                     .find(|property| property.name == "DESCRIPTION")
                     .and_then(|property| property.value.clone())
                     .unwrap_or_else(|| "".to_string()),
+                location: event
+                    .properties
+                    .iter()
+                    .find(|property| property.name == "LOCATION")
+                    .and_then(|property| property.value.clone()),
+                url: event
+                    .properties
+                    .iter()
+                    .find(|property| property.name == "URL")
+                    .and_then(|property| property.value.clone()),
+                organizer: event
+                    .properties
+                    .iter()
+                    .find(|property| property.name == "ORGANIZER")
+                    .and_then(|property| property.value.as_deref().map(strip_mailto)),
+                categories: event
+                    .properties
+                    .iter()
+                    .filter(|property| property.name == "CATEGORIES")
+                    .filter_map(|property| property.value.as_deref())
+                    .flat_map(|value| value.split(','))
+                    .map(|category| category.trim().to_string())
+                    .filter(|category| !category.is_empty())
+                    .collect(),
+                attendees: event
+                    .properties
+                    .iter()
+                    .filter(|property| property.name == "ATTENDEE")
+                    .filter_map(|property| property.value.as_deref().map(strip_mailto))
+                    .collect(),
             })
             .filter(move |event| {
                 // Check if the event is within the specified date range
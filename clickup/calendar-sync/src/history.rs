@@ -0,0 +1,118 @@
+/// The history of sync runs, for the `status` command
+///
+/// Each run of the sync (not in stateless mode, which has no database) records when it started & finished, what it did (see
+/// `SyncStats`), and why it failed if it did, in the sync_runs table (see db.rs). The `status` command shows the recent runs,
+/// and flags when the sync hasn't succeeded for longer than a threshold, e.g. because the cron job has stopped or the calendar
+/// can't be downloaded, so it can be used by a monitoring check.
+use std::fmt::Write;
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::models::SyncRun;
+
+/// How many runs the `status` command shows
+pub const RECENT_RUNS: i64 = 10;
+
+/// How long since the last successful run before the `status` command says the sync is stale, unless told otherwise
+pub const DEFAULT_STALE_AFTER_HOURS: i64 = 24;
+
+/// How a run went
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunOutcome {
+	/// Still going, or killed before it could record how it went
+	Unfinished,
+	/// Finished without an error (though some tasks may have failed, see `SyncRun::failed`)
+	Succeeded,
+	/// Stopped by an error
+	Failed(String),
+}
+
+/// How a run went, from its row in the history
+pub fn outcome(run: &SyncRun) -> RunOutcome {
+	match (&run.finished_at, &run.error) {
+		(None, _) => RunOutcome::Unfinished,
+		(Some(_), None) => RunOutcome::Succeeded,
+		(Some(_), Some(error)) => RunOutcome::Failed(error.clone()),
+	}
+}
+
+/// Render runs as a table for people to read, one run per line (with the error, if any, on the line after)
+pub fn render_runs(runs: &[SyncRun]) -> String {
+	let mut out = String::new();
+	writeln!(
+		out,
+		"{:>6}  {:<19}  {:>8}  {:<10}  {:>7}  {:>7}  {:>7}  {:>7}  {:>6}  {:>8}  Calendar",
+		"Run", "Started (UTC)", "Took", "Result", "Created", "Updated", "Deleted", "Skipped", "Failed", "Requests"
+	).unwrap();
+	for run in runs {
+		let took = run.finished_at
+			.map(|finished_at| format_duration(finished_at - run.started_at))
+			.unwrap_or_else(|| "-".to_string());
+		let outcome = outcome(run);
+		let result = match outcome {
+			RunOutcome::Unfinished => "unfinished",
+			RunOutcome::Succeeded => "ok",
+			RunOutcome::Failed(_) => "FAILED",
+		};
+		writeln!(
+			out,
+			"{:>6}  {:<19}  {:>8}  {:<10}  {:>7}  {:>7}  {:>7}  {:>7}  {:>6}  {:>8}  {}",
+			run.run_id,
+			run.started_at.format("%Y-%m-%d %H:%M:%S"),
+			took,
+			result,
+			run.created,
+			run.updated,
+			run.deleted,
+			run.skipped,
+			run.failed,
+			run.requests,
+			run.source.as_deref().unwrap_or("-")
+		).unwrap();
+		if let RunOutcome::Failed(error) = outcome {
+			writeln!(out, "{:>6}  {}", "", error).unwrap();
+		}
+	}
+	out
+}
+
+/// Check whether the sync has succeeded recently enough
+/// ### Arguments
+/// * `last_success` - The newest run that succeeded, if any
+/// * `now` - The time now, in UTC
+/// * `stale_after` - How long since the last successful run before the sync counts as stale
+/// ### Returns
+/// None if the sync is fine, or what's wrong with it
+pub fn staleness(last_success: Option<&SyncRun>, now: NaiveDateTime, stale_after: Duration) -> Option<String> {
+	let Some(run) = last_success else {
+		return Some("The sync has never run successfully".to_string());
+	};
+	// Finished runs are the only ones that can have succeeded
+	let finished_at = run.finished_at.unwrap_or(run.started_at);
+	let since = now - finished_at;
+	if since > stale_after {
+		Some(format!(
+			"The last successful run (run {}) finished {} ago, more than the {} allowed",
+			run.run_id,
+			format_duration(since),
+			format_duration(stale_after)
+		))
+	} else {
+		None
+	}
+}
+
+/// Format a duration for people to read, to the most significant unit or two (e.g. "3h 5m", "42s")
+pub fn format_duration(duration: Duration) -> String {
+	let seconds = duration.num_seconds().max(0);
+	let (days, hours, minutes) = (seconds / 86_400, seconds / 3_600 % 24, seconds / 60 % 60);
+	if days > 0 {
+		format!("{}d {}h", days, hours)
+	} else if hours > 0 {
+		format!("{}h {}m", hours, minutes)
+	} else if minutes > 0 {
+		format!("{}m {}s", minutes, seconds % 60)
+	} else {
+		format!("{}s", seconds)
+	}
+}
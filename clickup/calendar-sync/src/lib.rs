@@ -1,3 +1,27 @@
+pub mod adoption;
+pub mod assignees;
+pub mod audit;
+pub mod auth;
+pub mod changes;
+pub mod clickup;
+pub mod config;
+pub mod custom_fields;
+pub mod db;
+pub mod description;
+pub mod discovery;
+pub mod history;
+pub mod docsoc_ical;
+pub mod matcher;
+pub mod meeting_notes;
 pub mod models;
+pub mod ownership;
+pub mod provenance;
+pub mod provisioning;
+pub mod rate_limit;
+pub mod routes;
 pub mod schema;
-pub mod db;
\ No newline at end of file
+pub mod stateless;
+pub mod statuses;
+pub mod store;
+pub mod sync;
+pub mod templates;
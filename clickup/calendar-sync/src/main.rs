@@ -1,32 +1,43 @@
-use std::error::Error;
 /// This is the main entry point for the DoCSoc ClickUp calendar sync tool.
 /// It fetches the iCal file from the DoCSoc calendar, parses it, and syncs the events to ClickUp.
-/// The mappings from iCal event IDs to ClickUp task IDs are stored in a postgres database to allow updates and deletions (e.g. when a calendar event changes, the clickup task corresponding to it is also updated)
+/// The mappings from iCal event IDs to ClickUp task IDs are stored in a postgres (or, with the sqlite feature, SQLite) database to allow updates and deletions (e.g. when a calendar event changes, the clickup task corresponding to it is also updated)
+/// (or, in stateless mode, on the ClickUp tasks themselves - see stateless.rs)
 /// When a calendar event is deleted, we also detect this and remove the corresponding task from ClickUp & mapping in the database.
 /// Recurring events are not supported, as the author couldn't be bothered.
 ///
 /// The tool is designed to be run as a cron job, and will only sync events that are within the range of ICAL_SYNC_START_DATE and ICAL_SYNC_END_DATE
 // STD types we need
-use std::{collections::HashSet, env};
+use std::any::Any;
+use std::env;
+use std::error::Error;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::OnceLock;
 
+use chrono::Utc;
 // Dotenv to load env vars from a .env file
 use dotenvy::dotenv;
-use log::{debug, info, warn}; // nice stdout logs
+use log::{debug, error, info, warn}; // nice stdout logs
 use reqwest::blocking::get; // for fetching the iCal file
 
-// Database models (using diesel ORM)
-use diesel::{dsl::insert_into, prelude::*};
-use models::*; // import all models // use diesel ORM for postgres queries
-
-// Local modules of our own code we need
-mod clickup;
-mod db;
-mod docsoc_ical;
-mod models;
-mod schema;
-use clickup::ClickUpApiInstance;
-use db::{establish_connection, run_migrations};
-use docsoc_ical::parse_ical;
+// Our own code we need, from lib.rs
+use clickup_ical_sync::adoption::plan_adoption;
+use clickup_ical_sync::audit::undo_run;
+use clickup_ical_sync::auth::{CallbackListener, OAuthApp, TokenCipher};
+use clickup_ical_sync::clickup::ClickUpApiInstance;
+use clickup_ical_sync::config::{load_config, ProvisionConfig, RateLimitConfig, SyncConfig};
+use clickup_ical_sync::db::{
+    connection_pool, finish_sync_run, last_successful_sync_run, load_audit_records,
+    load_oauth_token, recent_sync_runs, run_migrations, save_oauth_token, start_sync_run,
+    tasks_changed_after, DbPool, DbRateLimit, DbStore,
+};
+use clickup_ical_sync::discovery::{discover, render_tree};
+use clickup_ical_sync::docsoc_ical::{self, calendar_name, parse_ical};
+use clickup_ical_sync::history::{render_runs, staleness, DEFAULT_STALE_AFTER_HOURS, RECENT_RUNS};
+use clickup_ical_sync::provisioning::provision;
+use clickup_ical_sync::rate_limit::FileRateLimit;
+use clickup_ical_sync::stateless::ClickUpStore;
+use clickup_ical_sync::store::MappingStore;
+use clickup_ical_sync::sync::{sync_events, SyncContext, SyncStats};
 
 // ==========
 // Helper functions specific to main
@@ -48,94 +59,374 @@ fn fetch_ical(url: &str) -> Result<String, Box<dyn Error>> {
     Ok(ical_content)
 }
 
-/// Called on each event extracted from the iCal file to map it to ClickUp using the clikcup API
-/// and also store the mapping in the database.
-/// ### Arguments
-/// * `event` - The parsed event from the iCal file we are mapping
-/// * `clickup_api` - The instance of the ClickUp API to use for mapping (use once instance for the whole program to ensure consistent rate limiting)
-fn map_event(event: docsoc_ical::ParsedEvent, clickup_api: &ClickUpApiInstance) {
-    use self::schema::clickup_ical_mapping::dsl::*;
-
-    let connection = &mut establish_connection();
-
-    info!("Mapping event: {:?}", event.summary);
-    debug!("Checking if event is already mapped...");
-    let existing_mapping = clickup_ical_mapping
-        .filter(calendar_id.eq(&event.uid))
-        .first::<CalendarMapping>(connection)
-        .optional()
-        .expect("Error loading mapping!");
-
-    if let Some(mapping) = existing_mapping {
-        // patern matching!
-        // We already have a mapping for this event
-        // So update the corresponding task in ClickUp with any changes to details in the calendar
-        debug!("Event already mapped, updating task...");
-        clickup_api.update_task(&mapping, &event);
-    } else {
-        // We don't have a mapping for this event
-        // So create a corresponding task, and store the mapping in the database
-        let task_id = clickup_api.create_task(&event);
+/// The pool of connections to the database, set up the first time it's needed (so stateless mode never connects)
+/// and shared by everything that uses the database for the rest of the run
+fn db_pool() -> &'static DbPool {
+    static POOL: OnceLock<DbPool> = OnceLock::new();
+    POOL.get_or_init(|| connection_pool().expect("Failed to connect to the database!"))
+}
 
-        // Inesrt
-        insert_into(clickup_ical_mapping)
-            .values((calendar_id.eq(&event.uid), clickup_id.eq(&task_id)))
-            .execute(connection)
-            .expect("Error saving new mapping!");
-        debug!("Added event {:?} under ID {}", event, task_id);
+/// The token to talk to ClickUp with: the personal access token in CLICKUP_ACCESS_TOKEN if set,
+/// otherwise the OAuth token the `authorize` command stored in the database (see auth.rs)
+fn clickup_access_token() -> String {
+    if let Ok(token) = env::var("CLICKUP_ACCESS_TOKEN") {
+        if !token.is_empty() {
+            return token;
+        }
     }
 
-    // 1: DB lookup -> is it there
+    debug!("No CLICKUP_ACCESS_TOKEN, using the stored OAuth token");
+    let cipher = TokenCipher::from_env()
+        .expect("Set CLICKUP_ACCESS_TOKEN, or CLICKUP_TOKEN_KEY to use the OAuth token!");
+    let token = load_oauth_token(
+        &mut db_pool().get().expect("Failed to connect to the database!"),
+        &cipher,
+    )
+    .expect("Failed to load OAuth token!")
+    .expect(
+        "No OAuth token stored, run the authorize command first (or set CLICKUP_ACCESS_TOKEN)!",
+    );
+    format!("Bearer {}", token)
 }
 
-/// Ensures that the mappings between calendar events and ClickUp tasks are up to date.
-/// Specifically, it checks if any events have been deleted from the calendar, and if so, deletes the corresponding task from ClickUp and the mapping from the database.
-///
-/// The idea of this function is to take in a set of all the event IDs in the calendar (event UIDs), generated from when we parsed the iCal file,
-/// and then for each mapping in the database, check if the event ID is in the set.
-/// If it is not, then the event has been deleted from the calendar, so we delete the corresponding task from ClickUp and the mapping from the database.
+/// Authorises the ClickUp OAuth app & stores its token in the database, replacing any token from before (see auth.rs)
+fn authorize() {
+    let app = OAuthApp::from_env().expect("Failed to load OAuth app!");
+    let cipher = TokenCipher::from_env().expect("Failed to load token key!");
+    run_migrations(&mut db_pool().get().expect("Failed to connect to the database!"))
+        .expect("Failed to run migrations!");
+
+    let listener =
+        CallbackListener::bind(&app.redirect_uri).expect("Failed to listen for OAuth callback!");
+    info!(
+        "Open this link & authorise the app (waiting for ClickUp to redirect to {}):",
+        app.redirect_uri
+    );
+    info!("{}", app.authorize_url());
+    let code = listener.wait_for_code().expect("Authorisation failed!");
+
+    // No token yet - that's what we're getting
+    let clickup_api = ClickUpApiInstance::unauthenticated();
+    let token = clickup_api
+        .exchange_oauth_code(&app.client_id, &app.client_secret, &code)
+        .expect("Failed to get token from ClickUp!");
+    save_oauth_token(
+        &mut db_pool().get().expect("Failed to connect to the database!"),
+        &cipher,
+        &token,
+    )
+    .expect("Failed to save token!");
+
+    info!("Authorised! The sync will use the new token from its next run.");
+}
+
+/// Adopts existing tasks in the lists we sync to instead of creating duplicates of them, by matching them to events and
+/// writing mappings for them (see adoption.rs). Tasks for events that match nothing are left to the next sync to create.
 ///
 /// ### Arguments
-/// * `clickup_api` - The instance of the ClickUp API to use for mapping (use once instance for the whole program to ensure consistent rate limiting)
-/// * `set_of_calendar_event_ids` - A set of all the event IDs in the calendar, generated from when we parsed the iCal file
-fn ensure_mappings_are_up_to_date(
+/// * `clickup_api` - The instance of the ClickUp API to use
+/// * `context` - How the sync is set up, from the config
+/// * `store` - Where to save the mappings
+/// * `events` - All the events in the calendar
+fn adopt_existing_tasks(
     clickup_api: &ClickUpApiInstance,
-    set_of_calendar_event_ids: &HashSet<String>,
+    context: &SyncContext,
+    store: &mut dyn MappingStore,
+    events: &[docsoc_ical::ParsedEvent],
 ) {
-    use self::schema::clickup_ical_mapping::dsl::*;
-
-    info!("Ensuring mappings are up to date between calendar and clickup...");
-
-    let connection = &mut establish_connection();
-
-    // Load all mappings
-    let all_mappings = clickup_ical_mapping
-        .load::<CalendarMapping>(connection)
-        .expect("Error loading mappings!");
-
-    // Maintain a set of mappings to delete
-    let mut mappings_to_delete: HashSet<String> = HashSet::new();
-
-    // for each mapping from the database
-    for mapping in all_mappings {
-        // ... if the event ID is not in the set of calendar event IDs
-        if !set_of_calendar_event_ids.contains(&mapping.calendar_id) {
-            // ... then the event has been deleted from the calendar, and we should delete the corresponding task from ClickUp and the mapping from the database
-            warn!(
-                "Deleting mapping for event ID {} task {}",
-                mapping.calendar_id, mapping.clickup_id
-            );
-            // The event is no longer in the calendar
-            mappings_to_delete.insert(mapping.calendar_id.clone()); // mark for deletion in the DB
-            clickup_api.delete_task(&mapping.clickup_id); // execute API request to delete the task from ClickUp
+    let all_mappings = store.load().expect("Error loading mappings!");
+
+    // Each list is adopted separately, as an event has its own task in each list it's routed to
+    for list_id in context.list_ids() {
+        info!("Adopting existing tasks in list {}...", list_id);
+
+        let list_events = events
+            .iter()
+            .filter(|event| context.lists_for(event).contains(&list_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        let list_mappings = all_mappings
+            .iter()
+            .filter(|mapping| mapping.list_id == list_id)
+            .collect::<Vec<_>>();
+        let mapped_events = list_mappings
+            .iter()
+            .map(|mapping| mapping.calendar_id.clone())
+            .collect();
+        let mapped_tasks = list_mappings
+            .iter()
+            .map(|mapping| mapping.clickup_id.clone())
+            .collect();
+
+        let tasks = clickup_api
+            .get_list_tasks(&list_id, false)
+            .expect("Failed to list tasks in the list!");
+        let plan = plan_adoption(
+            &list_events,
+            &tasks,
+            context.uid_field(&list_id),
+            &mapped_events,
+            &mapped_tasks,
+        );
+
+        for (event_uid, task_id) in &plan.adopted {
+            info!("Adopting task {} for event ID {}", task_id, event_uid);
+            store
+                .insert(
+                    event_uid,
+                    &list_id,
+                    task_id,
+                    &Default::default(),
+                    None,
+                    None,
+                )
+                .expect("Error saving adopted mapping!");
+        }
+        for (event_uid, task_ids) in &plan.ambiguous {
+            warn!("Event ID {} matches more than one task, or shares its task with another event, so it wasn't adopted: {:?}", event_uid, task_ids);
+        }
+        store.flush().expect("Error saving adopted mappings!");
+
+        info!(
+            "Adopted {} tasks in list {}, {} events were ambiguous and {} matched no task (tasks will be created for these on the next sync)",
+            plan.adopted.len(),
+            list_id,
+            plan.ambiguous.len(),
+            plan.unmatched.len()
+        );
+    }
+}
+
+/// Prints every workspace, space, folder & list the access token can see, along with the statuses, custom fields & members
+/// of each list, so the IDs for the env vars & config file can be copied from it (see discovery.rs)
+///
+/// ### Arguments
+/// * `json` - Print JSON rather than a tree
+fn discover_workspaces(json: bool) {
+    // No list needed, we're looking for one
+    let clickup_api = ClickUpApiInstance::new(
+        clickup_access_token(),
+        env::var("CLICKUP_TARGET_LIST_ID").unwrap_or_default(),
+    );
+
+    info!("Browsing ClickUp, this can take a while for big workspaces...");
+    let workspaces = discover(&clickup_api).expect("Failed to browse ClickUp!");
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&workspaces).expect("Failed to serialise workspaces!")
+        );
+    } else {
+        print!("{}", render_tree(&workspaces));
+    }
+}
+
+/// Shares the rate limit of the ClickUp API with every other process using the same token, if the config says to
+///
+/// ### Arguments
+/// * `clickup_api` - The instance of the ClickUp API to share the rate limit of
+/// * `config` - The `[rate_limit]` section of the config file
+fn share_rate_limit(clickup_api: &mut ClickUpApiInstance, config: &RateLimitConfig) {
+    match config {
+        RateLimitConfig::Memory => {}
+        RateLimitConfig::Database => {
+            // Stateless mode doesn't otherwise use the database, so it might not be set up
+            run_migrations(&mut db_pool().get().expect("Failed to connect to the database!"))
+                .expect("Failed to run migrations!");
+            info!("Sharing the ClickUp rate limit through the database");
+            let rate_limit = DbRateLimit::connect().expect("Failed to connect to the database!");
+            clickup_api.share_rate_limit(Box::new(rate_limit));
+        }
+        RateLimitConfig::File { path } => {
+            info!("Sharing the ClickUp rate limit through {}", path.display());
+            clickup_api.share_rate_limit(Box::new(FileRateLimit::new(path.clone())));
+        }
+    }
+}
+
+/// Creates or updates the list described in the `[provision]` section of the config file, so it has everything the sync needs
+/// (see provisioning.rs). Exits with an error if anything has to be fixed by hand.
+///
+/// ### Arguments
+/// * `config` - The `[provision]` section of the config file
+/// * `rate_limit` - The `[rate_limit]` section of the config file
+fn provision_list(config: Option<&ProvisionConfig>, rate_limit: &RateLimitConfig) {
+    let Some(config) = config else {
+        error!("Nothing to provision: add a [provision] section to the config file");
+        std::process::exit(2);
+    };
+    let mut clickup_api = ClickUpApiInstance::new(
+        clickup_access_token(),
+        env::var("CLICKUP_TARGET_LIST_ID").unwrap_or_default(),
+    );
+    share_rate_limit(&mut clickup_api, rate_limit);
+
+    let report = provision(&clickup_api, config).expect("Failed to provision list!");
+    for change in &report.changes {
+        info!("{}", change);
+    }
+    if report.changes.is_empty() {
+        info!("List is already set up, nothing changed");
+    }
+    info!(
+        "List ID is {} (put this in CLICKUP_TARGET_LIST_ID)",
+        report.list_id
+    );
+
+    if !report.problems.is_empty() {
+        for problem in &report.problems {
+            error!("{}", problem);
+        }
+        std::process::exit(1);
+    }
+}
+
+/// What to do, from the command line arguments
+enum Command {
+    /// Sync the calendar to ClickUp (the default)
+    Sync,
+    /// Match existing tasks in the lists we sync to to events & write mappings for them, instead of syncing
+    Adopt,
+    /// Authorise the ClickUp OAuth app & store its token, instead of syncing
+    Authorize,
+    /// Create or update the list in the [provision] section of the config, instead of syncing
+    Provision,
+    /// Print the workspaces, spaces, folders & lists the access token can see, as a tree or as JSON (with --json)
+    Discover { json: bool },
+    /// Print the recent sync runs, and exit with an error if the last successful one was more than this many hours ago
+    /// (--stale-after, default `DEFAULT_STALE_AFTER_HOURS`)
+    Status { stale_after_hours: i64 },
+    /// Undo the changes a sync run made to tasks (--run, the ID of the run from the status command)
+    Undo { run_id: i32 },
+}
+
+impl Command {
+    fn from_args() -> Self {
+        let args = env::args().skip(1).collect::<Vec<_>>();
+        match args
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [] | ["sync"] => Command::Sync,
+            ["adopt"] => Command::Adopt,
+            ["provision"] => Command::Provision,
+            ["authorize"] => Command::Authorize,
+            ["discover"] => Command::Discover { json: false },
+            ["discover", "--json"] => Command::Discover { json: true },
+            ["status"] => Command::Status {
+                stale_after_hours: DEFAULT_STALE_AFTER_HOURS,
+            },
+            ["status", "--stale-after", hours] => match hours.parse() {
+                Ok(stale_after_hours) => Command::Status { stale_after_hours },
+                Err(_) => {
+                    error!("--stale-after should be a number of hours, not {:?}", hours);
+                    std::process::exit(2);
+                }
+            },
+            ["undo", "--run", run_id] => match run_id.parse() {
+                Ok(run_id) => Command::Undo { run_id },
+                Err(_) => {
+                    error!("--run should be the ID of a sync run, not {:?}", run_id);
+                    std::process::exit(2);
+                }
+            },
+            other => {
+                error!(
+                    "Unknown command {:?}, expected sync, adopt, provision, authorize, discover [--json], status [--stale-after <hours>] or undo --run <id>",
+                    other
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+/// Prints the recent sync runs (see history.rs), and exits with an error if the sync hasn't succeeded recently enough,
+/// so it can be used as a monitoring check
+///
+/// ### Arguments
+/// * `stale_after_hours` - How long since the last successful run before the sync counts as stale
+fn show_status(stale_after_hours: i64) {
+    let mut connection = db_pool().get().expect("Failed to connect to the database!");
+    let runs = recent_sync_runs(&mut connection, RECENT_RUNS).expect("Failed to load sync runs!");
+    print!("{}", render_runs(&runs));
+
+    let last_success =
+        last_successful_sync_run(&mut connection).expect("Failed to load sync runs!");
+    match staleness(
+        last_success.as_ref(),
+        Utc::now().naive_utc(),
+        chrono::Duration::hours(stale_after_hours),
+    ) {
+        Some(problem) => {
+            error!("{}", problem);
+            std::process::exit(1);
         }
+        None => info!("The sync is running fine"),
     }
+}
+
+/// Undoes the changes a sync run made to tasks, newest first, using the audit log (see audit.rs).
+/// Exits with an error if ClickUp gave an error for any of them (which are tried again if the run is undone again).
+///
+/// ### Arguments
+/// * `config` - The config file
+/// * `run_id` - The ID of the run to undo
+fn undo(config: &SyncConfig, run_id: i32) {
+    // Let go of the connection before undoing anything, as the pool might only have the one
+    let (records, changed_later) = {
+        let mut connection = db_pool().get().expect("Failed to connect to the database!");
+        let records =
+            load_audit_records(&mut connection, run_id).expect("Failed to load the audit log!");
+        let changed_later =
+            tasks_changed_after(&mut connection, run_id).expect("Failed to load the audit log!");
+        (records, changed_later)
+    };
+    if records.is_empty() {
+        info!(
+            "Nothing to undo: run {} made no changes to tasks, or they've already been undone",
+            run_id
+        );
+        return;
+    }
+
+    let clickup_api = clickup_api_for(config);
+    let context = SyncContext::load(&clickup_api, config, None)
+        .expect("Failed to set up the sync from the config!");
+    let mut store = DbStore::new(db_pool().clone(), &clickup_api.target_list_id);
+    let report = undo_run(&clickup_api, &context, &mut store, &records, &changed_later)
+        .expect("Failed to undo run!");
+
+    for (audit_id, reason) in &report.skipped {
+        warn!("Left change {} alone: {}", audit_id, reason);
+    }
+    for (audit_id, err) in &report.failed {
+        error!("Failed to undo change {}: {}", audit_id, err);
+    }
+    info!(
+        "Undid {} of the {} changes run {} made ({} left alone, {} failed)",
+        report.undone.len(),
+        records.len(),
+        run_id,
+        report.skipped.len(),
+        report.failed.len()
+    );
+    if !report.failed.is_empty() {
+        std::process::exit(1);
+    }
+}
 
-    // Delete from DB as last act
-    debug!("Deleting mappings: {:?}", mappings_to_delete);
-    diesel::delete(clickup_ical_mapping.filter(calendar_id.eq_any(mappings_to_delete)))
-        .execute(connection)
-        .expect("Error deleting mappings!");
+/// The message of a panic, for recording why a run failed
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown error".to_string()
+    }
 }
 
 /// Main entry point for the DoCSoc ClickUp calendar sync tool.
@@ -153,9 +444,127 @@ fn main() {
     info!("DoCSoc ClickUp calendar sync");
     info!("CWD: {}", env::current_dir().unwrap().display());
 
-    info!("Updating DB...");
-    run_migrations(&mut establish_connection()).expect("Failed to run migrations!");
+    let command = Command::from_args();
+
+    // Authorising only needs the database to put the token in
+    if let Command::Authorize = command {
+        authorize();
+        return;
+    }
+
+    // Discovery is for setting the tool up, so doesn't need the calendar, config or database
+    if let Command::Discover { json } = command {
+        discover_workspaces(json);
+        return;
+    }
+
+    let config = load_config().expect("Failed to load sync config!");
+
+    // Provisioning sets up the list to sync into, so doesn't need the calendar or database either
+    if let Command::Provision = command {
+        provision_list(config.provision.as_ref(), &config.rate_limit);
+        return;
+    }
+
+    // In stateless mode the mappings live on the tasks, so there's no database to set up
+    if config.markers.stateless {
+        info!("Running in stateless mode, no database needed");
+        if let Command::Adopt = command {
+            error!("Nothing to adopt in stateless mode: tasks are found by the UID in their uid_field each run");
+            std::process::exit(2);
+        }
+        if let Command::Status { .. } = command {
+            error!("No run history in stateless mode: runs are only recorded in the database");
+            std::process::exit(2);
+        }
+        if let Command::Undo { .. } = command {
+            error!("Nothing to undo in stateless mode: changes to tasks are only recorded in the database");
+            std::process::exit(2);
+        }
+    } else {
+        info!("Updating DB...");
+        run_migrations(&mut db_pool().get().expect("Failed to connect to the database!"))
+            .expect("Failed to run migrations!");
+    }
+
+    if let Command::Status { stale_after_hours } = command {
+        show_status(stale_after_hours);
+        return;
+    }
+
+    if let Command::Undo { run_id } = command {
+        undo(&config, run_id);
+        return;
+    }
+
+    // Sync runs are recorded, so the status command can tell when the sync has stopped working (see history.rs)
+    let run_id = match command {
+        Command::Sync if !config.markers.stateless => Some(
+            start_sync_run(&mut db_pool().get().expect("Failed to connect to the database!"))
+                .expect("Failed to record sync run!"),
+        ),
+        _ => None,
+    };
+    let mut calendar = None;
+    // Failures come back with what the run did before it stopped; panics are only caught so the run is still recorded
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        sync(&config, &command, run_id, &mut calendar)
+    }));
+    if let Some(run_id) = run_id {
+        let (stats, run_error) = match &outcome {
+            Ok(Ok(stats)) => (stats.clone(), None),
+            Ok(Err((stats, err))) => (stats.clone(), Some(err.to_string())),
+            Err(panic) => (SyncStats::default(), Some(panic_message(&**panic))),
+        };
+        finish_sync_run(
+            &mut db_pool().get().expect("Failed to connect to the database!"),
+            run_id,
+            calendar.as_deref(),
+            &stats,
+            run_error.as_deref(),
+        )
+        .expect("Failed to record sync run!");
+    }
+    match outcome {
+        Ok(Ok(_)) => {}
+        Ok(Err((_, err))) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+        // The panic has already been logged, this just exits with its status
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+/// Sets up the ClickUp API as the config says, to sync with (or undo a sync)
+///
+/// ### Arguments
+/// * `config` - The config file
+fn clickup_api_for(config: &SyncConfig) -> ClickUpApiInstance {
+    debug!("Loading clickup API...");
+    let mut clickup_api = ClickUpApiInstance::new(
+        clickup_access_token(),
+        env::var("CLICKUP_TARGET_LIST_ID").expect("CLICKUP_TARGET_LIST_ID env var must be set!"),
+    );
+    share_rate_limit(&mut clickup_api, &config.rate_limit);
+    clickup_api
+}
 
+/// Syncs the calendar to ClickUp (or adopts existing tasks, if that's the command)
+///
+/// ### Arguments
+/// * `config` - The config file
+/// * `command` - Whether to sync or adopt
+/// * `run_id` - The ID of the run, to record the changes made to tasks against in the audit log (None if they aren't recorded)
+/// * `calendar` - Set to the name of the calendar once it's downloaded, so it's known even if the sync then fails
+/// ### Returns
+/// What the sync did (nothing, when adopting), or why it failed along with what it did before it stopped
+fn sync(
+    config: &SyncConfig,
+    command: &Command,
+    run_id: Option<i32>,
+    calendar: &mut Option<String>,
+) -> Result<SyncStats, (SyncStats, Box<dyn Error>)> {
     // 1: Load ical fil from Google Calendar, using the URL in the env var ICAL_SYNC_PRIVATE_ICAL
     info!("Downloading iCal...");
     // get ical from env var ICAL_SYNC_PRIVATE_ICAL
@@ -163,30 +572,62 @@ fn main() {
         env::var("ICAL_SYNC_PRIVATE_ICAL").expect("ICAL_SYNC_PRIVATE_ICAL env var must be set!");
 
     info!("Fetching iCal from: {}", ical_url);
-    let ical_content = fetch_ical(&ical_url).expect("Failed to fetch iCal file");
+    let ical_content = fetch_ical(&ical_url).map_err(|err| {
+        (
+            SyncStats::default(),
+            format!("Failed to fetch iCal file: {}", err).into(),
+        )
+    })?;
+    *calendar = calendar_name(&ical_content);
 
     info!("iCal fetched successfully!");
 
     // 2: Create a single instance of the ClickUp API to use for the whole program
     // (specifically this is an object that wraps calls to the API and handles rate limiting for us)
-    debug!("Loading clickup API...");
-    let clickup_api = ClickUpApiInstance::new(
-        env::var("CLICKUP_ACCESS_TOKEN").expect("CLICKUP_ACCESS_TOKEN env var must be set!"),
-        env::var("CLICKUP_TARGET_LIST_ID").expect("CLICKUP_TARGET_LIST_ID env var must be set!"),
-    );
+    let clickup_api = clickup_api_for(config);
+    let context = SyncContext::load(&clickup_api, config, calendar.clone()).map_err(|err| {
+        let stats = SyncStats {
+            requests: clickup_api.requests_sent(),
+            ..Default::default()
+        };
+        (
+            stats,
+            format!("Failed to set up the sync from the config: {}", err).into(),
+        )
+    })?;
 
     // 3: Parse ical from ICAL_SYNC_START_DATE to ICAL_SYNC_END_DATE (we don't want to include events from before the current committee!)
     let ical_parsed = parse_ical(&ical_content);
 
-    // 4: For each event in the iCal, map it to ClickUp (or update the existing task if it's already mapped)
-    let mut set_of_event_ids: HashSet<String> = std::collections::HashSet::new(); // needed for ensure_mappings_are_up_to_date()
-    for event in ical_parsed {
-        set_of_event_ids.insert(event.uid.clone());
-        map_event(event, &clickup_api);
+    // Mappings are kept in the database, or on the tasks themselves in stateless mode
+    let mut store: Box<dyn MappingStore> = if config.markers.stateless {
+        Box::new(ClickUpStore::new(&clickup_api, &context))
+    } else {
+        let mut store = DbStore::new(db_pool().clone(), &clickup_api.target_list_id);
+        // So the run can be undone (see audit.rs)
+        if let Some(run_id) = run_id {
+            store.set_run(run_id);
+        }
+        Box::new(store)
+    };
+
+    // If asked, adopt tasks that already exist instead of syncing
+    if let Command::Adopt = command {
+        adopt_existing_tasks(
+            &clickup_api,
+            &context,
+            store.as_mut(),
+            &ical_parsed.collect::<Vec<_>>(),
+        );
+        info!("Adoption complete! Run the sync as usual to update the adopted tasks.");
+        return Ok(SyncStats::default());
     }
 
-    // 5: Delete events from ClickUp no longer in iCal
-    ensure_mappings_are_up_to_date(&clickup_api, &set_of_event_ids);
+    // 4: For each event in the iCal, map it to ClickUp (or update the existing task if it's already mapped),
+    // then delete events from ClickUp no longer in iCal
+    let stats = sync_events(&clickup_api, &context, store.as_mut(), ical_parsed)
+        .map_err(|(stats, err)| (stats, format!("Failed to sync events: {}", err).into()))?;
 
     info!("Sync complete!");
+    Ok(stats)
 }
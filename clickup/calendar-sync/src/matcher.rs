@@ -0,0 +1,38 @@
+/// Matching events on their category & summary, as assignee rules, routes, templates and meeting notes rules do
+use regex::Regex;
+
+use crate::docsoc_ical::ParsedEvent;
+
+/// The category & summary an event must have for a rule from the config to apply to it.
+/// If both are given, both must match, and if neither is given every event matches.
+#[derive(Debug)]
+pub struct EventMatcher {
+	/// Category the event must have (case insensitive), if any
+	category: Option<String>,
+	/// Regex the summary of the event must match, if any
+	summary: Option<Regex>,
+}
+
+impl EventMatcher {
+	/// Build a matcher from a rule in the config
+	/// ### Arguments
+	/// * `category` - Category the event must have, if any
+	/// * `summary` - Regex the summary of the event must match, if any
+	/// ### Returns
+	/// The matcher, or an error if the summary regex is invalid
+	pub fn new(category: Option<&str>, summary: Option<&str>) -> Result<Self, regex::Error> {
+		Ok(Self {
+			category: category.map(str::to_string),
+			summary: summary.map(Regex::new).transpose()?,
+		})
+	}
+
+	/// Whether the rule applies to events like this one
+	pub fn matches(&self, event: &ParsedEvent) -> bool {
+		let category_matches = self.category.as_ref()
+			.is_none_or(|category| event.categories.iter().any(|event_category| event_category.eq_ignore_ascii_case(category)));
+		let summary_matches = self.summary.as_ref()
+			.is_none_or(|summary| summary.is_match(&event.summary));
+		category_matches && summary_matches
+	}
+}
@@ -0,0 +1,64 @@
+/// Meeting notes documents in ClickUp Docs for meetings in the calendar
+///
+/// For events matching a rule (e.g. category "Meeting"), an agenda/minutes document is made in ClickUp Docs from a
+/// template filled in from the event (e.g. the date & attendees), and linked from the task, either in a URL custom
+/// field or in a comment. The ID of the document is kept with the mapping, so each event only ever gets one.
+use crate::{config::MeetingNotesRuleConfig, docsoc_ical::ParsedEvent, matcher::EventMatcher};
+
+/// A rule from the config, with its regex compiled
+#[derive(Debug)]
+pub struct MeetingNotesRule {
+	/// Which events the rule applies to
+	matcher: EventMatcher,
+	/// Name of the document, to fill in from the event
+	title: String,
+	/// Contents of the document in markdown, to fill in from the event
+	template: String,
+}
+
+impl MeetingNotesRule {
+	/// Build a rule from the config
+	/// ### Returns
+	/// The rule, or an error if the summary regex is invalid
+	pub fn new(config: &MeetingNotesRuleConfig) -> Result<Self, regex::Error> {
+		Ok(Self {
+			matcher: EventMatcher::new(config.category.as_deref(), config.summary.as_deref())?,
+			title: config.title.clone(),
+			template: config.template.clone(),
+		})
+	}
+
+	/// Whether events like this one should get a document from this rule
+	pub fn matches(&self, event: &ParsedEvent) -> bool {
+		self.matcher.matches(event)
+	}
+
+	/// Name of the document for an event
+	pub fn title_for(&self, event: &ParsedEvent) -> String {
+		event.render(&self.title)
+	}
+
+	/// Contents of the document for an event, in markdown
+	pub fn content_for(&self, event: &ParsedEvent) -> String {
+		event.render(&self.template)
+	}
+}
+
+/// The first rule that matches an event, if any
+pub fn rule_for<'a>(rules: &'a [MeetingNotesRule], event: &ParsedEvent) -> Option<&'a MeetingNotesRule> {
+	rules.iter().find(|rule| rule.matches(event))
+}
+
+/// Link to a document in ClickUp
+/// ### Arguments
+/// * `workspace_id` - ID of the workspace the document is in
+/// * `doc_id` - ID of the document
+pub fn doc_url(workspace_id: &str, doc_id: &str) -> String {
+	format!("https://app.clickup.com/{}/v/dc/{}", workspace_id, doc_id)
+}
+
+/// The ID of the document a link from `doc_url` is to
+pub fn doc_id_from_url(url: &str) -> Option<&str> {
+	let (_, doc_id) = url.trim_end_matches('/').rsplit_once("/v/dc/")?;
+	Some(doc_id.split('/').next().unwrap_or(doc_id)).filter(|doc_id| !doc_id.is_empty())
+}
@@ -1,13 +1,119 @@
 /// This file contains the models for the database tables. The models are used by Diesel to generate the SQL queries.
 /// Make sure to create a new diesel migration after changing the models!!!!!
 use diesel::prelude::*;
+use std::collections::BTreeSet;
+
+use crate::{config::DeletionPolicy, ownership::SyncedFields};
+use chrono::NaiveDateTime;
 
 #[derive(Queryable, Selectable)]
 #[diesel(table_name = crate::schema::clickup_ical_mapping)]
-#[diesel(check_for_backend(diesel::pg::Pg))]
-#[derive(Debug)]
+#[diesel(check_for_backend(crate::db::DbBackend))]
+#[derive(Debug, Clone)]
 pub struct CalendarMapping {
     pub mapping_id: i32,
     pub clickup_id: String,
     pub calendar_id: String,
-}
\ No newline at end of file
+    /// JSON array of the ClickUp user IDs the sync assigned to the task last time
+    pub synced_assignees: String,
+    /// If the event has disappeared from the calendar but the task was kept, how it was retired (JSON of the `DeletionPolicy`)
+    pub tombstone: Option<String>,
+    /// What the sync last wrote to the task (JSON of `SyncedFields`), or None if it hasn't been recorded yet
+    pub last_synced: Option<String>,
+    /// ID of the ClickUp list the task is in (the event can have a task in more than one list)
+    pub list_id: String,
+    /// Whether someone deleted the task by hand in ClickUp, so we've stopped syncing the event
+    pub user_deleted: bool,
+    /// ID of the meeting notes document made in ClickUp Docs for the event, if any
+    pub meeting_doc: Option<String>,
+    /// Fingerprint of the event the task was last synced from (see `SyncContext::fingerprint_for`), so unchanged events can be skipped
+    pub fingerprint: Option<String>,
+}
+
+impl CalendarMapping {
+    /// The ClickUp user IDs the sync assigned to the task last time
+    pub fn synced_assignees(&self) -> BTreeSet<i64> {
+        serde_json::from_str(&self.synced_assignees).unwrap_or_default()
+    }
+
+    /// What the sync last wrote to the task, if recorded
+    pub fn last_synced(&self) -> Option<SyncedFields> {
+        self.last_synced
+            .as_deref()
+            .and_then(|last_synced| serde_json::from_str(last_synced).ok())
+    }
+
+    /// How the task was retired when its event disappeared, or None if the event is still in the calendar
+    pub fn tombstone(&self) -> Option<DeletionPolicy> {
+        self.tombstone
+            .as_deref()
+            .and_then(|tombstone| serde_json::from_str(tombstone).ok())
+    }
+}
+
+/// Serialise what the sync wrote to a task for storing in `CalendarMapping::last_synced`
+pub fn synced_fields_to_json(fields: &SyncedFields) -> String {
+    serde_json::to_string(fields).expect("Failed to serialise synced fields")
+}
+
+/// Serialise a deletion policy for storing in `CalendarMapping::tombstone`
+pub fn tombstone_to_json(policy: &DeletionPolicy) -> String {
+    serde_json::to_string(policy).expect("Failed to serialise tombstone")
+}
+
+/// Serialise a set of ClickUp user IDs for storing in `CalendarMapping::synced_assignees`
+pub fn assignees_to_json(assignees: &BTreeSet<i64>) -> String {
+    serde_json::to_string(assignees).expect("Failed to serialise assignees")
+}
+
+/// A sync run, from the sync_runs table (see history.rs)
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::sync_runs)]
+#[diesel(check_for_backend(crate::db::DbBackend))]
+#[derive(Debug, Clone)]
+pub struct SyncRun {
+    pub run_id: i32,
+    /// When the run started, in UTC
+    pub started_at: NaiveDateTime,
+    /// When the run finished, in UTC, or None if it's still going (or was killed before it could say)
+    pub finished_at: Option<NaiveDateTime>,
+    /// Name of the calendar synced from, if it was downloaded (and has a name)
+    pub source: Option<String>,
+    /// Tasks created, updated, deleted, skipped & failed (see `SyncStats`)
+    pub created: i32,
+    pub updated: i32,
+    pub deleted: i32,
+    pub skipped: i32,
+    pub failed: i32,
+    /// Requests sent to ClickUp
+    pub requests: i32,
+    /// Why the run failed, or None if it succeeded
+    pub error: Option<String>,
+}
+
+/// A change a sync run made to a task, from the sync_audit table (see audit.rs)
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::sync_audit)]
+#[diesel(check_for_backend(crate::db::DbBackend))]
+#[derive(Debug, Clone)]
+pub struct AuditRecord {
+    pub audit_id: i32,
+    /// The sync run that made the change
+    pub run_id: i32,
+    /// When the change was made, in UTC
+    pub recorded_at: NaiveDateTime,
+    /// What was done to the task: create, update, delete, retire or restore
+    pub operation: String,
+    /// ID of the list the task is in
+    pub list_id: String,
+    /// The ClickUp task ID
+    pub task_id: String,
+    /// The iCal UID of the event the task is for
+    pub event_uid: String,
+    /// JSON of the task (or deletion policy) before the change, if there was anything to record
+    pub payload_before: Option<String>,
+    /// JSON of the task (or deletion policy) after the change, if there was anything to record
+    pub payload_after: Option<String>,
+    /// Whether the `undo` command has undone the change
+    pub undone: bool,
+}
@@ -0,0 +1,94 @@
+/// Protecting edits people make to tasks in ClickUp
+///
+/// Each of the fields we write (name, description, dates) has an ownership setting in the config: the sync either
+/// writes it on every sync, only when creating the task, or never. On top of that, we remember what we last wrote to each
+/// field (`SyncedFields`), so that before updating a task we can fetch it and see if someone has changed a field since
+/// ("drift"). If they have, we either leave their edit alone or overwrite it, depending on the config.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{clickup::ClickUpTask, config::{FieldOwnership, OwnershipConfig}};
+
+/// A field of a task the sync writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskField {
+	Name,
+	Description,
+	/// Start & due date (these go together, as moving an event moves both)
+	Dates,
+}
+
+impl TaskField {
+	/// All the fields the sync writes
+	pub const ALL: [TaskField; 3] = [TaskField::Name, TaskField::Description, TaskField::Dates];
+
+	/// Keys of this field in the task payload we send to ClickUp
+	pub fn payload_keys(&self) -> &'static [&'static str] {
+		match self {
+			TaskField::Name => &["name"],
+			TaskField::Description => &["description"],
+			TaskField::Dates => &["start_date", "start_date_time", "due_date", "due_date_time"],
+		}
+	}
+
+	/// How the config says this field is owned
+	pub fn ownership(&self, config: &OwnershipConfig) -> FieldOwnership {
+		match self {
+			TaskField::Name => config.name,
+			TaskField::Description => config.description,
+			TaskField::Dates => config.dates,
+		}
+	}
+}
+
+impl fmt::Display for TaskField {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			TaskField::Name => write!(f, "name"),
+			TaskField::Description => write!(f, "description"),
+			TaskField::Dates => write!(f, "dates"),
+		}
+	}
+}
+
+/// What the sync last wrote to the fields of a task, stored with the mapping (see `CalendarMapping::last_synced`)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncedFields {
+	pub name: String,
+	pub description: String,
+	/// Start date in milliseconds since the epoch
+	pub start_date: Option<i64>,
+	/// Due date in milliseconds since the epoch
+	pub due_date: Option<i64>,
+	/// Location of the event (not a field of the task itself, but kept so we can say when the room changes)
+	#[serde(default)]
+	pub location: Option<String>,
+}
+
+impl SyncedFields {
+	/// Copy a field from another set of synced fields (e.g. for fields we didn't write this time, so we keep what we wrote before)
+	pub fn copy_field(&mut self, field: TaskField, from: &SyncedFields) {
+		match field {
+			TaskField::Name => self.name = from.name.clone(),
+			TaskField::Description => self.description = from.description.clone(),
+			TaskField::Dates => {
+				self.start_date = from.start_date;
+				self.due_date = from.due_date;
+			}
+		}
+	}
+
+	/// Whether a field of the task in ClickUp still has the value we last wrote to it
+	pub fn matches(&self, field: TaskField, task: &ClickUpTask) -> bool {
+		// ClickUp sends dates back as strings of milliseconds
+		let same_date = |ours: Option<i64>, theirs: &Option<String>| {
+			ours.map(|millis| millis.to_string()) == theirs.as_ref().filter(|date| !date.is_empty()).cloned()
+		};
+		match field {
+			TaskField::Name => self.name.trim() == task.name.trim(),
+			// ClickUp can add trailing whitespace to descriptions
+			TaskField::Description => self.description.trim() == task.description.as_deref().unwrap_or_default().trim(),
+			TaskField::Dates => same_date(self.start_date, &task.start_date) && same_date(self.due_date, &task.due_date),
+		}
+	}
+}
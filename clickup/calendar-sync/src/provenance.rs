@@ -0,0 +1,56 @@
+/// Showing on each task that the sync manages it, and where it came from
+///
+/// Nothing on a task otherwise says it was made by the sync, so people edit tasks without knowing their edits will be
+/// overwritten. With the footer turned on, the description of each task ends with which calendar it came from
+/// (and a link to the event, if it has one) and a notice that the sync manages it.
+///
+/// Tasks can also be marked for machines, with a tag (or the UID custom field, see `SyncContext::load_markers`).
+/// When a marker is configured, tasks without it are never deleted, so the sync can't delete tasks people made by hand.
+use crate::{config::ProvenanceConfig, docsoc_ical::ParsedEvent};
+
+/// Line separating the footer from the rest of the description
+const FOOTER_SEPARATOR: &str = "---";
+
+/// How tasks are marked as the sync's
+#[derive(Debug, Clone, Default)]
+pub struct Provenance {
+	/// Name of the calendar for the footer, or None if there's no footer
+	footer_calendar: Option<String>,
+	/// Tag to put on every task the sync makes, if any
+	marker_tag: Option<String>,
+}
+
+impl Provenance {
+	/// Set up marking tasks from the config
+	/// ### Arguments
+	/// * `config` - The provenance section of the config
+	/// * `ical_calendar_name` - Name of the calendar from the iCal file, used if the config doesn't give one
+	pub fn new(config: &ProvenanceConfig, ical_calendar_name: Option<String>) -> Self {
+		Self {
+			footer_calendar: config.footer.then(|| config.calendar_name.clone().or(ical_calendar_name).unwrap_or_else(|| "Google".to_string())),
+			marker_tag: config.marker_tag.clone(),
+		}
+	}
+
+	/// Tag to put on every task the sync makes, if any
+	pub fn marker_tag(&self) -> Option<&str> {
+		self.marker_tag.as_deref()
+	}
+
+	/// Add the footer to the description from an event, if the footer is turned on
+	pub fn add_footer(&self, description: &str, event: &ParsedEvent) -> String {
+		let Some(calendar) = &self.footer_calendar else {
+			return description.to_string();
+		};
+		let source = match &event.url {
+			Some(url) => format!("Synced from the {} calendar: {}", calendar, url),
+			None => format!("Synced from the {} calendar", calendar),
+		};
+		let footer = format!("{}\n{}\nManaged by calendar sync: change the event in the calendar, as edits to this task may be overwritten.", FOOTER_SEPARATOR, source);
+		if description.trim().is_empty() {
+			footer
+		} else {
+			format!("{}\n\n{}", description.trim_end(), footer)
+		}
+	}
+}
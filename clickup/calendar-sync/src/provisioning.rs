@@ -0,0 +1,115 @@
+/// Setting up a list to sync into from a spec in the config file (the `provision` command)
+///
+/// Each committee year the calendar list gets made again, and it's easy to forget a status or custom field.
+/// Provisioning finds (or makes) the list, then adds whatever tags & custom fields it's missing. It never removes or
+/// renames anything, so it's safe to run again - if everything's already there, it makes no changes.
+///
+/// Some things can't be done through ClickUp's API (adding statuses to a list, or options to an existing dropdown),
+/// so those are reported as problems for someone to fix by hand instead.
+use std::error::Error;
+
+use log::info;
+
+use crate::{clickup::{ClickUpApiInstance, SpaceTag}, config::ProvisionConfig};
+
+/// What provisioning did
+#[derive(Debug, Default)]
+pub struct ProvisionReport {
+	/// ID of the list (what to put in CLICKUP_TARGET_LIST_ID)
+	pub list_id: String,
+	/// Everything we changed in ClickUp, e.g. "Created custom field Location"
+	pub changes: Vec<String>,
+	/// Things that don't match the spec but have to be fixed by hand
+	pub problems: Vec<String>,
+}
+
+/// Create or update a list to match the spec
+/// ### Arguments
+/// * `clickup_api` - The instance of the ClickUp API to use
+/// * `config` - What the list should look like
+/// ### Returns
+/// What was changed, and what still needs fixing by hand
+pub fn provision(clickup_api: &ClickUpApiInstance, config: &ProvisionConfig) -> Result<ProvisionReport, Box<dyn Error>> {
+	if config.space_id.is_empty() || config.list_name.is_empty() {
+		return Err("[provision] needs a space_id and list_name".into());
+	}
+	let mut report = ProvisionReport::default();
+
+	// Find the list, or make it
+	let lists = match &config.folder_id {
+		Some(folder_id) => clickup_api.get_folder_lists(folder_id)?,
+		None => clickup_api.get_folderless_lists(&config.space_id)?,
+	};
+	report.list_id = match lists.into_iter().find(|list| list.name.trim().eq_ignore_ascii_case(config.list_name.trim())) {
+		Some(list) => {
+			info!("Found list {:?} [{}]", list.name, list.id);
+			list.id
+		}
+		None => {
+			let list = clickup_api.create_list(&config.space_id, config.folder_id.as_deref(), &config.list_name)?;
+			report.changes.push(format!("Created list {:?} [{}]", list.name, list.id));
+			list.id
+		}
+	};
+
+	// Statuses come from the space (or folder) unless changed by hand, and the API can't add them
+	let statuses = clickup_api.get_list_statuses(&report.list_id)?;
+	for status in &config.statuses {
+		if !statuses.iter().any(|existing| existing.status.eq_ignore_ascii_case(status)) {
+			report.problems.push(format!("Status {:?} is missing from the list, add it in ClickUp (list settings > statuses)", status));
+		}
+	}
+
+	// Tags live on the space
+	let tags = clickup_api.get_space_tags(&config.space_id)?;
+	for tag_config in &config.tags {
+		let tag = SpaceTag { name: tag_config.name.to_lowercase(), tag_fg: tag_config.fg_color.clone(), tag_bg: tag_config.bg_color.clone() };
+		match tags.iter().find(|existing| existing.name.eq_ignore_ascii_case(&tag.name)) {
+			None => {
+				clickup_api.create_space_tag(&config.space_id, &tag)?;
+				report.changes.push(format!("Created tag {:?}", tag.name));
+			}
+			// Only fix the colours we were given
+			Some(existing) if !same_colour(&existing.tag_fg, &tag.tag_fg) || !same_colour(&existing.tag_bg, &tag.tag_bg) => {
+				let tag = SpaceTag {
+					name: existing.name.clone(),
+					tag_fg: tag.tag_fg.or_else(|| existing.tag_fg.clone()),
+					tag_bg: tag.tag_bg.or_else(|| existing.tag_bg.clone()),
+				};
+				clickup_api.edit_space_tag(&config.space_id, &tag)?;
+				report.changes.push(format!("Changed the colours of tag {:?}", tag.name));
+			}
+			Some(_) => {}
+		}
+	}
+
+	// Custom fields live on the list
+	let fields = clickup_api.get_custom_fields(&report.list_id)?;
+	for spec in &config.custom_fields {
+		let Some(existing) = fields.iter().find(|field| field.name.trim().eq_ignore_ascii_case(spec.name.trim())) else {
+			clickup_api.create_custom_field(&report.list_id, &spec.name, &spec.field_type, &spec.options)?;
+			report.changes.push(format!("Created custom field {:?}", spec.name));
+			continue;
+		};
+		if existing.field_type != spec.field_type {
+			report.problems.push(format!("Custom field {:?} is a {} field rather than {}, change it in ClickUp", existing.name, existing.field_type, spec.field_type));
+			continue;
+		}
+		for option in &spec.options {
+			if existing.option_id(option).is_none() {
+				report.problems.push(format!("Custom field {:?} is missing option {:?}, add it in ClickUp", existing.name, option));
+			}
+		}
+	}
+
+	Ok(report)
+}
+
+/// Whether a tag colour from ClickUp matches the one we want (if we want one at all)
+fn same_colour(existing: &Option<String>, wanted: &Option<String>) -> bool {
+	match (existing, wanted) {
+		(_, None) => true,
+		(Some(existing), Some(wanted)) => existing.eq_ignore_ascii_case(wanted),
+		(None, Some(_)) => false,
+	}
+}
@@ -0,0 +1,116 @@
+/// Sharing the ClickUp rate limit between processes
+///
+/// Each `ClickUpApiInstance` limits itself, but ClickUp's limit is per token, so several sync jobs (or other tools) on the same
+/// token can go over it together. Instead they can all draw from one token bucket, kept in the database (see `db::DbRateLimit`)
+/// or, for processes on the same host, in a lock file (see `FileRateLimit`).
+/// The bucket holds a minute's worth of requests, and refills at the rate limit. Times come from each host's clock, so they should be in sync.
+use std::{error::Error, fs::OpenOptions, io::{Read, Seek, SeekFrom, Write}, path::PathBuf, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+/// Somewhere to keep token buckets that other processes can see
+pub trait SharedBucket {
+	/// Take a token from a bucket, if it has one
+	/// ### Arguments
+	/// * `key` - Which bucket (see `ClickUpApiInstance::rate_limit_key`)
+	/// * `limit` - Requests per minute, which is also how many the bucket holds
+	/// ### Returns
+	/// None if we took a token so can send a request, or how long to wait before trying again
+	fn try_take(&mut self, key: &str, limit: u32) -> Result<Option<Duration>, Box<dyn Error>>;
+}
+
+/// The state of a token bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bucket {
+	/// Requests that can be sent right now (fractions build up as the bucket refills)
+	pub tokens: f64,
+	/// When the tokens were last counted, in milliseconds since the epoch
+	pub updated_at: i64,
+}
+
+impl Bucket {
+	/// A bucket that hasn't been used yet, so has a whole minute's worth of requests in it
+	pub fn full(limit: u32, now: i64) -> Self {
+		Self { tokens: limit as f64, updated_at: now }
+	}
+
+	/// Refill the bucket for the time since it was last used, then take a token if there is one
+	/// ### Arguments
+	/// * `limit` - Requests per minute
+	/// * `now` - The time, in milliseconds since the epoch
+	/// ### Returns
+	/// None if we took a token, or how long until there'll be one
+	pub fn take(&mut self, limit: u32, now: i64) -> Option<Duration> {
+		let per_milli = limit as f64 / 60_000.0;
+		// Another host's clock being ahead shouldn't make ours wait
+		let elapsed = (now - self.updated_at).max(0);
+		self.tokens = (self.tokens + elapsed as f64 * per_milli).min(limit as f64);
+		self.updated_at = self.updated_at.max(now);
+
+		if self.tokens >= 1.0 {
+			self.tokens -= 1.0;
+			None
+		} else {
+			Some(Duration::from_millis(((1.0 - self.tokens) / per_milli).ceil() as u64))
+		}
+	}
+}
+
+/// The time now, in milliseconds since the epoch
+pub fn now_millis() -> i64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_millis() as i64).unwrap_or_default()
+}
+
+/// Keeps token buckets in a file, locked whilst one is taken from so only one process changes it at once
+///
+/// Each line of the file is a bucket, as its key, tokens & updated_at separated by spaces.
+pub struct FileRateLimit {
+	path: PathBuf,
+}
+
+impl FileRateLimit {
+	/// Use the buckets in a file, which is made if it doesn't exist
+	/// ### Arguments
+	/// * `path` - Where the file is (the same path for every process that should share the limit)
+	pub fn new(path: PathBuf) -> Self {
+		Self { path }
+	}
+}
+
+impl SharedBucket for FileRateLimit {
+	fn try_take(&mut self, key: &str, limit: u32) -> Result<Option<Duration>, Box<dyn Error>> {
+		let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&self.path)?;
+		// Held until the file is closed at the end of this function
+		file.lock()?;
+
+		let mut contents = String::new();
+		file.read_to_string(&mut contents)?;
+		// Lines we can't read (e.g. an empty file) are treated as full buckets
+		let mut buckets = contents.lines()
+			.filter_map(|line| {
+				let mut parts = line.split(' ');
+				let key = parts.next()?.to_string();
+				let tokens = parts.next()?.parse().ok()?;
+				let updated_at = parts.next()?.parse().ok()?;
+				Some((key, Bucket { tokens, updated_at }))
+			})
+			.collect::<Vec<_>>();
+
+		let now = now_millis();
+		let wait = match buckets.iter_mut().find(|(bucket_key, _)| bucket_key == key) {
+			Some((_, bucket)) => bucket.take(limit, now),
+			None => {
+				let mut bucket = Bucket::full(limit, now);
+				let wait = bucket.take(limit, now);
+				buckets.push((key.to_string(), bucket));
+				wait
+			}
+		};
+
+		let contents = buckets.iter()
+			.map(|(key, bucket)| format!("{} {} {}\n", key, bucket.tokens, bucket.updated_at))
+			.collect::<String>();
+		file.set_len(0)?;
+		file.seek(SeekFrom::Start(0))?;
+		file.write_all(contents.as_bytes())?;
+		Ok(wait)
+	}
+}
@@ -0,0 +1,48 @@
+/// Working out which lists each event is synced to
+///
+/// By default every event goes to the target list (CLICKUP_TARGET_LIST_ID), but the config can instead give
+/// routes, e.g. every event to the main calendar list, and careers events to the sponsorship team's list too.
+/// An event gets a task in every list whose route matches it, and none if no route matches.
+use crate::{config::RouteConfig, docsoc_ical::ParsedEvent, matcher::EventMatcher};
+
+/// A route from the config, with its regex compiled
+#[derive(Debug)]
+pub struct Route {
+	/// ID of the list to sync matching events to
+	pub list_id: String,
+	/// Which events the route applies to
+	matcher: EventMatcher,
+}
+
+impl Route {
+	/// Build a route from the config
+	/// ### Returns
+	/// The route, or an error if the summary regex is invalid
+	pub fn new(config: &RouteConfig) -> Result<Self, regex::Error> {
+		Ok(Self {
+			list_id: config.list_id.clone(),
+			matcher: EventMatcher::new(config.category.as_deref(), config.summary.as_deref())?,
+		})
+	}
+
+	/// Whether events like this one should be synced to the route's list
+	pub fn matches(&self, event: &ParsedEvent) -> bool {
+		self.matcher.matches(event)
+	}
+}
+
+/// The lists an event should have tasks in
+/// ### Arguments
+/// * `routes` - The routes from the config, in order
+/// * `event` - The event
+/// ### Returns
+/// The ID of every list whose route matches, without duplicates
+pub fn lists_for(routes: &[Route], event: &ParsedEvent) -> Vec<String> {
+	let mut lists = vec![];
+	for route in routes.iter().filter(|route| route.matches(event)) {
+		if !lists.contains(&route.list_id) {
+			lists.push(route.list_id.clone());
+		}
+	}
+	lists
+}
@@ -5,5 +5,69 @@ diesel::table! {
         mapping_id -> Int4,
         clickup_id -> Text,
         calendar_id -> Text,
+        synced_assignees -> Text,
+        tombstone -> Nullable<Text>,
+        last_synced -> Nullable<Text>,
+        list_id -> Text,
+        user_deleted -> Bool,
+        meeting_doc -> Nullable<Text>,
+        fingerprint -> Nullable<Text>,
     }
 }
+
+diesel::table! {
+    clickup_oauth_token (token_id) {
+        token_id -> Int4,
+        encrypted_token -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    clickup_rate_limit (bucket) {
+        bucket -> Text,
+        tokens -> Float8,
+        updated_at -> Int8,
+    }
+}
+
+diesel::table! {
+    sync_audit (audit_id) {
+        audit_id -> Int4,
+        run_id -> Int4,
+        recorded_at -> Timestamp,
+        operation -> Text,
+        list_id -> Text,
+        task_id -> Text,
+        event_uid -> Text,
+        payload_before -> Nullable<Text>,
+        payload_after -> Nullable<Text>,
+        undone -> Bool,
+    }
+}
+
+diesel::table! {
+    sync_runs (run_id) {
+        run_id -> Int4,
+        started_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+        source -> Nullable<Text>,
+        created -> Int4,
+        updated -> Int4,
+        deleted -> Int4,
+        skipped -> Int4,
+        failed -> Int4,
+        requests -> Int4,
+        error -> Nullable<Text>,
+    }
+}
+
+diesel::joinable!(sync_audit -> sync_runs (run_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    clickup_ical_mapping,
+    clickup_oauth_token,
+    clickup_rate_limit,
+    sync_audit,
+    sync_runs,
+);
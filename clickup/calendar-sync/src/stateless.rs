@@ -0,0 +1,137 @@
+/// Stateless mode: keeping the mappings on the ClickUp tasks themselves, instead of in the database
+///
+/// Each task has the iCal UID of its event (and optionally a fingerprint of the event) written into custom fields
+/// (see `SyncContext::load_markers`), so at the start of each run we can rebuild the mappings by listing the
+/// tasks in the target list. This means the sync only needs a ClickUp token to run, but:
+/// - Nothing is remembered between runs apart from what's on the tasks, so drift detection is off, and people
+///   the sync assigned are never unassigned
+/// - Retired tasks are found again from the deletion policy (archived tasks, tasks with the cancelled status or tag,
+///   or tasks in the graveyard list), so the deletion policy shouldn't be changed whilst tasks are retired
+///   (and the move policy can only be used when syncing to a single list)
+use log::warn;
+use std::{collections::BTreeSet, error::Error};
+
+use crate::{
+	clickup::{ClickUpApiInstance, ClickUpTask},
+	config::DeletionPolicy,
+	meeting_notes::doc_id_from_url,
+	models::*,
+	ownership::SyncedFields,
+	store::MappingStore,
+	sync::SyncContext,
+};
+
+/// Keeps mappings on the tasks in ClickUp
+pub struct ClickUpStore<'a> {
+	clickup_api: &'a ClickUpApiInstance,
+	/// How the sync is set up, for the fields the UID (etc.) are kept in & the deletion policy
+	context: &'a SyncContext,
+}
+
+impl<'a> ClickUpStore<'a> {
+	pub fn new(clickup_api: &'a ClickUpApiInstance, context: &'a SyncContext) -> Self {
+		Self { clickup_api, context }
+	}
+
+	/// How a task was retired, going by the deletion policy and the state the task is in
+	fn tombstone_for(&self, task: &ClickUpTask) -> Option<DeletionPolicy> {
+		let policy = self.context.deletion_policy();
+		let retired = match policy {
+			DeletionPolicy::Status { status } => task.status.as_ref().is_some_and(|task_status| task_status.status.eq_ignore_ascii_case(status)),
+			DeletionPolicy::Tag { tag } => task.tags.iter().any(|task_tag| task_tag.name.eq_ignore_ascii_case(tag)),
+			_ => false,
+		};
+		retired.then(|| policy.clone())
+	}
+}
+
+impl MappingStore for ClickUpStore<'_> {
+	fn load(&mut self) -> Result<Vec<CalendarMapping>, Box<dyn Error>> {
+		let (clickup_api, context) = (self.clickup_api, self.context);
+		let mut mappings: Vec<CalendarMapping> = vec![];
+		for list_id in context.list_ids() {
+			let uid_field = context.uid_field(&list_id)
+				.ok_or("Stateless mode needs a uid_field to keep the UID of each event on its task")?;
+
+			// Tasks in the list, plus wherever retired tasks from it end up
+			let mut tasks = clickup_api.get_list_tasks(&list_id, false)?.into_iter()
+				.map(|task| {
+					let tombstone = self.tombstone_for(&task);
+					(task, tombstone)
+				})
+				.collect::<Vec<_>>();
+			match context.deletion_policy() {
+				policy @ DeletionPolicy::Archive => tasks.extend(clickup_api.get_list_tasks(&list_id, true)?.into_iter()
+					.map(|task| (task, Some(policy.clone())))),
+				// Only allowed with a single list (see `SyncContext::load_markers`), so everything in the graveyard is from this list
+				policy @ DeletionPolicy::Move { list_id: graveyard_list_id } => tasks.extend(clickup_api.get_list_tasks(graveyard_list_id, false)?.into_iter()
+					.map(|task| (task, Some(policy.clone())))),
+				_ => {}
+			}
+
+			for (task, tombstone) in tasks {
+				// Subtasks (e.g. from templates) can carry a copy of their parent's UID, but aren't for the event themselves
+				if task.parent.is_some() {
+					continue;
+				}
+				// Tasks without a UID weren't made by the sync
+				let Some(uid) = task.custom_field_text(uid_field) else {
+					continue;
+				};
+				// e.g. if someone duplicated a task in ClickUp, which copies its custom fields
+				if let Some(existing) = mappings.iter().find(|mapping| mapping.calendar_id == uid && mapping.list_id == list_id) {
+					warn!("Tasks {} and {} are both for event ID {} in list {}, ignoring {}", existing.clickup_id, task.id, uid, list_id, task.id);
+					continue;
+				}
+
+				// The link to the meeting notes is the only record of them
+				let meeting_doc = context.notes_field(&list_id)
+					.and_then(|field| task.custom_field_text(field))
+					.and_then(doc_id_from_url)
+					.map(str::to_string);
+				mappings.push(CalendarMapping {
+					mapping_id: 0,
+					clickup_id: task.id.clone(),
+					calendar_id: uid.to_string(),
+					synced_assignees: assignees_to_json(&BTreeSet::new()),
+					tombstone: tombstone.as_ref().map(tombstone_to_json),
+					last_synced: None,
+					list_id: list_id.clone(),
+					user_deleted: false,
+					meeting_doc,
+					fingerprint: context.fingerprint_field(&list_id)
+						.and_then(|field| task.custom_field_text(field))
+						.map(str::to_string),
+				});
+			}
+		}
+		Ok(mappings)
+	}
+
+	fn insert(&mut self, calendar_id: &str, list_id: &str, clickup_id: &str, _synced_assignees: &BTreeSet<i64>, _last_synced: Option<&SyncedFields>, fingerprint: Option<&str>) -> Result<CalendarMapping, Box<dyn Error>> {
+		// The UID (and fingerprint) are already on the task, as create_task wrote them
+		Ok(CalendarMapping {
+			mapping_id: 0,
+			clickup_id: clickup_id.to_string(),
+			calendar_id: calendar_id.to_string(),
+			synced_assignees: assignees_to_json(&BTreeSet::new()),
+			tombstone: None,
+			last_synced: None,
+			list_id: list_id.to_string(),
+			user_deleted: false,
+			meeting_doc: None,
+			// Only kept if there's a field to keep it in
+			fingerprint: fingerprint.filter(|_| self.context.fingerprint_field(list_id).is_some()).map(str::to_string),
+		})
+	}
+
+	fn update(&mut self, _mapping: &CalendarMapping) -> Result<(), Box<dyn Error>> {
+		// Nothing to save: the state of the task is the state of the mapping
+		Ok(())
+	}
+
+	fn delete(&mut self, _mappings: &[CalendarMapping]) -> Result<(), Box<dyn Error>> {
+		// The tasks are gone, so their mappings are too
+		Ok(())
+	}
+}
@@ -0,0 +1,63 @@
+/// Setting the status of tasks based on where their event is in its lifecycle
+///
+/// The config has an ordered list of rules, e.g. "done" once the event has ended, "this week" if it starts
+/// within 7 days, and "upcoming" otherwise. The first rule that matches the event gives the status of the task.
+/// Rules are checked against the statuses of the target list when we start up, as ClickUp rejects unknown statuses.
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{
+	config::{StatusCondition, StatusRuleConfig},
+	docsoc_ical::ParsedEvent,
+};
+
+/// A status rule from the config, checked against the list's statuses
+#[derive(Debug, Clone)]
+pub struct StatusRule {
+	/// Status to set, as it is named in ClickUp
+	pub status: String,
+	/// When the rule applies
+	pub when: StatusCondition,
+	/// Number of days, for `StartsWithin`
+	pub days: i64,
+}
+
+/// Check the status rules from the config against the statuses of the target list
+/// ### Arguments
+/// * `config` - The status rules from the config
+/// * `list_statuses` - Names of the statuses of the target list
+/// ### Returns
+/// The rules, using the list's spelling of each status, or an error if a status doesn't exist or a rule is missing `days`
+pub fn resolve_status_rules(config: &[StatusRuleConfig], list_statuses: &[String]) -> Result<Vec<StatusRule>, String> {
+	config.iter().map(|rule| {
+		let status = list_statuses.iter()
+			.find(|status| status.eq_ignore_ascii_case(&rule.status))
+			.ok_or_else(|| format!("No status {:?} on the target list (it has: {})", rule.status, list_statuses.join(", ")))?;
+
+		let days = match (rule.when, rule.days) {
+			(StatusCondition::StartsWithin, Some(days)) => days,
+			(StatusCondition::StartsWithin, None) => return Err(format!("Status rule for {:?} needs days set", rule.status)),
+			_ => 0,
+		};
+
+		Ok(StatusRule { status: status.clone(), when: rule.when, days })
+	}).collect()
+}
+
+impl StatusRule {
+	/// Whether the rule applies to an event at the given time
+	fn matches(&self, event: &ParsedEvent, now: NaiveDateTime) -> bool {
+		match self.when {
+			StatusCondition::Ended => event.end_time.is_some_and(|end| end <= now),
+			StatusCondition::Started => event.start_time.is_some_and(|start| start <= now) && event.end_time.is_none_or(|end| now < end),
+			StatusCondition::StartsWithin => event.start_time.is_some_and(|start| now <= start && start < now + Duration::days(self.days)),
+			StatusCondition::Always => true,
+		}
+	}
+}
+
+/// Work out the status a task should have for an event at the given time
+/// ### Returns
+/// The status of the first matching rule, or None if no rule matches (in which case the status is left alone)
+pub fn status_for(rules: &[StatusRule], event: &ParsedEvent, now: NaiveDateTime) -> Option<String> {
+	rules.iter().find(|rule| rule.matches(event, now)).map(|rule| rule.status.clone())
+}
@@ -0,0 +1,67 @@
+/// Where the mappings between calendar events & ClickUp tasks are kept
+///
+/// Usually this is the database (see `db::DbStore`), but for small deployments they can instead be kept on
+/// the tasks themselves (see `stateless::ClickUpStore`), so the sync can run without a database at all.
+use std::{collections::BTreeSet, error::Error};
+
+use crate::{audit::AuditEntry, models::CalendarMapping, ownership::SyncedFields};
+
+/// Somewhere to load & save mappings
+///
+/// Stores can hold onto changes and save them in batches, so the sync calls `flush` after each step.
+pub trait MappingStore {
+	/// Load every mapping (the sync loads them once at the start of each run)
+	fn load(&mut self) -> Result<Vec<CalendarMapping>, Box<dyn Error>>;
+
+	/// Record the task we just created for an event
+	/// ### Arguments
+	/// * `calendar_id` - The iCal UID of the event
+	/// * `list_id` - The ID of the list the task is in
+	/// * `clickup_id` - The ClickUp task ID
+	/// * `synced_assignees` - Who the sync assigned to the task
+	/// * `last_synced` - What the sync wrote to the task, if it should be remembered
+	/// * `fingerprint` - Fingerprint of the event the task was synced from, if it was (adopted tasks haven't been yet)
+	/// ### Returns
+	/// The new mapping
+	fn insert(&mut self, calendar_id: &str, list_id: &str, clickup_id: &str, synced_assignees: &BTreeSet<i64>, last_synced: Option<&SyncedFields>, fingerprint: Option<&str>) -> Result<CalendarMapping, Box<dyn Error>>;
+
+	/// Save changes to the task ID, assignees, tombstone, last synced, user deleted, meeting doc & fingerprint fields of a mapping
+	fn update(&mut self, mapping: &CalendarMapping) -> Result<(), Box<dyn Error>>;
+
+	/// Remove mappings whose tasks are gone
+	fn delete(&mut self, mappings: &[CalendarMapping]) -> Result<(), Box<dyn Error>>;
+
+	/// Save any changes the store is holding onto
+	fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+		Ok(())
+	}
+
+	/// Whether the store keeps an audit log of the changes the sync makes to tasks (see audit.rs), in which case
+	/// the sync fetches tasks before deleting them, so they can be made again if the run is undone
+	fn audited(&self) -> bool {
+		false
+	}
+
+	/// Record a change the sync made to a task in the audit log, if the store keeps one (it's saved along with the mappings,
+	/// so record the change before saving the mapping it goes with)
+	fn record(&mut self, _entry: AuditEntry) -> Result<(), Box<dyn Error>> {
+		Ok(())
+	}
+
+	/// Mark a change in the audit log as undone, if the store keeps one (it's saved along with the mappings, so the mappings
+	/// undoing the change put back are saved together with it)
+	/// ### Arguments
+	/// * `audit_id` - The ID of the change (`AuditRecord::audit_id`)
+	fn mark_undone(&mut self, _audit_id: i32) -> Result<(), Box<dyn Error>> {
+		Ok(())
+	}
+
+	/// Whether the task for a mapping was last synced from exactly this version of the event, so doesn't need updating
+	/// (if the store didn't keep the fingerprint of the event, e.g. stateless mode without a fingerprint field, the task is always updated)
+	/// ### Arguments
+	/// * `mapping` - The mapping of the task
+	/// * `fingerprint` - The fingerprint of the event as it is now (see `SyncContext::fingerprint_for`)
+	fn is_up_to_date(&self, mapping: &CalendarMapping, fingerprint: &str) -> bool {
+		mapping.fingerprint.as_deref() == Some(fingerprint)
+	}
+}
@@ -0,0 +1,1530 @@
+/// The sync itself: bringing the tasks in ClickUp in line with the events in the calendar
+///
+/// Each event has a task in each list it is routed to (see routes.rs). For each event in the calendar, and each of its lists,
+/// we look up its mapping to find its task, and either update the task or create one. Then, for each mapping whose
+/// event is no longer in the calendar (or no longer routed to the mapping's list), we retire its task according to the deletion policy.
+/// If someone has deleted a task by hand, the event is unlinked or its task recreated, according to the missing tasks policy.
+/// Where the mappings are kept is up to the `MappingStore` - usually the database, or the tasks themselves in stateless mode.
+///
+/// The work is planned up front and done most urgent first: new events, then events gone from the calendar, then events that
+/// have changed, then checking for tasks deleted by hand. Events that haven't changed since they were last synced are skipped.
+/// If the run uses up its request budget it stops there, and as the plan is made afresh each run from the calendar & the mappings,
+/// whatever was left is picked up (most urgent first again) next run.
+///
+/// Each change made to a task is recorded with the store (see `MappingStore::record`), so a run can be undone (see audit.rs).
+use chrono::Timelike; // needed for time().hour() so we can check if an event is all day
+use log::{debug, error, info, warn};
+use sha2::{Digest, Sha256};
+use std::{collections::{BTreeSet, HashMap, HashSet}, error::Error};
+
+use crate::{
+	assignees::AssigneeResolver,
+	audit::{AuditEntry, AuditOperation, TaskSnapshot},
+	changes::{change_comment, describe_changes},
+	clickup::{AssigneesPayload, ClickUpApiInstance, ClickUpError, ClickUpTask, CreateTaskPayload, CustomFieldValue},
+	config::{AssigneesConfig, BudgetConfig, CommentsConfig, ConflictPolicy, CustomFieldConfig, DeletionPolicy, DescriptionConfig, FieldOwnership, MarkersConfig, MeetingNotesConfig, MissingTaskPolicy, MissingTasksConfig, OwnershipConfig, ProvenanceConfig, RouteConfig, StatusRuleConfig, SyncConfig, TaskTemplateConfig, TemplateConfig},
+	custom_fields::{resolve_custom_fields, CustomFieldMapping},
+	description::ManagedBlock,
+	docsoc_ical::ParsedEvent,
+	meeting_notes::{doc_url, rule_for, MeetingNotesRule},
+	models::*,
+	ownership::{SyncedFields, TaskField},
+	provenance::Provenance,
+	routes::{lists_for, Route},
+	statuses::{resolve_status_rules, status_for, StatusRule},
+	store::MappingStore,
+	templates::{items_for, Template, CHECKLIST_NAME},
+};
+
+/// Mappings are keyed by (event UID, list ID)
+type MappingKey = (String, String);
+
+/// What a run did, counted per task (so an event synced to two lists counts twice), for the run history (see history.rs)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncStats {
+	/// Tasks created for new events, or recreated after being deleted by hand
+	pub created: u32,
+	/// Tasks updated for changed events, or restored for events that came back
+	pub updated: u32,
+	/// Tasks retired (according to the deletion policy) as their events have gone from the calendar
+	pub deleted: u32,
+	/// Tasks left alone: unchanged, no longer synced, or left for the next run when the request budget ran out
+	pub skipped: u32,
+	/// Tasks ClickUp gave an error for, which are tried again next run
+	pub failed: u32,
+	/// Requests sent to ClickUp
+	pub requests: u32,
+}
+
+/// The custom fields we fill in on one of the lists we sync to
+/// (custom fields have different IDs on each list, unless they're shared by the whole space or folder)
+#[derive(Debug, Default)]
+struct ListFields {
+	/// Custom fields to fill in from each event (see `load_custom_fields`)
+	custom_fields: Vec<CustomFieldMapping>,
+	/// ID of the custom field we write the iCal UID of each event into, if any (see `load_markers`)
+	uid_field: Option<String>,
+	/// ID of the custom field we write the fingerprint of each event into, if any (see `load_markers`)
+	fingerprint_field: Option<String>,
+	/// ID of the URL custom field we put the link to the meeting notes of each event in, if any (see `load_meeting_notes`)
+	notes_field: Option<String>,
+}
+
+/// For lists we haven't loaded any fields for
+const NO_FIELDS: &ListFields = &ListFields { custom_fields: Vec::new(), uid_field: None, fingerprint_field: None, notes_field: None };
+
+/// How the sync is set up from the config file: which lists each event goes to, what we write to its tasks,
+/// and what to do when events change or go. This is loaded once per run (see `load`), looking up & checking
+/// what it needs in ClickUp, and is passed along with the ClickUp API to everything that syncs (or undoes) tasks.
+#[derive(Debug, Default)]
+pub struct SyncContext {
+	/// The list to sync every event to if there are no routes (CLICKUP_TARGET_LIST_ID)
+	target_list_id: String,
+	/// Which lists to sync each event to, or empty to sync every event to the target list only (see `load_routes`)
+	routes: Vec<Route>,
+	/// Custom fields to fill in on each list we sync to, by list ID
+	list_fields: HashMap<String, ListFields>,
+	/// Works out who to assign to each task, if assignment is configured (see `load_assignees`)
+	assignees: Option<AssigneeResolver>,
+	/// Rules for setting the status of tasks from their events (see `load_statuses`)
+	statuses: Vec<StatusRule>,
+	/// What to do with tasks whose events disappear from the calendar (see `load_deletion_policy`)
+	deletion_policy: DeletionPolicy,
+	/// Which fields of tasks we own, and how to handle people editing them (see `set_ownership`)
+	ownership: OwnershipConfig,
+	/// Whether to comment on tasks when they change (see `set_comments`)
+	comments: CommentsConfig,
+	/// What to do when a task the sync made has been deleted by hand (see `set_missing_tasks`)
+	missing_tasks: MissingTasksConfig,
+	/// Subtasks & checklist items to add to the tasks for matching events (see `load_templates`)
+	templates: Vec<Template>,
+	/// ClickUp task templates to create tasks from (see `load_task_template`)
+	task_template: TaskTemplateConfig,
+	/// How tasks are marked as the sync's (see `set_provenance`)
+	provenance: Provenance,
+	/// Rules for which events get meeting notes documents (see `load_meeting_notes`)
+	meeting_notes: Vec<MeetingNotesRule>,
+	/// Markers around the part of the description we write, if the rest is left for people to edit (see `set_description`)
+	managed_description: Option<ManagedBlock>,
+	/// Most requests this run should make, if limited (see `set_budget`)
+	max_requests: Option<u32>,
+}
+
+/// Sync events from the calendar to ClickUp
+/// ### Arguments
+/// * `clickup_api` - The instance of the ClickUp API to use (use once instance for the whole program to ensure consistent rate limiting)
+/// * `context` - How the sync is set up, from the config
+/// * `store` - Where the mappings between events & tasks are kept
+/// * `events` - Every event in the calendar (in the date range we sync)
+/// ### Returns
+/// What the run did, or an error if the mappings couldn't be loaded or saved along with what the run did before it stopped.
+/// Errors from ClickUp are logged & the event is skipped, to be tried again next run.
+pub fn sync_events(clickup_api: &ClickUpApiInstance, context: &SyncContext, store: &mut dyn MappingStore, events: impl IntoIterator<Item = ParsedEvent>) -> Result<SyncStats, (SyncStats, Box<dyn Error>)> {
+	let mut stats = SyncStats::default();
+	let result = sync_planned(clickup_api, context, store, events, &mut stats);
+	stats.requests = clickup_api.requests_sent();
+	info!("Sent {} requests to ClickUp", stats.requests);
+	match result {
+		Ok(()) => Ok(stats),
+		Err(err) => Err((stats, err)),
+	}
+}
+
+/// Plan the run, then do the work most urgent first (see `sync_events`)
+/// ### Arguments
+/// * `clickup_api` - The instance of the ClickUp API to use
+/// * `context` - How the sync is set up, from the config
+/// * `store` - Where the mappings between events & tasks are kept
+/// * `events` - Every event in the calendar (in the date range we sync)
+/// * `stats` - What the run has done, counted as it goes so it's known even if the run stops partway
+fn sync_planned(clickup_api: &ClickUpApiInstance, context: &SyncContext, store: &mut dyn MappingStore, events: impl IntoIterator<Item = ParsedEvent>, stats: &mut SyncStats) -> Result<(), Box<dyn Error>> {
+	let mut mappings = store.load()?.into_iter()
+		.map(|mapping| ((mapping.calendar_id.clone(), mapping.list_id.clone()), mapping))
+		.collect::<HashMap<_, _>>();
+	let plan = plan_sync(context, &*store, &mappings, events);
+	info!("{} new events to create tasks for, {} changed events to update", plan.creates.len(), plan.updates.len());
+	stats.skipped += plan.unchanged;
+
+	// 1: Create tasks for new events
+	for (done, key) in plan.creates.iter().enumerate() {
+		if context.budget_exhausted(clickup_api) {
+			warn!("Request budget used up, leaving {} new events for the next run", plan.creates.len() - done);
+			stats.skipped += (plan.creates.len() - done) as u32;
+			break;
+		}
+		map_event(&plan.events[key], &key.1, clickup_api, context, store, &mut mappings, stats)?;
+	}
+	// Saved after each step, so a run that's stopped partway still remembers the tasks it made
+	store.flush()?;
+
+	// 2: Delete events from ClickUp no longer in iCal
+	ensure_mappings_are_up_to_date(clickup_api, context, store, &mut mappings, &plan.events, stats)?;
+	store.flush()?;
+
+	// 3: Update the tasks of events that have changed
+	for (done, key) in plan.updates.iter().enumerate() {
+		if context.budget_exhausted(clickup_api) {
+			warn!("Request budget used up, leaving {} changed events for the next run", plan.updates.len() - done);
+			stats.skipped += (plan.updates.len() - done) as u32;
+			break;
+		}
+		map_event(&plan.events[key], &key.1, clickup_api, context, store, &mut mappings, stats)?;
+	}
+	store.flush()?;
+
+	// 4: If asked, look for tasks that have been deleted by hand (tasks we've tried to update this run we already know about)
+	if context.missing_tasks().verify {
+		if context.budget_exhausted(clickup_api) {
+			warn!("Request budget used up, leaving verifying tasks for the next run");
+		} else {
+			verify_tasks(clickup_api, context, store, &mut mappings, &plan.events, stats)?;
+			store.flush()?;
+		}
+	}
+	Ok(())
+}
+
+/// The work for a run, split up so the most urgent can be done first (see `sync_events`)
+struct Plan {
+	/// The event for each (event UID, list ID) pair in the calendar
+	events: HashMap<MappingKey, ParsedEvent>,
+	/// Events that don't have a task yet, in calendar order
+	creates: Vec<MappingKey>,
+	/// Events whose tasks need syncing (because the event has changed, come back, or needs meeting notes), in calendar order
+	updates: Vec<MappingKey>,
+	/// How many events' tasks are left alone, as they're up to date or no longer synced
+	unchanged: u32,
+}
+
+/// Work out what needs doing this run
+/// ### Arguments
+/// * `context` - How the sync is set up (nothing is sent to ClickUp)
+/// * `store` - Where the mappings are kept
+/// * `mappings` - All the mappings, by (event UID, list ID)
+/// * `events` - Every event in the calendar (in the date range we sync)
+fn plan_sync(context: &SyncContext, store: &dyn MappingStore, mappings: &HashMap<MappingKey, CalendarMapping>, events: impl IntoIterator<Item = ParsedEvent>) -> Plan {
+	let mut plan = Plan { events: HashMap::new(), creates: vec![], updates: vec![], unchanged: 0 };
+	let mut keys = vec![];
+	for event in events {
+		let list_ids = context.lists_for(&event);
+		if list_ids.is_empty() {
+			debug!("No routes match event {:?}, not syncing it", event.summary);
+		}
+		for list_id in list_ids {
+			// Events that share a UID share a task, which is synced from the last of them
+			let key = (event.uid.clone(), list_id);
+			if plan.events.insert(key.clone(), event.clone()).is_none() {
+				keys.push(key);
+			}
+		}
+	}
+
+	for key in keys {
+		let event = &plan.events[&key];
+		match mappings.get(&key) {
+			None => plan.creates.push(key),
+			Some(mapping) if mapping.user_deleted => {
+				debug!("Task {} was deleted by hand, not syncing event {:?}", mapping.clickup_id, event.summary);
+				plan.unchanged += 1;
+			}
+			Some(mapping) if mapping.tombstone.is_some()
+				|| (mapping.meeting_doc.is_none() && context.wants_meeting_notes(event))
+				|| !store.is_up_to_date(mapping, &context.fingerprint_for(&key.1, event)) => plan.updates.push(key),
+			Some(mapping) => {
+				debug!("Event {:?} unchanged since task {} was last synced, skipping", event.summary, mapping.clickup_id);
+				plan.unchanged += 1;
+			}
+		}
+	}
+	plan
+}
+
+/// Called on each event extracted from the iCal file, for each list it's routed to, to map it to ClickUp using the clikcup API
+/// and also store the mapping.
+/// ### Arguments
+/// * `event` - The parsed event from the iCal file we are mapping
+/// * `list_id` - The list to map it to
+/// * `clickup_api` - The instance of the ClickUp API to use for mapping
+/// * `context` - How the sync is set up, from the config
+/// * `store` - Where to save the mapping
+/// * `mappings` - All the mappings, by (event UID, list ID)
+/// * `stats` - What the run has done so far
+fn map_event(event: &ParsedEvent, list_id: &str, clickup_api: &ClickUpApiInstance, context: &SyncContext, store: &mut dyn MappingStore, mappings: &mut HashMap<MappingKey, CalendarMapping>, stats: &mut SyncStats) -> Result<(), Box<dyn Error>> {
+	info!("Mapping event: {:?} to list {}", event.summary, list_id);
+	debug!("Checking if event is already mapped...");
+
+	let key = (event.uid.clone(), list_id.to_string());
+	let fingerprint = context.fingerprint_for(list_id, event);
+	let Some(mapping) = mappings.get_mut(&key) else {
+		// We don't have a mapping for this event in this list
+		// So create a corresponding task, and store the mapping
+		let task_id = match context.create_task_in(clickup_api, list_id, event) {
+			Ok(task_id) => task_id,
+			Err(err) => {
+				// Don't store a mapping - we'll try again next run
+				error!("Failed to create task for {:?} in list {}: {}", event.summary, list_id, err);
+				stats.failed += 1;
+				return Ok(());
+			}
+		};
+		stats.created += 1;
+
+		let assignees = context.assignees_for(list_id, event).unwrap_or_default();
+		let synced = context.synced_fields_for(event);
+		store.record(AuditEntry::new(&event.uid, list_id, &task_id, AuditOperation::Create { after: TaskSnapshot::synced(synced.clone(), assignees.clone()) }))?;
+		let mut mapping = store.insert(&event.uid, list_id, &task_id, &assignees, Some(&synced), Some(&fingerprint))?;
+		debug!("Added event {:?} under ID {}", event, task_id);
+		apply_templates(event, list_id, clickup_api, context, &task_id);
+		ensure_meeting_notes(event, clickup_api, context, store, &mut mapping)?;
+		mappings.insert(key, mapping);
+		return Ok(());
+	};
+
+	// We already have a mapping for this event
+	// So update the corresponding task in ClickUp with any changes to details in the calendar
+	debug!("Event already mapped, updating task...");
+
+	if mapping.user_deleted {
+		debug!("Task {} was deleted by hand, not syncing event {:?}", mapping.clickup_id, event.summary);
+		stats.skipped += 1;
+		return Ok(());
+	}
+
+	// If the event had disappeared from the calendar and come back, undo whatever we did to the task when it disappeared
+	let restored = if let Some(policy) = mapping.tombstone() {
+		info!("Event came back, restoring task {}", mapping.clickup_id);
+		match clickup_api.restore_task(&mapping.clickup_id, list_id, &policy) {
+			Ok(()) => store.record(AuditEntry::for_mapping(mapping, AuditOperation::Restore { policy }))?,
+			Err(err) if err.is_not_found() => return handle_missing_task(event, clickup_api, context, store, mapping, stats),
+			Err(err) => {
+				error!("Failed to restore task {}: {}", mapping.clickup_id, err);
+				stats.failed += 1;
+				return Ok(());
+			}
+		}
+		mapping.tombstone = None;
+		store.update(mapping)?;
+		true
+	} else {
+		false
+	};
+
+	// Before checking if the event changed, so meeting notes are made for events synced before they were set up
+	ensure_meeting_notes(event, clickup_api, context, store, mapping)?;
+
+	if store.is_up_to_date(mapping, &fingerprint) {
+		debug!("Event unchanged since task {} was last synced, skipping", mapping.clickup_id);
+		if restored {
+			stats.updated += 1;
+		} else {
+			stats.skipped += 1;
+		}
+		return Ok(());
+	}
+
+	match context.update_task(clickup_api, mapping, event) {
+		Ok(synced) => {
+			// Tell people what changed, as otherwise an event moving is easy to miss
+			let before = mapping.last_synced();
+			if let Some(before) = &before {
+				if let Err(err) = context.comment_changes(clickup_api, &mapping.clickup_id, before, &synced) {
+					error!("Failed to comment on task {} for {:?}: {}", mapping.clickup_id, event.summary, err);
+				}
+			}
+			apply_templates(event, list_id, clickup_api, context, &mapping.clickup_id);
+			let assignees = context.assignees_for(list_id, event).unwrap_or_else(|| mapping.synced_assignees());
+			store.record(AuditEntry::for_mapping(mapping, AuditOperation::Update {
+				before: before.map(|before| TaskSnapshot::synced(before, mapping.synced_assignees())),
+				after: TaskSnapshot::synced(synced.clone(), assignees.clone()),
+			}))?;
+			// Remember what we wrote, so next time we can tell if someone has edited the task
+			mapping.last_synced = Some(synced_fields_to_json(&synced));
+			mapping.fingerprint = Some(fingerprint);
+			// Remember who we assigned, so next time we only change our own assignments
+			mapping.synced_assignees = assignees_to_json(&assignees);
+			store.update(mapping)?;
+			stats.updated += 1;
+		}
+		Err(err) if err.is_not_found() => return handle_missing_task(event, clickup_api, context, store, mapping, stats),
+		Err(err) => {
+			error!("Failed to update task for {:?} with ID {}: {}", event.summary, mapping.clickup_id, err);
+			stats.failed += 1;
+		}
+	}
+	Ok(())
+}
+
+/// Add the subtasks & checklist items from the templates matching an event to its task (see templates.rs).
+/// The task itself is synced either way, so failures are only logged (and tried again when the event next changes).
+fn apply_templates(event: &ParsedEvent, list_id: &str, clickup_api: &ClickUpApiInstance, context: &SyncContext, task_id: &str) {
+	if let Err(err) = context.apply_templates(clickup_api, task_id, list_id, event) {
+		error!("Failed to add template items to task {} for {:?}: {}", task_id, event.summary, err);
+	}
+}
+
+/// Make the meeting notes document for an event, if it should have one and doesn't yet (see meeting_notes.rs).
+/// The document is remembered on the mapping, so it's only ever made once; failures are logged and tried again next run.
+/// ### Arguments
+/// * `event` - The event
+/// * `clickup_api` - The instance of the ClickUp API to use
+/// * `context` - How the sync is set up, from the config
+/// * `store` - Where to save the mapping
+/// * `mapping` - The mapping to the event's task
+fn ensure_meeting_notes(event: &ParsedEvent, clickup_api: &ClickUpApiInstance, context: &SyncContext, store: &mut dyn MappingStore, mapping: &mut CalendarMapping) -> Result<(), Box<dyn Error>> {
+	if mapping.meeting_doc.is_some() || !context.wants_meeting_notes(event) {
+		return Ok(());
+	}
+	match context.create_meeting_notes(clickup_api, &mapping.clickup_id, &mapping.list_id, event) {
+		Ok(doc_id) => {
+			mapping.meeting_doc = doc_id;
+			store.update(mapping)?;
+		}
+		Err(err) => error!("Failed to create meeting notes for {:?} on task {}: {}", event.summary, mapping.clickup_id, err),
+	}
+	Ok(())
+}
+
+/// Called when the task for a mapping turns out to have been deleted by hand in ClickUp, so that we don't try
+/// (and fail) to update it on every run. Depending on the missing tasks policy, either stops syncing the event,
+/// or makes a new task for it.
+/// ### Arguments
+/// * `event` - The event whose task is missing
+/// * `clickup_api` - The instance of the ClickUp API to use
+/// * `context` - How the sync is set up, from the config
+/// * `store` - Where to save the mapping
+/// * `mapping` - The mapping to the missing task
+/// * `stats` - What the run has done so far
+fn handle_missing_task(event: &ParsedEvent, clickup_api: &ClickUpApiInstance, context: &SyncContext, store: &mut dyn MappingStore, mapping: &mut CalendarMapping, stats: &mut SyncStats) -> Result<(), Box<dyn Error>> {
+	match context.missing_tasks().policy.unwrap_or_default() {
+		MissingTaskPolicy::Unlink => {
+			warn!("Task {} for {:?} was deleted in ClickUp, no longer syncing the event", mapping.clickup_id, event.summary);
+			mapping.user_deleted = true;
+			mapping.tombstone = None;
+			stats.skipped += 1;
+		}
+		MissingTaskPolicy::Recreate => {
+			warn!("Task {} for {:?} was deleted in ClickUp, recreating it", mapping.clickup_id, event.summary);
+			let task_id = match context.create_task_in(clickup_api, &mapping.list_id, event) {
+				Ok(task_id) => task_id,
+				Err(err) => {
+					error!("Failed to recreate task for {:?}: {}", event.summary, err);
+					stats.failed += 1;
+					return Ok(());
+				}
+			};
+			stats.created += 1;
+			apply_templates(event, &mapping.list_id, clickup_api, context, &task_id);
+			let assignees = context.assignees_for(&mapping.list_id, event).unwrap_or_default();
+			let synced = context.synced_fields_for(event);
+			store.record(AuditEntry::new(&mapping.calendar_id, &mapping.list_id, &task_id, AuditOperation::Create { after: TaskSnapshot::synced(synced.clone(), assignees.clone()) }))?;
+			mapping.clickup_id = task_id;
+			mapping.tombstone = None;
+			mapping.synced_assignees = assignees_to_json(&assignees);
+			mapping.last_synced = Some(synced_fields_to_json(&synced));
+			mapping.fingerprint = Some(context.fingerprint_for(&mapping.list_id, event));
+		}
+	}
+	store.update(mapping)
+}
+
+/// Look for tasks that have been deleted by hand (see `find_missing_tasks`), and unlink or recreate them
+/// ### Arguments
+/// * `clickup_api` - The instance of the ClickUp API to use
+/// * `context` - How the sync is set up, from the config
+/// * `store` - Where to save the mappings
+/// * `mappings` - All the mappings, by (event UID, list ID)
+/// * `events` - The event for each (event UID, list ID) pair in the calendar
+/// * `stats` - What the run has done so far
+fn verify_tasks(clickup_api: &ClickUpApiInstance, context: &SyncContext, store: &mut dyn MappingStore, mappings: &mut HashMap<MappingKey, CalendarMapping>, events: &HashMap<MappingKey, ParsedEvent>, stats: &mut SyncStats) -> Result<(), Box<dyn Error>> {
+	let missing_tasks = match find_missing_tasks(clickup_api, context, mappings) {
+		Ok(missing_tasks) => missing_tasks,
+		Err(err) => {
+			error!("Failed to verify tasks: {}", err);
+			stats.failed += 1;
+			return Ok(());
+		}
+	};
+	// Tasks whose events have gone are left to be retired next run, which will find they're already gone
+	for (key, mapping) in mappings.iter_mut().filter(|(_, mapping)| missing_tasks.contains(&mapping.clickup_id)) {
+		if let Some(event) = events.get(key) {
+			handle_missing_task(event, clickup_api, context, store, mapping, stats)?;
+		}
+	}
+	Ok(())
+}
+
+/// Find the tasks that have been deleted by hand, by listing the tasks in each list we sync to rather than fetching them one by one.
+/// Tasks that aren't in their list are fetched to check whether they've been deleted, or just moved or archived
+/// (which are logged, but otherwise left alone as the task can still be updated).
+/// ### Arguments
+/// * `clickup_api` - The instance of the ClickUp API to use
+/// * `context` - How the sync is set up, from the config
+/// * `mappings` - All the mappings, by (event UID, list ID)
+/// ### Returns
+/// The IDs of the tasks that have been deleted
+fn find_missing_tasks(clickup_api: &ClickUpApiInstance, context: &SyncContext, mappings: &HashMap<MappingKey, CalendarMapping>) -> Result<HashSet<String>, ClickUpError> {
+	info!("Verifying tasks...");
+	let mut missing_tasks = HashSet::new();
+	for list_id in context.list_ids() {
+		let tasks_in_list = clickup_api.get_list_tasks(&list_id, false)?.into_iter()
+			.map(|task| task.id)
+			.collect::<HashSet<_>>();
+
+		// Retired tasks are expected to have gone somewhere, and ones deleted by hand we already know about
+		for mapping in mappings.values()
+			.filter(|mapping| mapping.list_id == list_id && mapping.tombstone.is_none() && !mapping.user_deleted)
+			.filter(|mapping| !tasks_in_list.contains(&mapping.clickup_id))
+		{
+			match clickup_api.get_task(&mapping.clickup_id) {
+				Ok(task) if task.archived => warn!("Task {} for event ID {} has been archived by hand", mapping.clickup_id, mapping.calendar_id),
+				Ok(task) => warn!(
+					"Task {} for event ID {} has been moved to list {} by hand",
+					mapping.clickup_id,
+					mapping.calendar_id,
+					task.list.map(|list| list.id).unwrap_or_default()
+				),
+				Err(err) if err.is_not_found() => {
+					warn!("Task {} for event ID {} has been deleted by hand", mapping.clickup_id, mapping.calendar_id);
+					missing_tasks.insert(mapping.clickup_id.clone());
+				}
+				Err(err) => return Err(err),
+			}
+		}
+	}
+	info!("Verified tasks, {} have been deleted by hand", missing_tasks.len());
+	Ok(missing_tasks)
+}
+
+/// Ensures that the mappings between calendar events and ClickUp tasks are up to date.
+/// Specifically, it checks if any events have been deleted from the calendar (or are no longer routed to a list), and if so,
+/// retires the corresponding task in ClickUp according to the deletion policy.
+/// If the task was deleted the mapping is removed, otherwise it's tombstoned so the event can be re-linked if it comes back.
+///
+/// The idea of this function is to take in all the (event ID, list ID) pairs in the calendar, generated from when we parsed the iCal file,
+/// and then for each mapping, check if its event & list are among them.
+/// If not, then the event has been deleted from the calendar (or from that list), so we retire the corresponding task in ClickUp.
+///
+/// ### Arguments
+/// * `clickup_api` - The instance of the ClickUp API to use
+/// * `context` - How the sync is set up, from the config
+/// * `store` - Where to save the mappings
+/// * `mappings` - All the mappings, by (event UID, list ID)
+/// * `events` - The event for each (event ID, list ID) pair in the calendar
+/// * `stats` - What the run has done so far
+fn ensure_mappings_are_up_to_date(clickup_api: &ClickUpApiInstance, context: &SyncContext, store: &mut dyn MappingStore, mappings: &mut HashMap<MappingKey, CalendarMapping>, events: &HashMap<MappingKey, ParsedEvent>, stats: &mut SyncStats) -> Result<(), Box<dyn Error>> {
+	info!("Ensuring mappings are up to date between calendar and clickup...");
+
+	// Maintain a list of mappings to delete
+	let mut mappings_to_delete = vec![];
+
+	// for each mapping (skipping ones we already retired) whose event isn't in the calendar...
+	let gone = mappings.iter_mut()
+		.filter(|(key, mapping)| mapping.tombstone.is_none() && !events.contains_key(*key))
+		.collect::<Vec<_>>();
+	let total = gone.len();
+	for (done, (key, mapping)) in gone.into_iter().enumerate() {
+		if context.budget_exhausted(clickup_api) {
+			warn!("Request budget used up, leaving {} events gone from the calendar for the next run", total - done);
+			stats.skipped += (total - done) as u32;
+			break;
+		}
+
+		// ... the event has been deleted from the calendar, and we should retire the corresponding task in ClickUp
+		// (unless someone has already deleted it)
+		if mapping.user_deleted {
+			debug!("Event ID {} gone, its task {} was already deleted by hand", mapping.calendar_id, mapping.clickup_id);
+			mappings_to_delete.push((key.clone(), mapping.clone()));
+			continue;
+		}
+		// Never delete a task that isn't marked as ours, in case the mapping is wrong (e.g. after adopting the wrong task)
+		let deleting = *context.deletion_policy() == DeletionPolicy::Delete;
+		let mut before = None;
+		if deleting {
+			// With an audit log, keep what the task was like so it can be made again if the run is undone
+			let marked = if store.audited() {
+				clickup_api.get_task(&mapping.clickup_id).map(|task| {
+					before = Some(TaskSnapshot::of_task(&task));
+					context.is_marked_as_ours(mapping, &task)
+				})
+			} else {
+				context.may_delete(clickup_api, mapping)
+			};
+			match marked {
+				Ok(true) => {}
+				Ok(false) => {
+					warn!("Task {} for event ID {} isn't marked as made by the sync, unlinking rather than deleting it", mapping.clickup_id, mapping.calendar_id);
+					mappings_to_delete.push((key.clone(), mapping.clone()));
+					stats.skipped += 1;
+					continue;
+				}
+				Err(err) if err.is_not_found() => {
+					mappings_to_delete.push((key.clone(), mapping.clone()));
+					stats.deleted += 1;
+					continue;
+				}
+				Err(err) => {
+					error!("Failed to check task with ID {} before deleting it: {}", mapping.clickup_id, err);
+					stats.failed += 1;
+					continue;
+				}
+			}
+		}
+		warn!("Retiring mapping for event ID {} task {} in list {}", mapping.calendar_id, mapping.clickup_id, mapping.list_id);
+
+		// execute API request(s) to retire the task in ClickUp
+		match context.retire_task(clickup_api, &mapping.clickup_id) {
+			// Task is gone, so mark the mapping for deletion
+			Ok(None) => {
+				if deleting {
+					store.record(AuditEntry::for_mapping(mapping, AuditOperation::Delete { before }))?;
+				}
+				mappings_to_delete.push((key.clone(), mapping.clone()));
+				stats.deleted += 1;
+			}
+			// Task was kept, so keep the mapping but remember how we retired it
+			Ok(Some(policy)) => {
+				store.record(AuditEntry::for_mapping(mapping, AuditOperation::Retire { policy: policy.clone() }))?;
+				mapping.tombstone = Some(tombstone_to_json(&policy));
+				store.update(mapping)?;
+				stats.deleted += 1;
+			}
+			// Someone beat us to it
+			Err(err) if err.is_not_found() => {
+				mappings_to_delete.push((key.clone(), mapping.clone()));
+				stats.deleted += 1;
+			}
+			// Leave the mapping alone so we try again next run
+			Err(err) => {
+				error!("Failed to retire task with ID {}: {}", mapping.clickup_id, err);
+				stats.failed += 1;
+			}
+		}
+	}
+
+	// Delete mappings as last act
+	debug!("Deleting mappings: {:?}", mappings_to_delete.iter().map(|(key, _)| key).collect::<Vec<_>>());
+	let (keys, mappings_to_delete): (Vec<_>, Vec<_>) = mappings_to_delete.into_iter().unzip();
+	store.delete(&mappings_to_delete)?;
+	for key in keys {
+		mappings.remove(&key);
+	}
+	Ok(())
+}
+
+impl SyncContext {
+	/// The sync as it is with no config file: every event to the target list, with nothing but the task itself
+	/// ### Arguments
+	/// * `target_list_id` - The ID of the list to sync events to (CLICKUP_TARGET_LIST_ID)
+	pub fn new(target_list_id: &str) -> Self {
+		Self {
+			target_list_id: target_list_id.to_string(),
+			..Default::default()
+		}
+	}
+
+	/// Set up the sync from the config file, looking up the custom fields, statuses, people etc. it mentions in ClickUp
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to look things up with, whose target list is synced to if there are no routes
+	/// * `config` - The config file
+	/// * `calendar_name` - Name of the calendar being synced from, if known (see provenance.rs)
+	/// ### Returns
+	/// The set up sync, or an error if the config doesn't match what's in ClickUp (e.g. a custom field that doesn't exist)
+	pub fn load(clickup_api: &ClickUpApiInstance, config: &SyncConfig, calendar_name: Option<String>) -> Result<Self, Box<dyn Error>> {
+		let mut context = Self::new(&clickup_api.target_list_id);
+		context.load_routes(clickup_api, &config.routes)?;
+		context.load_custom_fields(clickup_api, &config.custom_fields)?;
+		context.load_assignees(clickup_api, &config.assignees)?;
+		context.load_statuses(clickup_api, &config.statuses)?;
+		context.load_deletion_policy(clickup_api, &config.deletion)?;
+		context.set_ownership(&config.ownership);
+		context.set_description(&config.description);
+		context.set_missing_tasks(&config.missing_tasks);
+		context.set_comments(&config.comments);
+		context.set_budget(&config.budget);
+		context.load_templates(&config.templates)?;
+		context.load_task_template(clickup_api, &config.task_template)?;
+		context.set_provenance(&config.provenance, calendar_name);
+		context.load_meeting_notes(clickup_api, &config.meeting_notes)?;
+		context.load_markers(clickup_api, &config.markers)?;
+		Ok(context)
+	}
+
+	/// Set up which lists each event is synced to, checking the lists exist
+	///
+	/// Call this before loading anything else from the config, as the rest is checked against every list we sync to.
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `config` - The routes from the config
+	fn load_routes(&mut self, clickup_api: &ClickUpApiInstance, config: &[RouteConfig]) -> Result<(), Box<dyn Error>> {
+		if config.is_empty() {
+			return Ok(());
+		}
+
+		self.routes = config.iter().map(Route::new).collect::<Result<_, _>>()?;
+		for list_id in self.list_ids() {
+			// Fails if the list doesn't exist
+			clickup_api.get_list_statuses(&list_id)?;
+		}
+		info!("Syncing to lists: {:?}", self.list_ids());
+		Ok(())
+	}
+
+	/// IDs of every list we sync events to
+	pub fn list_ids(&self) -> Vec<String> {
+		if self.routes.is_empty() {
+			return vec![self.target_list_id.clone()];
+		}
+		let mut list_ids = vec![];
+		for route in &self.routes {
+			if !list_ids.contains(&route.list_id) {
+				list_ids.push(route.list_id.clone());
+			}
+		}
+		list_ids
+	}
+
+	/// IDs of the lists an event should have tasks in
+	pub fn lists_for(&self, event: &ParsedEvent) -> Vec<String> {
+		if self.routes.is_empty() {
+			return vec![self.target_list_id.clone()];
+		}
+		lists_for(&self.routes, event)
+	}
+
+	/// The custom fields we fill in on a list
+	fn fields(&self, list_id: &str) -> &ListFields {
+		self.list_fields.get(list_id).unwrap_or(NO_FIELDS)
+	}
+
+	/// Look up the custom fields from the config on every list we sync to, so that they're filled in on every task we create or update
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `config` - The custom fields from the config
+	fn load_custom_fields(&mut self, clickup_api: &ClickUpApiInstance, config: &[CustomFieldConfig]) -> Result<(), Box<dyn Error>> {
+		if config.is_empty() {
+			return Ok(());
+		}
+
+		for list_id in self.list_ids() {
+			let definitions = clickup_api.get_custom_fields(&list_id)?;
+			let custom_fields = resolve_custom_fields(config, &definitions).map_err(|err| format!("{} (list {})", err, list_id))?;
+			info!("Syncing custom fields to list {}: {:?}", list_id, custom_fields.iter().map(|field| &field.definition.name).collect::<Vec<_>>());
+			self.list_fields.entry(list_id).or_default().custom_fields = custom_fields;
+		}
+		Ok(())
+	}
+
+	/// Set up assigning people to tasks from the config, looking up everyone mentioned in it in the workspace
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `config` - The assignees section of the config
+	fn load_assignees(&mut self, clickup_api: &ClickUpApiInstance, config: &AssigneesConfig) -> Result<(), Box<dyn Error>> {
+		if !config.is_enabled() {
+			return Ok(());
+		}
+
+		let members = clickup_api.get_team_members()?;
+		self.assignees = Some(AssigneeResolver::new(config, members)?);
+		Ok(())
+	}
+
+	/// Work out who the sync should assign to the task for an event
+	/// ### Arguments
+	/// * `list_id` - ID of the list the task is in
+	/// * `event` - The event
+	/// ### Returns
+	/// The ClickUp user IDs to assign, or None if assignment isn't configured (in which case we leave assignees alone)
+	pub fn assignees_for(&self, list_id: &str, event: &ParsedEvent) -> Option<BTreeSet<i64>> {
+		self.assignees.as_ref().map(|resolver| resolver.assignees_for(list_id, event))
+	}
+
+	/// Set up setting the status of tasks from the config, checking the statuses exist on every list we sync to
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `config` - The status rules from the config
+	fn load_statuses(&mut self, clickup_api: &ClickUpApiInstance, config: &[StatusRuleConfig]) -> Result<(), Box<dyn Error>> {
+		if config.is_empty() {
+			return Ok(());
+		}
+
+		// Use the spelling of the statuses from the first list (ClickUp doesn't mind the case of statuses)
+		let mut statuses = None;
+		for list_id in self.list_ids() {
+			let list_statuses = clickup_api.get_list_statuses(&list_id)?.into_iter()
+				.map(|status| status.status)
+				.collect::<Vec<_>>();
+			let rules = resolve_status_rules(config, &list_statuses).map_err(|err| format!("{} (list {})", err, list_id))?;
+			statuses.get_or_insert(rules);
+		}
+		self.statuses = statuses.unwrap_or_default();
+		Ok(())
+	}
+
+	/// Work out the status the task for an event should have right now
+	/// ### Returns
+	/// The status, or None to leave the status alone
+	pub fn status_for(&self, event: &ParsedEvent) -> Option<String> {
+		status_for(&self.statuses, event, chrono::Utc::now().naive_utc())
+	}
+
+	/// Set up the templates of subtasks & checklist items to add to the tasks for matching events
+	/// ### Arguments
+	/// * `config` - The templates from the config
+	fn load_templates(&mut self, config: &[TemplateConfig]) -> Result<(), Box<dyn Error>> {
+		self.templates = config.iter().map(Template::new).collect::<Result<_, _>>()?;
+		Ok(())
+	}
+
+	/// Set up creating tasks from ClickUp task templates, checking the templates exist
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `config` - The task template section of the config
+	fn load_task_template(&mut self, clickup_api: &ClickUpApiInstance, config: &TaskTemplateConfig) -> Result<(), Box<dyn Error>> {
+		let wanted = config.id.iter().chain(config.lists.values()).collect::<Vec<_>>();
+		if wanted.is_empty() {
+			return Ok(());
+		}
+
+		let mut templates = vec![];
+		for team in clickup_api.get_teams()? {
+			templates.extend(clickup_api.get_task_templates(&team.id)?);
+		}
+		for id in wanted {
+			if !templates.iter().any(|template| &template.id == id) {
+				let available = templates.iter().map(|template| format!("{} [{}]", template.name, template.id)).collect::<Vec<_>>();
+				return Err(format!("No task template with ID {} (task templates: {})", id, available.join(", ")).into());
+			}
+		}
+		self.task_template = config.clone();
+		Ok(())
+	}
+
+	/// The ClickUp task template to create tasks in a list from, if any
+	fn task_template_for(&self, list_id: &str) -> Option<&str> {
+		self.task_template.lists.get(list_id).or(self.task_template.id.as_ref()).map(String::as_str)
+	}
+
+	/// Set what to do with tasks whose events disappear from the calendar, checking that the status or list it uses exists
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `policy` - The deletion policy from the config
+	fn load_deletion_policy(&mut self, clickup_api: &ClickUpApiInstance, policy: &DeletionPolicy) -> Result<(), Box<dyn Error>> {
+		match policy {
+			DeletionPolicy::Status { status } => {
+				let mut spelling = None;
+				for list_id in self.list_ids() {
+					let list_statuses = clickup_api.get_list_statuses(&list_id)?;
+					let list_status = list_statuses.iter()
+						.find(|list_status| list_status.status.eq_ignore_ascii_case(status))
+						.ok_or_else(|| format!("No status {:?} on list {} for deleted events", status, list_id))?;
+					spelling.get_or_insert(list_status.status.clone());
+				}
+				// Use ClickUp's spelling of the status
+				self.deletion_policy = DeletionPolicy::Status { status: spelling.unwrap_or_else(|| status.clone()) };
+			}
+			DeletionPolicy::Move { list_id } => {
+				// Fails if the list doesn't exist
+				clickup_api.get_list_statuses(list_id)?;
+				self.deletion_policy = policy.clone();
+			}
+			_ => self.deletion_policy = policy.clone(),
+		}
+		info!("Deletion policy: {:?}", self.deletion_policy);
+		Ok(())
+	}
+
+	/// Set which fields of tasks the sync owns, and how to handle people editing them in ClickUp
+	/// ### Arguments
+	/// * `config` - The ownership section of the config
+	fn set_ownership(&mut self, config: &OwnershipConfig) {
+		self.ownership = config.clone();
+	}
+
+	/// Set what to do when a task the sync made has been deleted by hand
+	/// ### Arguments
+	/// * `config` - The missing tasks section of the config
+	fn set_missing_tasks(&mut self, config: &MissingTasksConfig) {
+		self.missing_tasks = config.clone();
+	}
+
+	/// What to do when a task the sync made has been deleted by hand
+	pub fn missing_tasks(&self) -> &MissingTasksConfig {
+		&self.missing_tasks
+	}
+
+	/// Set whether to comment on tasks when they change
+	/// ### Arguments
+	/// * `config` - The comments section of the config
+	fn set_comments(&mut self, config: &CommentsConfig) {
+		self.comments = config.clone();
+	}
+
+	/// Set how many requests this run may make. Requests already made (e.g. whilst loading the config) count towards it.
+	/// ### Arguments
+	/// * `config` - The budget section of the config
+	fn set_budget(&mut self, config: &BudgetConfig) {
+		self.max_requests = config.max_requests;
+	}
+
+	/// Whether this run has made as many requests as it's allowed to, so should leave the rest of the work for the next run
+	pub fn budget_exhausted(&self, clickup_api: &ClickUpApiInstance) -> bool {
+		self.max_requests.is_some_and(|max_requests| clickup_api.requests_sent() >= max_requests)
+	}
+
+	/// Set how the description of tasks is written - either the whole thing, or just a block between markers
+	/// ### Arguments
+	/// * `config` - The description section of the config
+	fn set_description(&mut self, config: &DescriptionConfig) {
+		self.managed_description = ManagedBlock::from_config(config);
+	}
+
+	/// Set up marking tasks as the sync's, with a footer on their descriptions and/or a marker tag
+	/// ### Arguments
+	/// * `config` - The provenance section of the config
+	/// * `calendar_name` - Name of the calendar from the iCal file (see `docsoc_ical::calendar_name`), if it has one
+	fn set_provenance(&mut self, config: &ProvenanceConfig, calendar_name: Option<String>) {
+		self.provenance = Provenance::new(config, calendar_name);
+	}
+
+	/// Whether we may delete the task for a mapping: only if it carries our marker (the marker tag, or the UID of the event
+	/// in the UID custom field), so that we never delete a task someone made by hand. If no marker is configured there's
+	/// nothing to check, so any task may be deleted.
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `mapping` - The mapping of the task
+	pub fn may_delete(&self, clickup_api: &ClickUpApiInstance, mapping: &CalendarMapping) -> Result<bool, ClickUpError> {
+		if self.provenance.marker_tag().is_none() && self.uid_field(&mapping.list_id).is_none() {
+			return Ok(true);
+		}
+		let task = clickup_api.get_task(&mapping.clickup_id)?;
+		Ok(self.is_marked_as_ours(mapping, &task))
+	}
+
+	/// Whether a task we've already fetched carries our marker for the event of a mapping (see `may_delete`),
+	/// which it does if no marker is configured
+	/// ### Arguments
+	/// * `mapping` - The mapping of the task
+	/// * `task` - The task, as it is in ClickUp
+	pub fn is_marked_as_ours(&self, mapping: &CalendarMapping, task: &ClickUpTask) -> bool {
+		let marker_tag = self.provenance.marker_tag();
+		let uid_field = self.uid_field(&mapping.list_id);
+		if marker_tag.is_none() && uid_field.is_none() {
+			return true;
+		}
+		let tagged = marker_tag.is_some_and(|marker_tag| task.tags.iter().any(|tag| tag.name.eq_ignore_ascii_case(marker_tag)));
+		let has_uid = uid_field.is_some_and(|field| task.custom_field_text(field) == Some(mapping.calendar_id.as_str()));
+		tagged || has_uid
+	}
+
+	/// Set up making meeting notes documents for matching events, checking the link field (if any) is a URL field on every list we sync to
+	///
+	/// Call this before `load_markers`, which checks stateless mode can find the documents again.
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `config` - The meeting notes section of the config
+	fn load_meeting_notes(&mut self, clickup_api: &ClickUpApiInstance, config: &MeetingNotesConfig) -> Result<(), Box<dyn Error>> {
+		self.meeting_notes = config.rules.iter().map(MeetingNotesRule::new).collect::<Result<_, _>>()?;
+		let Some(link_field) = &config.link_field else {
+			return Ok(());
+		};
+
+		for list_id in self.list_ids() {
+			let definitions = clickup_api.get_custom_fields(&list_id)?;
+			let mapping = resolve_custom_fields(&[CustomFieldConfig { field: link_field.clone(), value: String::new() }], &definitions)
+				.map_err(|err| format!("{} (list {})", err, list_id))?
+				.remove(0);
+			if mapping.definition.field_type != "url" {
+				return Err(format!("The meeting notes link field {:?} must be a URL field, not {}", mapping.definition.name, mapping.definition.field_type).into());
+			}
+			info!("Linking meeting notes in the custom field {:?} on list {}", mapping.definition.name, list_id);
+			self.list_fields.entry(list_id).or_default().notes_field = Some(mapping.definition.id);
+		}
+		Ok(())
+	}
+
+	/// ID of the custom field we put the link to the meeting notes in on a list, if any
+	pub fn notes_field(&self, list_id: &str) -> Option<&str> {
+		self.fields(list_id).notes_field.as_deref()
+	}
+
+	/// Whether an event should have a meeting notes document
+	pub fn wants_meeting_notes(&self, event: &ParsedEvent) -> bool {
+		rule_for(&self.meeting_notes, event).is_some()
+	}
+
+	/// Make the meeting notes document for an event in ClickUp Docs, and link it from the event's task
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `task_id` - The ClickUp task ID of the event's task
+	/// * `list_id` - The list the task is in (the document is made in the list too)
+	/// * `event` - The event
+	/// ### Returns
+	/// The ID of the new document, or None if no rule says the event should have one
+	pub fn create_meeting_notes(&self, clickup_api: &ClickUpApiInstance, task_id: &str, list_id: &str, event: &ParsedEvent) -> Result<Option<String>, ClickUpError> {
+		let Some(rule) = rule_for(&self.meeting_notes, event) else {
+			return Ok(None);
+		};
+
+		// Docs are only in the v3 API, which needs the workspace ID, which is on the task
+		let workspace_id = clickup_api.get_task(task_id)?.team_id.unwrap_or_default();
+		let title = rule.title_for(event);
+		let doc_id = clickup_api.create_doc(&workspace_id, list_id, &title)?;
+		info!("Created meeting notes {:?} for {:?} with ID {}", title, event.summary, doc_id);
+
+		// The document exists now, so if this fails we still want to remember it (rather than make another next run)
+		let filled_in = clickup_api.create_doc_page(&workspace_id, &doc_id, &title, &rule.content_for(event))
+			.and_then(|()| self.link_meeting_notes(clickup_api, task_id, list_id, &doc_url(&workspace_id, &doc_id)));
+		if let Err(err) = filled_in {
+			warn!("Created meeting notes {} for task {}, but failed to fill them in or link them: {}", doc_id, task_id, err);
+		}
+		Ok(Some(doc_id))
+	}
+
+	/// Link a new meeting notes document from the task, in the link field if there is one or otherwise in a comment
+	fn link_meeting_notes(&self, clickup_api: &ClickUpApiInstance, task_id: &str, list_id: &str, url: &str) -> Result<(), ClickUpError> {
+		match self.notes_field(list_id) {
+			Some(field) => clickup_api.set_custom_field(task_id, field, &serde_json::json!(url)),
+			None => clickup_api.post_comment(task_id, &format!("Meeting notes: {}", url), &[]),
+		}
+	}
+
+	/// Set up writing the iCal UID (and optionally a fingerprint) of each event onto its task,
+	/// so that tasks can be matched to events without the database
+	///
+	/// The UID is written like any other custom field, so call this after `load_custom_fields`.
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `config` - The markers section of the config
+	fn load_markers(&mut self, clickup_api: &ClickUpApiInstance, config: &MarkersConfig) -> Result<(), Box<dyn Error>> {
+		if config.stateless && config.uid_field.is_none() {
+			return Err("Stateless mode needs a uid_field to keep the UID of each event on its task".into());
+		}
+		if config.stateless && self.list_ids().len() > 1 && matches!(self.deletion_policy, DeletionPolicy::Move { .. }) {
+			// We'd have no way to tell which list a task in the graveyard list came from
+			return Err("The move deletion policy can't be used in stateless mode with more than one list".into());
+		}
+		if config.stateless && !self.meeting_notes.is_empty() && self.list_ids().iter().any(|list_id| self.notes_field(list_id).is_none()) {
+			// The link on the task is the only record of the document
+			return Err("Meeting notes in stateless mode need a link_field, to tell which events already have them".into());
+		}
+		if config.stateless {
+			// A deleted task takes its UID with it, so there's nothing left to say the event was unlinked,
+			// and the next run would make a new task for it anyway
+			if self.missing_tasks.policy == Some(MissingTaskPolicy::Unlink) {
+				return Err("The unlink missing tasks policy can't be used in stateless mode, as deleted tasks are always recreated".into());
+			}
+			self.missing_tasks.policy = Some(MissingTaskPolicy::Recreate);
+		}
+		if config.uid_field.is_none() && config.fingerprint_field.is_none() {
+			return Ok(());
+		}
+
+		for list_id in self.list_ids() {
+			let definitions = clickup_api.get_custom_fields(&list_id)?;
+			// Both markers must be text fields, and are filled in from the templates given here
+			let resolve = |field: &str, template: &str| -> Result<CustomFieldMapping, Box<dyn Error>> {
+				let mapping = resolve_custom_fields(&[CustomFieldConfig { field: field.to_string(), value: template.to_string() }], &definitions)
+					.map_err(|err| format!("{} (list {})", err, list_id))?
+					.remove(0);
+				if !["short_text", "text"].contains(&mapping.definition.field_type.as_str()) {
+					return Err(format!("The marker field {:?} must be a text field, not {}", mapping.definition.name, mapping.definition.field_type).into());
+				}
+				Ok(mapping)
+			};
+
+			let mut fields = self.list_fields.remove(&list_id).unwrap_or_default();
+			if let Some(field) = &config.uid_field {
+				let mapping = resolve(field, "{uid}")?;
+				info!("Writing event UIDs into the custom field {:?} on list {}", mapping.definition.name, list_id);
+				fields.uid_field = Some(mapping.definition.id.clone());
+				fields.custom_fields.push(mapping);
+			}
+			if let Some(field) = &config.fingerprint_field {
+				// The fingerprint isn't from a template, it's written by create_task & update_task
+				let mapping = resolve(field, "")?;
+				info!("Writing event fingerprints into the custom field {:?} on list {}", mapping.definition.name, list_id);
+				fields.fingerprint_field = Some(mapping.definition.id);
+			}
+			self.list_fields.insert(list_id, fields);
+		}
+		Ok(())
+	}
+
+	/// ID of the custom field the fingerprint of each event is written into on a list, if configured
+	pub fn fingerprint_field(&self, list_id: &str) -> Option<&str> {
+		self.fields(list_id).fingerprint_field.as_deref()
+	}
+
+	/// What to do with tasks whose events disappear from the calendar
+	pub fn deletion_policy(&self) -> &DeletionPolicy {
+		&self.deletion_policy
+	}
+
+	/// ID of the custom field the iCal UID of each event is written into on a list, if configured
+	pub fn uid_field(&self, list_id: &str) -> Option<&str> {
+		self.fields(list_id).uid_field.as_deref()
+	}
+	/// Private function to take a ParsedEvent (that we created when parsing the ical) and convert it into a CreateTaskPayload
+	fn mk_task_payload(&self, event: &ParsedEvent) -> CreateTaskPayload {
+
+		// NOTE: QUIRK: Google calendar encodes all day events as starting at 00:00:00 and ending at 00:00:00 the next day UTC
+		// Unfortunately, if you're in a non UTC timezone, such as BST, and you set these exact times as the start and end times in ClickUp
+		// ClickUp will show the event in its UI based on your timezone.
+		// For BST specifically, this results in the event showing as 1am to 1am the next day,
+		// which then shows up as a 2 day event in ClickUp (misleading visually!)
+		// 
+		// To fix this, we check if the event is all day and if it is, we tell clickup to ignore the times and roll the end date back by 1 day
+		// as ClickUp defaults to the end of a day if no time is provided
+
+		let mut is_all_day = false;
+		let mut actual_due_date = event.end_time.map(|time| time.and_utc().timestamp_millis());
+		// If end_time & start_time both happen to midnight, set is_all_day to true
+		if let Some(start_time) = event.start_time {
+			if let Some(end_time) = event.end_time {
+				if start_time.time().hour() == 0 && end_time.time().hour() == 0 {
+					is_all_day = true;
+				}
+			}
+		}
+
+		// If it's an all day event, set the due date to 1 day before the end time
+		if is_all_day {
+			const ONE_DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+			actual_due_date = event.end_time.map(|time| time.and_utc().timestamp_millis() - ONE_DAY_MILLIS);
+		}
+
+		// Create the payload
+		CreateTaskPayload {
+			name: event.summary.clone(),
+			description: self.provenance.add_footer(&event.description, event),
+			tags: self.provenance.marker_tag().map(str::to_string).into_iter().collect(),
+			start_date: event.start_time.map(|time| time.and_utc().timestamp_millis()),
+			start_date_time: event.start_time.is_some() && !is_all_day,
+			due_date: actual_due_date,
+			due_date_time: event.end_time.is_some() && !is_all_day,
+			custom_fields: vec![],
+			assignees: None,
+			status: self.status_for(event),
+		}
+	}
+
+
+	/// What we write to the fields of the task for an event, for storing with the mapping
+	pub fn synced_fields_for(&self, event: &ParsedEvent) -> SyncedFields {
+		let payload = self.mk_task_payload(event);
+		SyncedFields {
+			name: payload.name,
+			description: payload.description,
+			start_date: payload.start_date,
+			due_date: payload.due_date,
+			location: event.location.clone(),
+		}
+	}
+
+	/// A fingerprint of everything we write to the task for an event, which changes whenever the event
+	/// (or the parts of the config that affect the task) changes
+	/// ### Arguments
+	/// * `list_id` - The list the task is in
+	/// * `event` - The event
+	/// ### Returns
+	/// A SHA-256 hash of the task payload, in hex
+	pub fn fingerprint_for(&self, list_id: &str, event: &ParsedEvent) -> String {
+		let mut payload = self.mk_task_payload(event);
+		payload.custom_fields = self.custom_field_values(list_id, event);
+		payload.assignees = self.assignees_for(list_id, event).map(AssigneesPayload::Create);
+		let mut body = serde_json::to_vec(&payload).expect("Failed to serialise task payload");
+		// So the items from templates are moved when the event moves, or added when the templates change
+		// (events without any keep the fingerprint they had before templates)
+		let (subtasks, checklist) = items_for(&self.templates, event);
+		for item in subtasks.iter().chain(&checklist) {
+			body.extend(format!("\n{}@{:?}", item.name, item.due_for(event)).as_bytes());
+		}
+		format!("{:x}", Sha256::digest(body))
+	}
+
+	/// The values of the custom fields we fill in on a list for an event (empty fields are left out)
+	fn custom_field_values(&self, list_id: &str, event: &ParsedEvent) -> Vec<CustomFieldValue> {
+		self.fields(list_id).custom_fields.iter()
+			.filter_map(|field| Some(CustomFieldValue { id: field.definition.id.clone(), value: field.value_for(event)? }))
+			.collect()
+	}
+
+	/// Create a clickup task for a given event in the target list
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `event` - The parsed event from the iCal file we are mapping
+	/// ### Returns
+	/// The ClickUp tsk ID of the newly created task to be stored in the database
+	pub fn create_task(&self, clickup_api: &ClickUpApiInstance, event: &ParsedEvent) -> Result<String, ClickUpError> {
+		self.create_task_in(clickup_api, &self.target_list_id, event)
+	}
+
+	/// Create a clickup task for a given event in the given list
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `list_id` - The ClickUp list ID
+	/// * `event` - The parsed event from the iCal file we are mapping
+	/// ### Returns
+	/// The ClickUp tsk ID of the newly created task to be stored in the database
+	pub fn create_task_in(&self, clickup_api: &ClickUpApiInstance, list_id: &str, event: &ParsedEvent) -> Result<String, ClickUpError> {
+		debug!("Creating task in list {} for: {:?}", list_id, event);
+
+		// Custom fields can be set as part of creating the task (unlike when updating)
+		let mut payload = self.mk_task_payload(event);
+		payload.custom_fields = self.custom_field_values(list_id, event);
+		if let Some(field) = self.fingerprint_field(list_id) {
+			payload.custom_fields.push(CustomFieldValue { id: field.to_string(), value: serde_json::json!(self.fingerprint_for(list_id, event)) });
+		}
+		payload.assignees = self.assignees_for(list_id, event).map(AssigneesPayload::Create);
+		if let Some(block) = &self.managed_description {
+			payload.description = block.wrap(&payload.description);
+		}
+
+		// Leave out fields we never write (ClickUp needs a name though)
+		let skip = TaskField::ALL.into_iter()
+			.filter(|field| *field != TaskField::Name && field.ownership(&self.ownership) == FieldOwnership::Never)
+			.collect::<Vec<_>>();
+
+		let task_id = match self.task_template_for(list_id) {
+			Some(template_id) => self.create_task_from_template(clickup_api, list_id, template_id, payload, &skip)?,
+			// Send the POST request to ClickUp to create a task
+			None => clickup_api.post_task(list_id, &payload.without(&skip))?,
+		};
+
+		info!("Created task for {:?} with ID {}", event.summary, task_id);
+
+		Ok(task_id)
+	}
+
+	/// Create a task from a ClickUp task template, then write the fields from the calendar over the top
+	/// (ClickUp only takes a name when creating a task from a template)
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `list_id` - The ClickUp list ID
+	/// * `template_id` - The ID of the task template
+	/// * `payload` - What we'd have created the task with otherwise
+	/// * `skip` - Fields to leave as the template has them
+	/// ### Returns
+	/// The ClickUp task ID of the new task
+	fn create_task_from_template(&self, clickup_api: &ClickUpApiInstance, list_id: &str, template_id: &str, payload: CreateTaskPayload, skip: &[TaskField]) -> Result<String, ClickUpError> {
+		let task_id = clickup_api.post_task_from_template(list_id, template_id, &payload.name)?;
+
+		// The task exists now, so if this fails we still want a mapping for it (rather than another task next run),
+		// and the next update fills in the fields
+		if let Err(err) = self.write_over_template(clickup_api, &task_id, payload, skip) {
+			warn!("Created task {} from task template {}, but failed to fill it in: {}", task_id, template_id, err);
+		}
+		Ok(task_id)
+	}
+
+	/// Write the fields from the calendar over a task just created from a task template
+	fn write_over_template(&self, clickup_api: &ClickUpApiInstance, task_id: &str, mut payload: CreateTaskPayload, skip: &[TaskField]) -> Result<(), ClickUpError> {
+		// With a managed block, the description from the template stays above it for people to fill in
+		if self.managed_description.is_some() {
+			let template_description = clickup_api.get_task(task_id)?.description.unwrap_or_default();
+			if !template_description.trim().is_empty() {
+				payload.description = format!("{}\n\n{}", template_description.trim(), payload.description);
+			}
+		}
+		// Add to the template's assignees rather than replacing them, and leave its tags alone
+		payload.assignees = payload.assignees.map(|assignees| match assignees {
+			AssigneesPayload::Create(add) => AssigneesPayload::Update { add, rem: BTreeSet::new() },
+			update => update,
+		});
+		let mut body = payload.without(skip);
+		if let Some(body) = body.as_object_mut() {
+			body.remove("tags");
+			body.remove("custom_fields");
+		}
+		clickup_api.put_task(task_id, &body)?;
+
+		// Custom fields & tags can't be set when updating a task, so they're set one by one
+		for field in &payload.custom_fields {
+			clickup_api.set_custom_field(task_id, &field.id, &field.value)?;
+		}
+		for tag in &payload.tags {
+			clickup_api.add_tag(task_id, tag)?;
+		}
+		Ok(())
+	}
+
+	/// Make a task again as it was before the sync deleted it, for undoing a run (see audit.rs)
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `list_id` - The ClickUp list ID
+	/// * `event_uid` - The iCal UID of the event the task was for, put in the UID field (if there is one) so the task is ours
+	/// * `snapshot` - What the task was like before it was deleted
+	/// ### Returns
+	/// The ClickUp task ID of the new task
+	pub fn recreate_task(&self, clickup_api: &ClickUpApiInstance, list_id: &str, event_uid: &str, snapshot: &TaskSnapshot) -> Result<String, ClickUpError> {
+		debug!("Making task in list {} again: {:?}", list_id, snapshot);
+
+		let all_day = is_all_day(snapshot.fields.start_date, snapshot.fields.due_date);
+		let payload = CreateTaskPayload {
+			name: snapshot.fields.name.clone(),
+			description: snapshot.fields.description.clone(),
+			tags: snapshot.tags.clone(),
+			start_date: snapshot.fields.start_date,
+			start_date_time: snapshot.fields.start_date.is_some() && !all_day,
+			due_date: snapshot.fields.due_date,
+			due_date_time: snapshot.fields.due_date.is_some() && !all_day,
+			custom_fields: self.uid_field(list_id)
+				.map(|field| CustomFieldValue { id: field.to_string(), value: serde_json::json!(event_uid) })
+				.into_iter()
+				.collect(),
+			assignees: Some(AssigneesPayload::Create(snapshot.assignees.clone())),
+			status: snapshot.status.clone(),
+		};
+		let task_id = clickup_api.post_task(list_id, &payload.without(&[]))?;
+
+		info!("Made task {:?} again with ID {}", snapshot.fields.name, task_id);
+		Ok(task_id)
+	}
+
+	/// Put back the fields of a task that the sync changed when it updated it, for undoing a run (see audit.rs).
+	/// Only the fields that changed are written, and only the people the sync assigned or unassigned are changed.
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `id` - The ClickUp task ID
+	/// * `before` - What the sync had written to the task before the update
+	/// * `after` - What the update wrote
+	pub fn revert_task(&self, clickup_api: &ClickUpApiInstance, id: &str, before: &TaskSnapshot, after: &TaskSnapshot) -> Result<(), ClickUpError> {
+		debug!("Reverting task with ID {} to {:?}", id, before);
+
+		let mut body = serde_json::Map::new();
+		if before.fields.name != after.fields.name {
+			body.insert("name".to_string(), serde_json::json!(before.fields.name));
+		}
+		if before.fields.description != after.fields.description {
+			// Only the managed block of the description is ours, people can have written what they like around it since
+			let description = match &self.managed_description {
+				Some(block) => block.merge(clickup_api.get_task(id)?.description.as_deref().unwrap_or_default(), &before.fields.description),
+				None => before.fields.description.clone(),
+			};
+			body.insert("description".to_string(), serde_json::json!(description));
+		}
+		if (before.fields.start_date, before.fields.due_date) != (after.fields.start_date, after.fields.due_date) {
+			let all_day = is_all_day(before.fields.start_date, before.fields.due_date);
+			body.insert("start_date".to_string(), serde_json::json!(before.fields.start_date));
+			body.insert("start_date_time".to_string(), serde_json::json!(before.fields.start_date.is_some() && !all_day));
+			body.insert("due_date".to_string(), serde_json::json!(before.fields.due_date));
+			body.insert("due_date_time".to_string(), serde_json::json!(before.fields.due_date.is_some() && !all_day));
+		}
+		if before.assignees != after.assignees {
+			let assignees = AssigneesPayload::Update {
+				add: before.assignees.difference(&after.assignees).copied().collect(),
+				rem: after.assignees.difference(&before.assignees).copied().collect(),
+			};
+			body.insert("assignees".to_string(), serde_json::to_value(assignees).expect("Failed to serialise assignees"));
+		}
+		if body.is_empty() {
+			return Ok(());
+		}
+
+		clickup_api.put_task(id, &serde_json::Value::Object(body))?;
+		info!("Reverted task with ID {}", id);
+		Ok(())
+	}
+
+	/// Update a task in ClickUp with the details of a given event
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `mapping` - The mapping between the event and the ClickUp task, taken from teh DB
+	/// * `event` - The parsed event from the iCal file we are mapping
+	/// ### Returns
+	/// What the sync has now written to the task, to store with the mapping
+	/// (for fields we didn't write this time, this is whatever we last wrote)
+	pub fn update_task(&self, clickup_api: &ClickUpApiInstance, mapping: &CalendarMapping, event: &ParsedEvent) -> Result<SyncedFields, ClickUpError> {
+		debug!("Updating task for: {:?}", event);
+
+		// Only write the fields we always own...
+		let mut skip = TaskField::ALL.into_iter()
+			.filter(|field| field.ownership(&self.ownership) != FieldOwnership::Always)
+			.collect::<Vec<_>>();
+
+		// We need the task as it is now to check for drift, and to keep the description outside the managed block
+		let last_synced = mapping.last_synced();
+		let check_drift = self.ownership.detect_drift && last_synced.is_some();
+		let merge_description = self.managed_description.is_some() && !skip.contains(&TaskField::Description);
+		let current = if check_drift || merge_description {
+			Some(clickup_api.get_task(&mapping.clickup_id)?)
+		} else {
+			None
+		};
+
+		// ...and if asked, check whether anyone has changed them since we last wrote them
+		if self.ownership.detect_drift {
+			if let (Some(last_synced), Some(current)) = (&last_synced, &current) {
+				// Only the managed block of the description is ours, people can write what they like around it
+				let mut current = current.clone();
+				if let Some(block) = &self.managed_description {
+					current.description = current.description.map(|description| block.extract(&description).map(str::to_string).unwrap_or(description));
+				}
+				let owned = TaskField::ALL.into_iter().filter(|field| !skip.contains(field)).collect::<Vec<_>>();
+				for field in owned {
+					if last_synced.matches(field, &current) {
+						continue;
+					}
+					match self.ownership.on_conflict {
+						ConflictPolicy::Keep => {
+							warn!("Conflict: the {} of task {} ({:?}) was changed in ClickUp, leaving it alone", field, mapping.clickup_id, event.summary);
+							skip.push(field);
+						}
+						ConflictPolicy::Overwrite => {
+							warn!("Conflict: the {} of task {} ({:?}) was changed in ClickUp, overwriting it with the calendar", field, mapping.clickup_id, event.summary);
+						}
+					}
+				}
+			}
+		}
+
+		// Only add/remove the people we assigned, so that people assigned by hand stay assigned
+		let mut payload = self.mk_task_payload(event);
+		payload.assignees = self.assignees_for(&mapping.list_id, event).map(|assignees| {
+			let previous = mapping.synced_assignees();
+			AssigneesPayload::Update {
+				add: assignees.difference(&previous).copied().collect(),
+				rem: previous.difference(&assignees).copied().collect(),
+			}
+		});
+		if let Some(block) = &self.managed_description {
+			let existing = current.as_ref().and_then(|task| task.description.as_deref()).unwrap_or_default();
+			payload.description = block.merge(existing, &payload.description);
+		}
+
+		// Send the PUT request to ClickUp to update a task
+		clickup_api.put_task(&mapping.clickup_id, &payload.without(&skip))?;
+
+		// Custom fields have to be set one by one
+		for field in &self.fields(&mapping.list_id).custom_fields {
+			match field.value_for(event) {
+				Some(value) => clickup_api.set_custom_field(&mapping.clickup_id, &field.definition.id, &value)?,
+				None => clickup_api.remove_custom_field(&mapping.clickup_id, &field.definition.id)?,
+			}
+		}
+		if let Some(field) = self.fingerprint_field(&mapping.list_id) {
+			clickup_api.set_custom_field(&mapping.clickup_id, field, &serde_json::json!(self.fingerprint_for(&mapping.list_id, event)))?;
+		}
+		// Tasks made before the marker tag was set up don't have it yet (and ClickUp ignores tags when updating)
+		if let Some(marker_tag) = self.provenance.marker_tag() {
+			let tagged = current.as_ref().is_some_and(|task| task.tags.iter().any(|tag| tag.name.eq_ignore_ascii_case(marker_tag)));
+			if !tagged {
+				clickup_api.add_tag(&mapping.clickup_id, marker_tag)?;
+			}
+		}
+
+		info!("Updated task for {:?} with ID {}", event.summary, mapping.clickup_id);
+
+		// Record what we wrote, keeping what we wrote before for fields we skipped
+		let mut synced = self.synced_fields_for(event);
+		if let Some(last_synced) = &last_synced {
+			for field in &skip {
+				synced.copy_field(*field, last_synced);
+			}
+		}
+		Ok(synced)
+	}
+
+	/// If commenting on changes is turned on, post a comment on a task saying what we just changed about it
+	/// (e.g. "Start: Tue 1 Oct 18:00 → Wed 2 Oct 18:00"), so people notice when an event moves
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `task_id` - The ClickUp task ID
+	/// * `before` - What we wrote to the task last time
+	/// * `after` - What we've just written to the task
+	pub fn comment_changes(&self, clickup_api: &ClickUpApiInstance, task_id: &str, before: &SyncedFields, after: &SyncedFields) -> Result<(), ClickUpError> {
+		if !self.comments.on_update {
+			return Ok(());
+		}
+		let changes = describe_changes(before, after);
+		if changes.is_empty() {
+			return Ok(());
+		}
+
+		let mentions = if self.comments.mention_assignees {
+			clickup_api.get_task(task_id)?.assignees.into_iter().map(|assignee| assignee.id).collect()
+		} else {
+			vec![]
+		};
+		clickup_api.post_comment(task_id, &change_comment(&changes), &mentions)
+	}
+
+	/// Add the subtasks & checklist items from the templates matching an event to its task (see templates.rs).
+	/// Items already on the task are matched up by name: missing ones are added, and ones that aren't done yet
+	/// have their due dates moved if the event has moved.
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `task_id` - The ClickUp task ID
+	/// * `list_id` - The list the task is in (which its subtasks go in too)
+	/// * `event` - The event the task is for
+	pub fn apply_templates(&self, clickup_api: &ClickUpApiInstance, task_id: &str, list_id: &str, event: &ParsedEvent) -> Result<(), ClickUpError> {
+		let (subtasks, checklist) = items_for(&self.templates, event);
+		if subtasks.is_empty() && checklist.is_empty() {
+			return Ok(());
+		}
+		let task = clickup_api.get_task_with_subtasks(task_id)?;
+
+		for item in &subtasks {
+			let due_date = item.due_for(event).map(|due| due.and_utc().timestamp_millis());
+			match task.subtasks.iter().find(|subtask| item.is_named(&subtask.name)) {
+				None => clickup_api.create_subtask(list_id, task_id, &item.name, due_date)?,
+				// Done is done, even if the event moves
+				Some(subtask) if subtask.is_done() => {}
+				Some(subtask) if due_date.is_some() && subtask.due_date.as_deref().and_then(|date| date.parse::<i64>().ok()) != due_date => {
+					info!("Moving the due date of subtask {:?} of task {}", item.name, task_id);
+					clickup_api.put_task(&subtask.id, &serde_json::json!({ "due_date": due_date, "due_date_time": true }))?;
+				}
+				Some(_) => {}
+			}
+		}
+
+		if !checklist.is_empty() {
+			let (checklist_id, items) = match task.checklists.iter().find(|existing| existing.name == CHECKLIST_NAME) {
+				Some(existing) => (existing.id.clone(), existing.items.clone()),
+				None => (clickup_api.create_checklist(task_id, CHECKLIST_NAME)?, vec![]),
+			};
+			for item in &checklist {
+				let name = item.checklist_name_for(event);
+				match items.iter().find(|existing| item.is_named(&existing.name)) {
+					None => clickup_api.create_checklist_item(&checklist_id, &name)?,
+					Some(existing) if !existing.resolved && existing.name != name => {
+						info!("Renaming checklist item {:?} of task {} to {:?}", existing.name, task_id, name);
+						clickup_api.rename_checklist_item(&checklist_id, &existing.id, &name)?;
+					}
+					Some(_) => {}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Deal with the task of an event that has disappeared from the calendar, according to the deletion policy
+	/// ### Arguments
+	/// * `clickup_api` - The instance of the ClickUp API to use
+	/// * `id` - The ClickUp task ID
+	/// ### Returns
+	/// The policy that was applied if the task was kept (so the mapping should be tombstoned),
+	/// or None if the task is gone (deleted by us, or already deleted by someone else) so the mapping should be removed
+	pub fn retire_task(&self, clickup_api: &ClickUpApiInstance, id: &str) -> Result<Option<DeletionPolicy>, ClickUpError> {
+		match clickup_api.retire_task_with(id, &self.deletion_policy) {
+			Ok(()) if self.deletion_policy == DeletionPolicy::Delete => Ok(None),
+			Ok(()) => {
+				warn!("Retired task with ID {} ({:?})", id, self.deletion_policy);
+				Ok(Some(self.deletion_policy.clone()))
+			}
+			Err(err) if err.is_not_found() => {
+				warn!("Task with ID {} was already deleted from ClickUp", id);
+				Ok(None)
+			}
+			Err(err) => Err(err),
+		}
+	}
+}
+
+/// Whether the dates of a task are for an all day event, which are sent to ClickUp without times (see `mk_task_payload`)
+/// ### Arguments
+/// * `start_date` / `due_date` - The dates of the task, in milliseconds since the epoch
+fn is_all_day(start_date: Option<i64>, due_date: Option<i64>) -> bool {
+	let midnight = |date: Option<i64>| date.and_then(chrono::DateTime::from_timestamp_millis).is_some_and(|time| time.hour() == 0);
+	midnight(start_date) && midnight(due_date)
+}
@@ -0,0 +1,132 @@
+/// Templates of subtasks & checklist items to add to the tasks for matching events
+///
+/// E.g. every social needs "book room" two weeks before and "order food" a week before, and every talk needs
+/// "write event report" three days after. Each template matches events on their category or summary (like routes),
+/// and each of its items can be due relative to the start or end of the event.
+///
+/// Items are matched up to what's already on the task by name, so nothing extra is stored: missing items are added,
+/// and when the event moves the due dates of items that aren't done yet are moved with it. Items people have completed
+/// are left alone. ClickUp checklist items don't have due dates, so for those the due date goes in the name instead,
+/// e.g. "Book room (by Tue 17 Sep)".
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{
+	config::{DueAnchor, TemplateConfig, TemplateItemConfig},
+	docsoc_ical::ParsedEvent,
+	matcher::EventMatcher,
+};
+
+/// Name of the checklist we add checklist items to
+pub const CHECKLIST_NAME: &str = "Event checklist";
+
+/// A template from the config, with its regex compiled & due dates parsed
+#[derive(Debug)]
+pub struct Template {
+	/// Which events the template applies to
+	matcher: EventMatcher,
+	/// Subtasks to make under the task
+	subtasks: Vec<TemplateItem>,
+	/// Items to add to the checklist on the task
+	checklist: Vec<TemplateItem>,
+}
+
+/// A subtask or checklist item in a template
+#[derive(Debug, Clone)]
+pub struct TemplateItem {
+	/// Name of the subtask or checklist item
+	pub name: String,
+	/// When it's due: what it's relative to, and by how much
+	due: Option<(DueAnchor, Duration)>,
+}
+
+impl Template {
+	/// Build a template from the config
+	/// ### Returns
+	/// The template, or an error if the summary regex or a due date is invalid
+	pub fn new(config: &TemplateConfig) -> Result<Self, String> {
+		let items = |items: &[TemplateItemConfig]| items.iter().map(TemplateItem::new).collect::<Result<Vec<_>, _>>();
+		Ok(Self {
+			matcher: EventMatcher::new(config.category.as_deref(), config.summary.as_deref()).map_err(|err| err.to_string())?,
+			subtasks: items(&config.subtasks)?,
+			checklist: items(&config.checklist)?,
+		})
+	}
+
+	/// Whether the template applies to events like this one
+	pub fn matches(&self, event: &ParsedEvent) -> bool {
+		self.matcher.matches(event)
+	}
+}
+
+impl TemplateItem {
+	fn new(config: &TemplateItemConfig) -> Result<Self, String> {
+		let due = config.due.as_deref()
+			.map(|due| parse_offset(due).ok_or_else(|| format!("Invalid due date {:?} for {:?}, expected e.g. -14d, +3d or -2h", due, config.name)))
+			.transpose()?;
+		Ok(Self { name: config.name.clone(), due: due.map(|offset| (config.from, offset)) })
+	}
+
+	/// When the item is due for an event, or None if it has no due date (or the event has no time to be relative to)
+	pub fn due_for(&self, event: &ParsedEvent) -> Option<NaiveDateTime> {
+		let (anchor, offset) = self.due?;
+		let time = match anchor {
+			DueAnchor::Start => event.start_time,
+			DueAnchor::End => event.end_time.or(event.start_time),
+		}?;
+		Some(time + offset)
+	}
+
+	/// Name of the item as a checklist item for an event, with its due date in it if it has one
+	pub fn checklist_name_for(&self, event: &ParsedEvent) -> String {
+		match self.due_for(event) {
+			Some(due) => format!("{} (by {})", self.name, due.format("%a %-d %b")),
+			None => self.name.clone(),
+		}
+	}
+
+	/// Whether the name of an existing subtask or checklist item is this item's
+	/// (checklist items have their due date on the end of the name, which changes when the event moves)
+	pub fn is_named(&self, name: &str) -> bool {
+		let name = name.trim();
+		name.eq_ignore_ascii_case(&self.name)
+			|| name.get(..self.name.len()).is_some_and(|start| start.eq_ignore_ascii_case(&self.name) && name[self.name.len()..].starts_with(" (by "))
+	}
+}
+
+/// Parse an offset like "-14d", "+3d", "2h" or "-30m"
+fn parse_offset(text: &str) -> Option<Duration> {
+	let text = text.trim();
+	let (sign, text) = match text.strip_prefix('-') {
+		Some(rest) => (-1, rest),
+		None => (1, text.strip_prefix('+').unwrap_or(text)),
+	};
+	let unit = text.chars().last()?;
+	let amount = text[..text.len() - unit.len_utf8()].parse::<i64>().ok()? * sign;
+	match unit {
+		'd' => Some(Duration::days(amount)),
+		'h' => Some(Duration::hours(amount)),
+		'm' => Some(Duration::minutes(amount)),
+		_ => None,
+	}
+}
+
+/// The subtasks & checklist items for an event, from every matching template
+/// ### Returns
+/// (subtasks, checklist items), without duplicate names
+pub fn items_for(templates: &[Template], event: &ParsedEvent) -> (Vec<TemplateItem>, Vec<TemplateItem>) {
+	let mut subtasks: Vec<TemplateItem> = vec![];
+	let mut checklist: Vec<TemplateItem> = vec![];
+	for template in templates.iter().filter(|template| template.matches(event)) {
+		for item in &template.subtasks {
+			if !subtasks.iter().any(|existing| existing.name.eq_ignore_ascii_case(&item.name)) {
+				subtasks.push(item.clone());
+			}
+		}
+		for item in &template.checklist {
+			if !checklist.iter().any(|existing| existing.name.eq_ignore_ascii_case(&item.name)) {
+				checklist.push(item.clone());
+			}
+		}
+	}
+	(subtasks, checklist)
+}
@@ -0,0 +1,149 @@
+//! Tests of adopting existing tasks instead of creating duplicates
+mod common;
+
+use std::collections::HashSet;
+
+use clickup_ical_sync::adoption::plan_adoption;
+use common::{event, setup_with, FakeClickUp, FAKE_LIST_ID};
+use serde_json::json;
+
+/// The sync set up to write UIDs into a marker field
+const CONFIG: &str = "[markers]\nuid_field = \"iCal UID\"";
+
+/// Add the UID custom field to the fake list
+fn add_uid_field(fake: &FakeClickUp) {
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-uid", "name": "iCal UID", "type": "short_text"}),
+    );
+}
+
+#[test]
+fn uid_is_written_to_marker_field() {
+    let (fake, api, context) = setup_with(CONFIG, add_uid_field);
+    let id = context
+        .create_task(
+            &api,
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .unwrap();
+
+    assert_eq!(fake.custom_field_value(&id, "f-uid"), Some(json!("uid-1")));
+}
+
+#[test]
+fn tasks_are_adopted_by_uid_then_name_and_date() {
+    let (fake, api, context) = setup_with(CONFIG, add_uid_field);
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    let social = event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00");
+    let fair = event(
+        "uid-3",
+        "Careers Fair",
+        "2024-10-03 10:00",
+        "2024-10-03 16:00",
+    );
+    let agm = event("uid-4", "AGM", "2024-10-04 18:00", "2024-10-04 19:00");
+
+    // Made by the sync before the database was lost, and renamed since
+    let talk_id = context.create_task(&api, &talk).unwrap();
+    fake.edit_task(&talk_id, json!({"name": "Talk (renamed)"}));
+    // Made by hand, with only a due date
+    let social_id = fake.insert_task(
+        FAKE_LIST_ID,
+        json!({"name": "social ", "due_date": 1727895600000i64}),
+    );
+    // Same name, wrong day
+    fake.insert_task(
+        FAKE_LIST_ID,
+        json!({"name": "Careers Fair", "start_date": 1728036000000i64}),
+    );
+    // Already mapped
+    let agm_id = context.create_task(&api, &agm).unwrap();
+
+    let tasks = api.get_list_tasks(FAKE_LIST_ID, false).unwrap();
+    assert_eq!(tasks.len(), 4);
+    let plan = plan_adoption(
+        &[talk, social, fair, agm],
+        &tasks,
+        context.uid_field(FAKE_LIST_ID),
+        &HashSet::from(["uid-4".to_string()]),
+        &HashSet::from([agm_id]),
+    );
+
+    assert_eq!(
+        plan.adopted,
+        vec![
+            ("uid-1".to_string(), talk_id),
+            ("uid-2".to_string(), social_id)
+        ]
+    );
+    assert_eq!(plan.ambiguous, vec![]);
+    assert_eq!(plan.unmatched, vec!["uid-3".to_string()]);
+}
+
+#[test]
+fn ambiguous_matches_are_reported_not_adopted() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let first = fake.insert_task(
+        FAKE_LIST_ID,
+        json!({"name": "Talk", "start_date": 1727805600000i64}),
+    );
+    let second = fake.insert_task(
+        FAKE_LIST_ID,
+        json!({"name": "Talk", "due_date": 1727809200000i64}),
+    );
+
+    let tasks = api.get_list_tasks(FAKE_LIST_ID, false).unwrap();
+    let plan = plan_adoption(
+        &[event(
+            "uid-1",
+            "Talk",
+            "2024-10-01 18:00",
+            "2024-10-01 19:00",
+        )],
+        &tasks,
+        None,
+        &HashSet::new(),
+        &HashSet::new(),
+    );
+
+    assert_eq!(plan.adopted, vec![]);
+    assert_eq!(
+        plan.ambiguous,
+        vec![("uid-1".to_string(), vec![first, second])]
+    );
+}
+
+#[test]
+fn subtasks_are_not_matched() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let task = fake.insert_task(
+        FAKE_LIST_ID,
+        json!({"name": "Talk", "start_date": 1727805600000i64}),
+    );
+    // e.g. added from a template, with the same name & date as its parent
+    fake.insert_task(
+        FAKE_LIST_ID,
+        json!({"name": "Talk", "start_date": 1727805600000i64, "parent": task}),
+    );
+
+    let tasks = api.get_list_tasks(FAKE_LIST_ID, false).unwrap();
+    assert_eq!(tasks.len(), 2);
+    let plan = plan_adoption(
+        &[event(
+            "uid-1",
+            "Talk",
+            "2024-10-01 18:00",
+            "2024-10-01 19:00",
+        )],
+        &tasks,
+        None,
+        &HashSet::new(),
+        &HashSet::new(),
+    );
+
+    assert_eq!(plan.adopted, vec![("uid-1".to_string(), task)]);
+    assert_eq!(plan.ambiguous, vec![]);
+}
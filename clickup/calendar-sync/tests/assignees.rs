@@ -0,0 +1,119 @@
+//! Tests of assigning committee members to tasks
+mod common;
+
+use clickup_ical_sync::{
+    models::{assignees_to_json, CalendarMapping},
+    sync::SyncContext,
+};
+use common::{event, mapping, setup_with, FakeClickUp, FAKE_LIST_ID};
+use serde_json::json;
+
+const CONFIG: &str = r#"
+[assignees]
+default = ["chair@docsoc.co.uk"]
+match_attendees = true
+
+[[assignees.rules]]
+category = "Careers"
+assignees = ["sponsorship"]
+
+[[assignees.rules]]
+summary = "(?i)social"
+assignees = ["3"]
+"#;
+
+/// Put the committee in the fake workspace
+fn add_committee(fake: &FakeClickUp) {
+    fake.add_member(1, "chair", "chair@docsoc.co.uk");
+    fake.add_member(2, "sponsorship", "sponsorship@docsoc.co.uk");
+    fake.add_member(3, "events", "events@docsoc.co.uk");
+    fake.add_member(4, "secretary", "secretary@docsoc.co.uk");
+}
+
+#[test]
+fn assignees_come_from_rules_defaults_and_attendees() {
+    let (fake, api, context) = setup_with(CONFIG, add_committee);
+
+    let mut careers = event(
+        "uid-1",
+        "Careers Fair",
+        "2024-10-01 10:00",
+        "2024-10-01 16:00",
+    );
+    careers.categories = vec!["careers".to_string()];
+    let careers_id = context.create_task(&api, &careers).unwrap();
+    assert_eq!(fake.assignees(&careers_id), vec![2]);
+
+    let talk = event("uid-2", "Talk", "2024-10-02 18:00", "2024-10-02 19:00");
+    let talk_id = context.create_task(&api, &talk).unwrap();
+    assert_eq!(fake.assignees(&talk_id), vec![1]);
+
+    let mut meeting = event(
+        "uid-3",
+        "Committee Social",
+        "2024-10-03 18:00",
+        "2024-10-03 19:00",
+    );
+    meeting.attendees = vec![
+        "Secretary@DoCSoc.co.uk".to_string(),
+        "someone@example.com".to_string(),
+    ];
+    let meeting_id = context.create_task(&api, &meeting).unwrap();
+    assert_eq!(fake.assignees(&meeting_id), vec![3, 4]);
+}
+
+#[test]
+fn lists_can_have_their_own_default_assignees() {
+    let (fake, api, context) = setup_with(
+        "[assignees]\ndefault = [\"chair\"]\nlists = { \"902\" = [\"sponsorship\", \"events\"] }",
+        add_committee,
+    );
+    let talk = event("uid-1", "Talk", "2024-10-02 18:00", "2024-10-02 19:00");
+
+    let main_id = context.create_task(&api, &talk).unwrap();
+    assert_eq!(fake.assignees(&main_id), vec![1]);
+    let sponsorship_id = context.create_task_in(&api, "902", &talk).unwrap();
+    assert_eq!(fake.assignees(&sponsorship_id), vec![2, 3]);
+}
+
+#[test]
+fn update_only_changes_assignees_the_sync_made() {
+    let (fake, api, context) = setup_with(CONFIG, add_committee);
+    let mut careers = event(
+        "uid-1",
+        "Careers Fair",
+        "2024-10-01 10:00",
+        "2024-10-01 16:00",
+    );
+    careers.categories = vec!["careers".to_string()];
+    let id = context.create_task(&api, &careers).unwrap();
+
+    // Someone assigns themselves by hand
+    fake.edit_task(&id, json!({"assignees": {"add": [4], "rem": []}}));
+
+    // Event is no longer a careers event: the sync's own assignment of 2 is replaced by the default, but 4 stays
+    careers.categories = vec![];
+    let mapping = CalendarMapping {
+        // what the sync assigned when it created the task
+        synced_assignees: assignees_to_json(&[2].into()),
+        ..mapping(&id, "uid-1")
+    };
+    context.update_task(&api, &mapping, &careers).unwrap();
+
+    assert_eq!(fake.assignees(&id), vec![1, 4]);
+}
+
+#[test]
+fn assignees_are_left_alone_when_not_configured() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let id = SyncContext::new(FAKE_LIST_ID)
+        .create_task(
+            &api,
+            &event("uid-1", "Talk", "2024-10-02 18:00", "2024-10-02 19:00"),
+        )
+        .unwrap();
+
+    assert_eq!(fake.task(&id).unwrap()["assignees"], json!([]));
+    assert!(!fake.requests().iter().any(|request| request == "GET /team"));
+}
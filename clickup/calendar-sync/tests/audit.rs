@@ -0,0 +1,366 @@
+//! Tests of the audit log of changes the sync makes to tasks, and undoing a run with it
+mod common;
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    error::Error,
+};
+
+use chrono::NaiveDateTime;
+use clickup_ical_sync::{
+    audit::{undo_run, AuditEntry, AuditOperation},
+    docsoc_ical::ParsedEvent,
+    models::{AuditRecord, CalendarMapping},
+    ownership::SyncedFields,
+    store::MappingStore,
+    sync::{sync_events, SyncContext},
+};
+use common::{event, setup, FakeClickUp, MemoryStore, FAKE_LIST_ID};
+
+/// A store that keeps an audit log in memory, recording changes against whichever run it's set to
+#[derive(Default)]
+struct AuditedStore {
+    inner: MemoryStore,
+    run: i32,
+    records: Vec<AuditRecord>,
+    /// Changes undone since changes were last saved
+    undone: Vec<i32>,
+    /// How many more times changes can be saved before saving fails, if it's going to
+    flushes_left: Option<usize>,
+}
+
+impl AuditedStore {
+    /// The changes recorded against a run that haven't been undone, oldest first
+    fn records_for(&self, run: i32) -> Vec<AuditRecord> {
+        self.records
+            .iter()
+            .filter(|record| record.run_id == run && !record.undone)
+            .cloned()
+            .collect()
+    }
+
+    /// The changes recorded against a run
+    fn entries_for(&self, run: i32) -> Vec<AuditEntry> {
+        self.records_for(run)
+            .iter()
+            .map(|record| AuditEntry::from_record(record).unwrap())
+            .collect()
+    }
+}
+
+impl MappingStore for AuditedStore {
+    fn load(&mut self) -> Result<Vec<CalendarMapping>, Box<dyn Error>> {
+        self.inner.load()
+    }
+
+    fn insert(
+        &mut self,
+        calendar_id: &str,
+        list_id: &str,
+        clickup_id: &str,
+        synced_assignees: &BTreeSet<i64>,
+        last_synced: Option<&SyncedFields>,
+        fingerprint: Option<&str>,
+    ) -> Result<CalendarMapping, Box<dyn Error>> {
+        self.inner.insert(
+            calendar_id,
+            list_id,
+            clickup_id,
+            synced_assignees,
+            last_synced,
+            fingerprint,
+        )
+    }
+
+    fn update(&mut self, mapping: &CalendarMapping) -> Result<(), Box<dyn Error>> {
+        self.inner.update(mapping)
+    }
+
+    fn delete(&mut self, mappings: &[CalendarMapping]) -> Result<(), Box<dyn Error>> {
+        self.inner.delete(mappings)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        let undone = std::mem::take(&mut self.undone);
+        match &mut self.flushes_left {
+            Some(0) => return Err("Database is locked".into()),
+            Some(left) => *left -= 1,
+            None => {}
+        }
+        for record in &mut self.records {
+            record.undone |= undone.contains(&record.audit_id);
+        }
+        Ok(())
+    }
+
+    fn audited(&self) -> bool {
+        true
+    }
+
+    fn record(&mut self, entry: AuditEntry) -> Result<(), Box<dyn Error>> {
+        let (operation, payload_before, payload_after) = entry.columns();
+        self.records.push(AuditRecord {
+            audit_id: self.records.len() as i32 + 1,
+            run_id: self.run,
+            recorded_at: NaiveDateTime::default(),
+            operation: operation.to_string(),
+            list_id: entry.list_id,
+            task_id: entry.task_id,
+            event_uid: entry.event_uid,
+            payload_before,
+            payload_after,
+            undone: false,
+        });
+        Ok(())
+    }
+
+    fn mark_undone(&mut self, audit_id: i32) -> Result<(), Box<dyn Error>> {
+        self.undone.push(audit_id);
+        Ok(())
+    }
+}
+
+/// Sync a talk & a social in run 1, then in run 2 move the talk an hour later and cancel the social
+fn two_runs(fake: &FakeClickUp, store: &mut AuditedStore) {
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    let social = event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00");
+
+    store.run = 1;
+    sync_events(&api, &context, store, vec![talk.clone(), social]).unwrap();
+    let mut moved_talk = talk;
+    moved_talk.start_time = moved_talk
+        .start_time
+        .map(|start| start + chrono::Duration::hours(1));
+    moved_talk.end_time = moved_talk
+        .end_time
+        .map(|end| end + chrono::Duration::hours(1));
+    store.run = 2;
+    sync_events(&api, &context, store, vec![moved_talk]).unwrap();
+}
+
+/// The task with the given name in the fake server
+fn task_named(fake: &FakeClickUp, name: &str) -> serde_json::Value {
+    fake.tasks()
+        .into_iter()
+        .find(|task| task["name"] == name)
+        .unwrap_or_else(|| panic!("No task named {}", name))
+}
+
+#[test]
+fn changes_are_recorded_with_the_task_before_and_after() {
+    let fake = FakeClickUp::start();
+    let mut store = AuditedStore::default();
+    two_runs(&fake, &mut store);
+
+    let created = store.entries_for(1);
+    assert_eq!(created.len(), 2);
+    assert!(matches!(
+        &created[0].operation,
+        AuditOperation::Create { after } if after.fields.name == "Talk"
+    ));
+
+    // The social is deleted before the talk is updated
+    let changed = store.entries_for(2);
+    assert_eq!(changed.len(), 2);
+    let AuditOperation::Delete {
+        before: Some(social),
+    } = &changed[0].operation
+    else {
+        panic!("Expected the social to be deleted, got {:?}", changed[0]);
+    };
+    assert_eq!(social.fields.name, "Social");
+    assert_eq!(social.fields.description, "Description of Social");
+    assert_eq!(changed[0].event_uid, "uid-2");
+    let AuditOperation::Update {
+        before: Some(before),
+        after,
+    } = &changed[1].operation
+    else {
+        panic!("Expected the talk to be updated, got {:?}", changed[1]);
+    };
+    assert_eq!(
+        after.fields.start_date.unwrap() - before.fields.start_date.unwrap(),
+        60 * 60 * 1000
+    );
+}
+
+#[test]
+fn undoing_a_run_puts_tasks_and_mappings_back() {
+    let fake = FakeClickUp::start();
+    let mut store = AuditedStore::default();
+    two_runs(&fake, &mut store);
+    let talk_before = task_named(&fake, "Talk");
+    let social_id = store.entries_for(2)[0].task_id.clone();
+
+    let records = store.records_for(2);
+    let report = undo_run(
+        &fake.client(),
+        &SyncContext::new(FAKE_LIST_ID),
+        &mut store,
+        &records,
+        &HashSet::new(),
+    )
+    .unwrap();
+    assert_eq!(report.undone, vec![4, 3]);
+    assert!(report.skipped.is_empty() && report.failed.is_empty());
+
+    // The talk is back at 18:00, and the social is made again under a new ID
+    let talk = task_named(&fake, "Talk");
+    let start = NaiveDateTime::parse_from_str("2024-10-01 18:00", "%Y-%m-%d %H:%M").unwrap();
+    assert_eq!(
+        talk["start_date"],
+        start.and_utc().timestamp_millis().to_string()
+    );
+    assert_ne!(talk["start_date"], talk_before["start_date"]);
+    let social = task_named(&fake, "Social");
+    assert_ne!(social["id"], social_id);
+    assert_eq!(social["description"], "Description of Social");
+
+    // The social's mapping is for its new task, and the talk's will be synced again next run
+    let mappings = store.load().unwrap();
+    assert_eq!(mappings.len(), 2);
+    let social_mapping = mappings
+        .iter()
+        .find(|mapping| mapping.calendar_id == "uid-2")
+        .unwrap();
+    assert_eq!(social_mapping.clickup_id, social["id"]);
+    let talk_mapping = mappings
+        .iter()
+        .find(|mapping| mapping.calendar_id == "uid-1")
+        .unwrap();
+    assert_eq!(talk_mapping.fingerprint, None);
+    assert_eq!(
+        talk_mapping.last_synced().unwrap().start_date,
+        Some(start.and_utc().timestamp_millis())
+    );
+}
+
+#[test]
+fn undoing_creates_deletes_the_tasks() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    let mut store = AuditedStore {
+        run: 1,
+        ..Default::default()
+    };
+    sync_events(
+        &api,
+        &context,
+        &mut store,
+        vec![event(
+            "uid-1",
+            "Talk",
+            "2024-10-01 18:00",
+            "2024-10-01 19:00",
+        )],
+    )
+    .unwrap();
+
+    let records = store.records_for(1);
+    let report = undo_run(&api, &context, &mut store, &records, &HashSet::new()).unwrap();
+    assert_eq!(report.undone, vec![1]);
+    assert!(fake.tasks().is_empty());
+    assert!(store.load().unwrap().is_empty());
+}
+
+#[test]
+fn undoing_a_retire_restores_the_task() {
+    let (fake, api, context) = setup("[deletion]\npolicy = \"archive\"");
+    let mut store = AuditedStore {
+        run: 1,
+        ..Default::default()
+    };
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    sync_events(&api, &context, &mut store, vec![talk]).unwrap();
+    store.run = 2;
+    sync_events(&api, &context, &mut store, vec![]).unwrap();
+    let task_id = store.entries_for(2)[0].task_id.clone();
+    assert_eq!(fake.task(&task_id).unwrap()["archived"], true);
+
+    let records = store.records_for(2);
+    undo_run(&api, &context, &mut store, &records, &HashSet::new()).unwrap();
+    assert_eq!(fake.task(&task_id).unwrap()["archived"], false);
+    assert_eq!(store.load().unwrap()[0].tombstone, None);
+}
+
+#[test]
+fn undoing_again_carries_on_from_where_a_failed_undo_stopped() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    let mut store = AuditedStore {
+        run: 1,
+        ..Default::default()
+    };
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    let social = event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00");
+    sync_events(
+        &api,
+        &context,
+        &mut store,
+        vec![talk.clone(), social.clone()],
+    )
+    .unwrap();
+    // Run 2 renames both
+    store.run = 2;
+    let renamed = |event: &ParsedEvent| {
+        let mut event = event.clone();
+        event.summary = format!("{} (renamed)", event.summary);
+        event
+    };
+    sync_events(
+        &api,
+        &context,
+        &mut store,
+        vec![renamed(&talk), renamed(&social)],
+    )
+    .unwrap();
+    let social_id = task_named(&fake, "Social (renamed)")["id"].clone();
+
+    // The database goes away after the social's rename is undone, while the talk's is being undone
+    store.flushes_left = Some(1);
+    let records = store.records_for(2);
+    assert!(undo_run(&api, &context, &mut store, &records, &HashSet::new()).is_err());
+    assert_eq!(store.records_for(2).len(), 1);
+
+    // Undoing again only undoes the talk's rename, rather than the social's again
+    store.flushes_left = None;
+    let before = fake.requests().len();
+    let records = store.records_for(2);
+    let report = undo_run(&api, &context, &mut store, &records, &HashSet::new()).unwrap();
+    assert_eq!(report.undone, vec![records[0].audit_id]);
+    assert!(!fake.requests()[before..]
+        .iter()
+        .any(|request| request.ends_with(social_id.as_str().unwrap())));
+    assert!(store.records_for(2).is_empty());
+    assert_eq!(task_named(&fake, "Talk")["id"], records[0].task_id);
+    assert_eq!(task_named(&fake, "Social")["id"], social_id);
+}
+
+#[test]
+fn tasks_changed_by_a_later_run_are_left_alone() {
+    let fake = FakeClickUp::start();
+    let mut store = AuditedStore::default();
+    two_runs(&fake, &mut store);
+    let talk_id = store.entries_for(2)[1].task_id.clone();
+    let requests = fake.requests().len();
+
+    let records = store.records_for(1);
+    let report = undo_run(
+        &fake.client(),
+        &SyncContext::new(FAKE_LIST_ID),
+        &mut store,
+        &records,
+        &HashSet::from([talk_id.clone()]),
+    )
+    .unwrap();
+    // The talk was updated by run 2, and the social's task was deleted by it
+    assert_eq!(report.undone, vec![2]);
+    assert_eq!(report.skipped.len(), 1);
+    assert!(report.skipped[0].1.contains(&talk_id));
+    assert!(fake.task(&talk_id).is_some());
+    assert_eq!(fake.requests().len(), requests + 1);
+}
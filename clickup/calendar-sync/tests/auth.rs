@@ -0,0 +1,154 @@
+//! Tests of authenticating as a ClickUp OAuth app
+mod common;
+
+use std::thread;
+
+use clickup_ical_sync::{
+    auth::{CallbackListener, OAuthApp, TokenCipher},
+    clickup::ClickUpApiInstance,
+    sync::SyncContext,
+};
+use common::{
+    event, FakeClickUp, FAKE_LIST_ID, FAKE_OAUTH_CLIENT_SECRET, FAKE_OAUTH_CODE, FAKE_OAUTH_TOKEN,
+};
+
+/// 32 bytes of key, base64 encoded
+const KEY: &str = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+const OTHER_KEY: &str = "ZmVkY2JhOTg3NjU0MzIxMGZlZGNiYTk4NzY1NDMyMTA=";
+
+#[test]
+fn tokens_are_encrypted_and_only_decrypt_with_the_same_key() {
+    let cipher = TokenCipher::new(KEY).unwrap();
+    let encrypted = cipher.encrypt("secret_token");
+
+    assert!(!encrypted.contains("secret_token"));
+    // A new nonce each time
+    assert_ne!(encrypted, cipher.encrypt("secret_token"));
+    assert_eq!(cipher.decrypt(&encrypted).unwrap(), "secret_token");
+    assert!(TokenCipher::new(OTHER_KEY)
+        .unwrap()
+        .decrypt(&encrypted)
+        .is_err());
+    assert!(TokenCipher::new("dG9vIHNob3J0").is_err());
+}
+
+#[test]
+fn callback_listener_gets_the_code() {
+    let listener = CallbackListener::bind("http://127.0.0.1:0/callback").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let browser = thread::spawn(move || {
+        let not_found = reqwest::blocking::get(format!("http://{}/favicon.ico", addr)).unwrap();
+        assert_eq!(not_found.status(), 404);
+        let callback =
+            reqwest::blocking::get(format!("http://{}/callback?code=abc123", addr)).unwrap();
+        assert_eq!(callback.status(), 200);
+    });
+
+    assert_eq!(listener.wait_for_code().unwrap(), "abc123");
+    browser.join().unwrap();
+}
+
+#[test]
+fn callback_listener_reports_refusal() {
+    let listener = CallbackListener::bind("http://127.0.0.1:0/callback").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let browser = thread::spawn(move || {
+        reqwest::blocking::get(format!("http://{}/callback?error=access_denied", addr)).unwrap()
+    });
+
+    let err = listener.wait_for_code().unwrap_err();
+    assert!(err.to_string().contains("access_denied"));
+    assert_eq!(browser.join().unwrap().status(), 400);
+}
+
+#[test]
+fn oauth_token_is_exchanged_and_used_as_a_bearer_token() {
+    let fake = FakeClickUp::start();
+    let app = OAuthApp {
+        client_id: "fake_client".to_string(),
+        client_secret: FAKE_OAUTH_CLIENT_SECRET.to_string(),
+        redirect_uri: "http://localhost:8910/callback".to_string(),
+    };
+    assert_eq!(
+        app.authorize_url(),
+        "https://app.clickup.com/api?client_id=fake_client&redirect_uri=http%3A%2F%2Flocalhost%3A8910%2Fcallback"
+    );
+
+    let unauthorised = ClickUpApiInstance::unauthenticated_with_base_url(fake.base_url.clone());
+    assert!(unauthorised
+        .exchange_oauth_code(&app.client_id, &app.client_secret, "wrong_code")
+        .is_err());
+    let token = unauthorised
+        .exchange_oauth_code(&app.client_id, &app.client_secret, FAKE_OAUTH_CODE)
+        .unwrap();
+    assert_eq!(token, FAKE_OAUTH_TOKEN);
+    // Without a token, nothing else is allowed
+    assert!(unauthorised.get_teams().is_err());
+
+    let api = ClickUpApiInstance::with_base_url(
+        format!("Bearer {}", token),
+        FAKE_LIST_ID.to_string(),
+        fake.base_url.clone(),
+    );
+    let id = SyncContext::new(FAKE_LIST_ID)
+        .create_task(
+            &api,
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .unwrap();
+    assert!(fake.task(&id).is_some());
+}
+
+#[test]
+fn stored_token_is_encrypted_and_replaced_by_newer_ones() {
+    use clickup_ical_sync::{
+        db::{load_oauth_token, save_oauth_token},
+        schema::clickup_oauth_token,
+    };
+    use diesel::prelude::*;
+
+    let Some(db) = common::TempDatabase::new() else {
+        return;
+    };
+    let cipher = TokenCipher::new(KEY).unwrap();
+    assert_eq!(
+        load_oauth_token(&mut db.connection(), &cipher).unwrap(),
+        None
+    );
+
+    save_oauth_token(&mut db.connection(), &cipher, "first_token").unwrap();
+    save_oauth_token(&mut db.connection(), &cipher, "second_token").unwrap();
+    assert_eq!(
+        load_oauth_token(&mut db.connection(), &cipher).unwrap(),
+        Some("second_token".to_string())
+    );
+
+    let stored = clickup_oauth_token::table
+        .select(clickup_oauth_token::encrypted_token)
+        .load::<String>(&mut db.connection())
+        .unwrap();
+    assert_eq!(stored.len(), 1);
+    assert!(!stored[0].contains("second_token"));
+}
+
+#[test]
+fn stored_token_fails_to_load_with_another_key() {
+    use clickup_ical_sync::db::{load_oauth_token, save_oauth_token};
+
+    let Some(db) = common::TempDatabase::new() else {
+        return;
+    };
+    save_oauth_token(
+        &mut db.connection(),
+        &TokenCipher::new(KEY).unwrap(),
+        "secret_token",
+    )
+    .unwrap();
+
+    // e.g. CLICKUP_TOKEN_KEY was changed after authorising
+    let err =
+        load_oauth_token(&mut db.connection(), &TokenCipher::new(OTHER_KEY).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("CLICKUP_TOKEN_KEY"));
+}
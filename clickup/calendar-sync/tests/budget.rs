@@ -0,0 +1,107 @@
+//! Tests of doing the most urgent work first, and stopping when a run's request budget is used up
+mod common;
+
+use clickup_ical_sync::{
+    clickup::ClickUpApiInstance,
+    sync::{sync_events, SyncContext},
+};
+use common::{context, event, FakeClickUp, MemoryStore, FAKE_LIST_ID};
+
+/// A client & sync as a new run would make, allowed to make the given number of requests
+fn run_with_budget(fake: &FakeClickUp, max_requests: u32) -> (ClickUpApiInstance, SyncContext) {
+    let api = fake.client();
+    let context = context(&api, &format!("[budget]\nmax_requests = {}", max_requests));
+    (api, context)
+}
+
+/// The requests made since `before` requests had been made
+fn requests_since(fake: &FakeClickUp, before: usize) -> Vec<String> {
+    fake.requests()[before..].to_vec()
+}
+
+#[test]
+fn creates_then_deletes_then_updates_with_the_rest_carried_over() {
+    let fake = FakeClickUp::start();
+    let mut store = MemoryStore::default();
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    let social = event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00");
+    let quiz = event("uid-3", "Quiz", "2024-10-03 18:00", "2024-10-03 20:00");
+    sync_events(
+        &fake.client(),
+        &SyncContext::new(FAKE_LIST_ID),
+        &mut store,
+        vec![talk.clone(), social.clone(), quiz],
+    )
+    .unwrap();
+    let task_id = |uid: &str| {
+        store
+            .mappings
+            .iter()
+            .find(|mapping| mapping.calendar_id == uid)
+            .map(|mapping| mapping.clickup_id.clone())
+    };
+    let (talk_id, quiz_id) = (task_id("uid-1").unwrap(), task_id("uid-3").unwrap());
+
+    // The talk moves, the quiz is cancelled, and two events are added
+    let mut moved_talk = talk;
+    moved_talk.start_time = moved_talk
+        .start_time
+        .map(|start| start + chrono::Duration::hours(1));
+    let events = vec![
+        moved_talk,
+        social,
+        event("uid-4", "Hackathon", "2024-10-04 10:00", "2024-10-04 18:00"),
+        event(
+            "uid-5",
+            "Careers fair",
+            "2024-10-05 12:00",
+            "2024-10-05 16:00",
+        ),
+    ];
+
+    // Only enough budget for the new events
+    let before = fake.requests().len();
+    let (api, context) = run_with_budget(&fake, 2);
+    sync_events(&api, &context, &mut store, events.clone()).unwrap();
+    assert_eq!(
+        requests_since(&fake, before),
+        vec!["POST /list/901/task", "POST /list/901/task"]
+    );
+    assert!(fake.task(&quiz_id).is_some());
+
+    // Next run the cancelled event is dealt with first
+    let before = fake.requests().len();
+    let (api, context) = run_with_budget(&fake, 1);
+    sync_events(&api, &context, &mut store, events.clone()).unwrap();
+    assert_eq!(
+        requests_since(&fake, before),
+        vec![format!("DELETE /task/{}", quiz_id)]
+    );
+
+    // And then the changed event, leaving the unchanged one alone
+    let before = fake.requests().len();
+    let (api, context) = run_with_budget(&fake, 100);
+    sync_events(&api, &context, &mut store, events).unwrap();
+    assert_eq!(
+        requests_since(&fake, before),
+        vec![format!("PUT /task/{}", talk_id)]
+    );
+    assert_eq!(fake.tasks().len(), 4);
+}
+
+#[test]
+fn unchanged_events_are_skipped() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    let mut store = MemoryStore::default();
+    let events = vec![
+        event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00"),
+    ];
+    sync_events(&api, &context, &mut store, events.clone()).unwrap();
+
+    let before = fake.requests().len();
+    sync_events(&api, &context, &mut store, events).unwrap();
+    assert!(requests_since(&fake, before).is_empty());
+}
@@ -0,0 +1,181 @@
+//! Tests of the ClickUp client against the fake ClickUp server in `common`
+mod common;
+
+use clickup_ical_sync::{clickup::ClickUpError, sync::SyncContext};
+use common::{event, mapping, FakeClickUp, FAKE_LIST_ID};
+use serde_json::json;
+
+#[test]
+fn create_task_creates_task_in_target_list() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+
+    let id = context
+        .create_task(
+            &api,
+            &event(
+                "uid-1",
+                "Freshers' Fair",
+                "2024-10-01 10:00",
+                "2024-10-01 16:00",
+            ),
+        )
+        .expect("Failed to create task");
+
+    let task = fake.task(&id).expect("Task not on fake server");
+    assert_eq!(task["name"], "Freshers' Fair");
+    assert_eq!(task["description"], "Description of Freshers' Fair");
+    assert_eq!(task["list"]["id"], FAKE_LIST_ID);
+    assert_eq!(task["start_date"], "1727776800000");
+    assert_eq!(task["due_date"], "1727798400000");
+    assert_eq!(task["start_date_time"], true);
+}
+
+#[test]
+fn create_task_treats_midnight_to_midnight_as_all_day() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+
+    let id = context
+        .create_task(
+            &api,
+            &event("uid-1", "Hackathon", "2024-10-05 00:00", "2024-10-07 00:00"),
+        )
+        .unwrap();
+
+    let task = fake.task(&id).unwrap();
+    assert_eq!(task["start_date_time"], false);
+    assert_eq!(task["due_date_time"], false);
+    // Rolled back a day, as ClickUp treats a date with no time as the end of that day
+    assert_eq!(task["due_date"], "1728172800000");
+}
+
+#[test]
+fn update_task_overwrites_task_with_event_details() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    let id = context
+        .create_task(
+            &api,
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .unwrap();
+
+    context
+        .update_task(
+            &api,
+            &mapping(&id, "uid-1"),
+            &event(
+                "uid-1",
+                "Talk (moved)",
+                "2024-10-02 18:00",
+                "2024-10-02 19:00",
+            ),
+        )
+        .expect("Failed to update task");
+
+    let task = api.get_task(&id).expect("Failed to fetch task");
+    assert_eq!(task.name, "Talk (moved)");
+    assert_eq!(task.start_date.as_deref(), Some("1727892000000"));
+}
+
+#[test]
+fn delete_task_removes_task() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    let id = context
+        .create_task(
+            &api,
+            &event("uid-1", "Social", "2024-10-01 19:00", "2024-10-01 23:00"),
+        )
+        .unwrap();
+
+    api.delete_task(&id).expect("Failed to delete task");
+
+    assert!(fake.task(&id).is_none());
+    assert_eq!(
+        fake.requests(),
+        vec![
+            format!("POST /list/{}/task", FAKE_LIST_ID),
+            format!("DELETE /task/{}", id)
+        ]
+    );
+}
+
+#[test]
+fn missing_task_is_reported_as_404() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+
+    let err = context
+        .update_task(
+            &api,
+            &mapping("gone", "uid-1"),
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .unwrap_err();
+    assert!(matches!(err, ClickUpError::Status { status, .. } if status.as_u16() == 404));
+
+    let err = api.delete_task("gone").unwrap_err();
+    assert!(matches!(err, ClickUpError::Status { status, .. } if status.as_u16() == 404));
+}
+
+#[test]
+fn server_errors_are_surfaced_and_nothing_is_created() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    fake.fail_next(500, json!({"err": "Internal error", "ECODE": "OAUTH_000"}));
+
+    let err = context
+        .create_task(
+            &api,
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .unwrap_err();
+
+    match err {
+        ClickUpError::Status { status, body } => {
+            assert_eq!(status.as_u16(), 500);
+            assert!(body.contains("Internal error"));
+        }
+        other => panic!("Expected status error, got {:?}", other),
+    }
+    assert!(fake.tasks().is_empty());
+}
+
+#[test]
+fn bad_token_is_rejected() {
+    let fake = FakeClickUp::start();
+    let api = clickup_ical_sync::clickup::ClickUpApiInstance::with_base_url(
+        "pk_wrong".to_string(),
+        FAKE_LIST_ID.to_string(),
+        fake.base_url.clone(),
+    );
+
+    let err = api.get_task("anything").unwrap_err();
+    assert!(matches!(err, ClickUpError::Status { status, .. } if status.as_u16() == 401));
+}
+
+#[test]
+fn rate_limited_request_is_retried_once() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    fake.rate_limit_next();
+
+    let id = context
+        .create_task(
+            &api,
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .expect("Request should have been retried after the rate limit reset");
+
+    assert!(fake.task(&id).is_some());
+    assert_eq!(fake.requests().len(), 2);
+}
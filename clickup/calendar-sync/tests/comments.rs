@@ -0,0 +1,68 @@
+//! Tests of commenting on tasks to say what changed when they're updated
+mod common;
+
+use clickup_ical_sync::sync::sync_events;
+use common::{event, setup, MemoryStore};
+use serde_json::json;
+
+#[test]
+fn updates_are_commented_with_what_changed() {
+    let (fake, api, context) = setup("[comments]\non_update = true");
+    let mut store = MemoryStore::default();
+    let mut talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    talk.location = Some("308".to_string());
+    sync_events(&api, &context, &mut store, vec![talk.clone()]).unwrap();
+    let task_id = store.mappings[0].clickup_id.clone();
+    // Nothing to say about a task we've only just made
+    assert!(fake.comments(&task_id).is_empty());
+
+    talk.start_time = Some(talk.start_time.unwrap() + chrono::Duration::days(1));
+    talk.end_time = Some(talk.end_time.unwrap() + chrono::Duration::days(1));
+    talk.location = Some("311".to_string());
+    sync_events(&api, &context, &mut store, vec![talk.clone()]).unwrap();
+    let comments = fake.comments(&task_id);
+    assert_eq!(comments.len(), 1);
+    assert_eq!(
+        comments[0]["comment_text"],
+        "Updated from the calendar:\n\
+         - Start: Tue 1 Oct 18:00 → Wed 2 Oct 18:00\n\
+         - End: Tue 1 Oct 19:00 → Wed 2 Oct 19:00\n\
+         - Location: 308 → 311"
+    );
+
+    // Syncing again without changes says nothing more
+    sync_events(&api, &context, &mut store, vec![talk]).unwrap();
+    assert_eq!(fake.comments(&task_id).len(), 1);
+}
+
+#[test]
+fn assignees_can_be_mentioned() {
+    let (fake, api, context) = setup("[comments]\non_update = true\nmention_assignees = true");
+    let mut store = MemoryStore::default();
+    let mut talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    sync_events(&api, &context, &mut store, vec![talk.clone()]).unwrap();
+    let task_id = store.mappings[0].clickup_id.clone();
+    fake.edit_task(&task_id, json!({"assignees": {"add": [7], "rem": []}}));
+
+    talk.summary = "Tech talk".to_string();
+    sync_events(&api, &context, &mut store, vec![talk]).unwrap();
+    let comments = fake.comments(&task_id);
+    assert_eq!(comments.len(), 1);
+    let parts = comments[0]["comment"].as_array().unwrap();
+    assert_eq!(
+        parts[0]["text"],
+        "Updated from the calendar:\n- Name: Talk → Tech talk\n"
+    );
+    assert_eq!(parts[1], json!({"type": "tag", "user": {"id": 7}}));
+}
+
+#[test]
+fn no_comments_unless_turned_on() {
+    let (fake, api, context) = setup("");
+    let mut store = MemoryStore::default();
+    let mut talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    sync_events(&api, &context, &mut store, vec![talk.clone()]).unwrap();
+    talk.summary = "Tech talk".to_string();
+    sync_events(&api, &context, &mut store, vec![talk]).unwrap();
+    assert!(fake.comments(&store.mappings[0].clickup_id).is_empty());
+}
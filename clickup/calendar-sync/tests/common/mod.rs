@@ -0,0 +1,1156 @@
+//! A fake ClickUp server for testing the sync offline
+//!
+//! It runs in-process on a random local port, and implements just enough of the ClickUp v2 API for our client:
+//! creating, updating, fetching, listing and deleting tasks, browsing & setting up the workspace, ClickUp's rate limit headers, and the ability to inject errors.
+//! Point a `ClickUpApiInstance` at it using `FakeClickUp::client()` (or `ClickUpApiInstance::with_base_url`).
+#![allow(dead_code)] // not every test file uses every helper
+
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    error::Error,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::NaiveDateTime;
+use clickup_ical_sync::{
+    clickup::ClickUpApiInstance,
+    config::parse_config,
+    docsoc_ical::ParsedEvent,
+    models::{assignees_to_json, synced_fields_to_json, CalendarMapping},
+    ownership::SyncedFields,
+    store::MappingStore,
+    sync::SyncContext,
+};
+use serde_json::{json, Map, Value};
+use tiny_http::{Header, Method, Response, Server};
+
+/// Access token the fake server accepts, anything else gets a 401
+pub const FAKE_TOKEN: &str = "pk_fake_token";
+/// ID of the list `FakeClickUp::client()` syncs into
+pub const FAKE_LIST_ID: &str = "901";
+/// Client secret of the fake OAuth app
+pub const FAKE_OAUTH_CLIENT_SECRET: &str = "fake_client_secret";
+/// Code the fake server accepts in exchange for `FAKE_OAUTH_TOKEN`
+pub const FAKE_OAUTH_CODE: &str = "fake_code";
+/// OAuth token the fake server hands out & accepts (as "Bearer fake_oauth_token")
+pub const FAKE_OAUTH_TOKEN: &str = "fake_oauth_token";
+/// Rate limit the fake server reports in its X-RateLimit-Limit header
+pub const FAKE_RATE_LIMIT: u32 = 100;
+/// Tasks per page when listing the tasks in a list (ClickUp uses 100, but small pages let us test paging)
+pub const FAKE_TASK_PAGE_SIZE: usize = 2;
+
+/// Everything the fake server knows about, shared between the server thread and the test
+#[derive(Default)]
+struct FakeState {
+    /// Tasks by ID, stored as the JSON ClickUp would send back from GET /task/{id}
+    tasks: HashMap<String, Value>,
+    /// Custom fields available on each list, by list ID
+    custom_fields: HashMap<String, Vec<Value>>,
+    /// Statuses of each list, by list ID (lists we haven't been told about have "to do" and "complete")
+    list_statuses: HashMap<String, Vec<String>>,
+    /// Members of the fake workspace, as the "user" objects ClickUp returns
+    members: Vec<Value>,
+    /// Spaces in the fake workspace, each with its "folders" (each with its "lists"), folderless "lists" and "tags"
+    spaces: Vec<Value>,
+    /// Comments posted on each task, by task ID, as the payloads they were posted with
+    comments: HashMap<String, Vec<Value>>,
+    /// Task templates in the fake workspace, by ID, as (name, fields of the tasks made from them)
+    task_templates: HashMap<String, (String, Value)>,
+    /// Docs in the fake workspace, in the order they were made, each with its "parent" and "pages"
+    docs: Vec<Value>,
+    /// Used to hand out task IDs
+    next_id: u64,
+    /// Errors to respond with instead of handling the next requests
+    injected: VecDeque<InjectedResponse>,
+    /// Every request we've received, as "METHOD /path"
+    requests: Vec<String>,
+    /// Requests left in the current rate limit window, reported in X-RateLimit-Remaining
+    rate_limit_remaining: u32,
+}
+
+/// A response to send instead of handling a request, for injecting errors
+struct InjectedResponse {
+    status: u16,
+    body: Value,
+    /// Headers to send, replacing the usual rate limit headers of the same name
+    headers: Vec<(String, String)>,
+}
+
+/// Handle to a running fake ClickUp server, which is shut down when dropped
+pub struct FakeClickUp {
+    /// Base URL to give the client, e.g. http://127.0.0.1:12345/api/v2
+    pub base_url: String,
+    state: Arc<Mutex<FakeState>>,
+    server: Arc<Server>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl FakeClickUp {
+    /// Start a fake ClickUp server on a random port
+    pub fn start() -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").expect("Failed to start fake ClickUp"));
+        let base_url = format!(
+            "http://{}/api/v2",
+            server
+                .server_addr()
+                .to_ip()
+                .expect("Fake ClickUp not on IP")
+        );
+        let state = Arc::new(Mutex::new(FakeState {
+            rate_limit_remaining: FAKE_RATE_LIMIT,
+            ..Default::default()
+        }));
+
+        let handle = {
+            let server = server.clone();
+            let state = state.clone();
+            thread::spawn(move || {
+                for mut request in server.incoming_requests() {
+                    let mut body = String::new();
+                    request.as_reader().read_to_string(&mut body).ok();
+                    let authorised = request.headers().iter().any(|h| {
+                        h.field.equiv("Authorization")
+                            && (h.value.as_str() == FAKE_TOKEN
+                                || h.value.as_str() == format!("Bearer {}", FAKE_OAUTH_TOKEN))
+                    });
+
+                    let (status, response, headers) = state.lock().unwrap().handle(
+                        request.method(),
+                        request.url(),
+                        &body,
+                        authorised,
+                    );
+
+                    let mut response = Response::from_string(response.to_string())
+                        .with_status_code(status)
+                        .with_header(header("Content-Type", "application/json"));
+                    for (name, value) in headers {
+                        response.add_header(header(&name, &value));
+                    }
+                    request.respond(response).ok();
+                }
+            })
+        };
+
+        Self {
+            base_url,
+            state,
+            server,
+            handle: Some(handle),
+        }
+    }
+
+    /// A client syncing into `FAKE_LIST_ID` on this server
+    pub fn client(&self) -> ClickUpApiInstance {
+        ClickUpApiInstance::with_base_url(
+            FAKE_TOKEN.to_string(),
+            FAKE_LIST_ID.to_string(),
+            self.base_url.clone(),
+        )
+    }
+
+    /// Respond to the next request with the given status and body rather than handling it
+    pub fn fail_next(&self, status: u16, body: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .injected
+            .push_back(InjectedResponse {
+                status,
+                body,
+                headers: vec![],
+            });
+    }
+
+    /// Respond to the next request with a 429, saying the rate limit resets right away
+    pub fn rate_limit_next(&self) {
+        self.state
+            .lock()
+            .unwrap()
+            .injected
+            .push_back(InjectedResponse {
+                status: 429,
+                body: json!({"err": "Rate limit reached", "ECODE": "APP_002"}),
+                headers: vec![
+                    ("X-RateLimit-Remaining".to_string(), "0".to_string()),
+                    ("X-RateLimit-Reset".to_string(), now_secs().to_string()),
+                ],
+            });
+    }
+
+    /// Add a task to a list directly (as if someone made it by hand in ClickUp), returning its ID
+    pub fn insert_task(&self, list_id: &str, task: Value) -> String {
+        self.state.lock().unwrap().insert_task(list_id, task)
+    }
+
+    /// Add a custom field to a list, e.g. json!({"id": "f1", "name": "Location", "type": "short_text"})
+    pub fn add_custom_field(&self, list_id: &str, field: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .custom_fields
+            .entry(list_id.to_string())
+            .or_default()
+            .push(field);
+    }
+
+    /// Set the statuses tasks in a list can have
+    pub fn set_list_statuses(&self, list_id: &str, statuses: &[&str]) {
+        self.state.lock().unwrap().list_statuses.insert(
+            list_id.to_string(),
+            statuses.iter().map(|status| status.to_string()).collect(),
+        );
+    }
+
+    /// Add a member to the fake workspace
+    pub fn add_member(&self, id: i64, username: &str, email: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .members
+            .push(json!({"id": id, "username": username, "email": email}));
+    }
+
+    /// Add a task template to the fake workspace
+    /// ### Arguments
+    /// * `id` - ID of the template, e.g. "t-1"
+    /// * `name` - Name of the template
+    /// * `task` - Fields of the tasks made from it, as a create payload (e.g. description, tags, checklists)
+    pub fn add_task_template(&self, id: &str, name: &str, task: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .task_templates
+            .insert(id.to_string(), (name.to_string(), task));
+    }
+
+    /// Add a space to the fake workspace
+    pub fn add_space(&self, id: &str, name: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .spaces
+            .push(json!({"id": id, "name": name, "folders": [], "lists": [], "tags": []}));
+    }
+
+    /// The tags in a space
+    pub fn space_tags(&self, space_id: &str) -> Vec<Value> {
+        self.state.lock().unwrap().space_mut(space_id)["tags"]
+            .as_array()
+            .unwrap()
+            .clone()
+    }
+
+    /// Add a folder to a space
+    pub fn add_folder(&self, space_id: &str, id: &str, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        let space = state.space_mut(space_id);
+        space["folders"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({"id": id, "name": name, "lists": []}));
+    }
+
+    /// Add a list to a space, in the given folder or directly in the space
+    pub fn add_list(&self, space_id: &str, folder_id: Option<&str>, id: &str, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        let space = state.space_mut(space_id);
+        let lists = match folder_id {
+            Some(folder_id) => &mut space["folders"]
+                .as_array_mut()
+                .unwrap()
+                .iter_mut()
+                .find(|folder| folder["id"] == folder_id)
+                .expect("No such folder")["lists"],
+            None => &mut space["lists"],
+        };
+        lists
+            .as_array_mut()
+            .unwrap()
+            .push(json!({"id": id, "name": name}));
+    }
+
+    /// The user IDs assigned to a task, in order
+    pub fn assignees(&self, task_id: &str) -> Vec<i64> {
+        let mut ids: Vec<i64> = self.task(task_id).unwrap()["assignees"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|assignee| assignee["id"].as_i64().unwrap())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// The value of a custom field on a task, if set
+    pub fn custom_field_value(&self, task_id: &str, field_id: &str) -> Option<Value> {
+        custom_field_value(&self.task(task_id)?, field_id)
+    }
+
+    /// Change a task as if someone edited it by hand in ClickUp, using the same fields as an update payload
+    pub fn edit_task(&self, id: &str, changes: Value) {
+        let mut state = self.state.lock().unwrap();
+        merge_task_fields(state.tasks.get_mut(id).expect("No such task"), &changes);
+    }
+
+    /// Delete a task as if someone deleted it by hand in ClickUp
+    pub fn delete_task(&self, id: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .tasks
+            .remove(id)
+            .expect("No such task");
+    }
+
+    /// The comments posted on a task, as the payloads they were posted with
+    pub fn comments(&self, task_id: &str) -> Vec<Value> {
+        self.state
+            .lock()
+            .unwrap()
+            .comments
+            .get(task_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The subtasks of a task, in the order they were made
+    pub fn subtasks(&self, parent_id: &str) -> Vec<Value> {
+        self.state.lock().unwrap().subtasks(parent_id)
+    }
+
+    /// The items on the checklists of a task
+    pub fn checklist_items(&self, task_id: &str) -> Vec<Value> {
+        self.task(task_id).unwrap()["checklists"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|checklist| checklist["items"].as_array().unwrap().clone())
+            .collect()
+    }
+
+    /// Tick off a checklist item as if someone did it in ClickUp
+    pub fn resolve_checklist_item(&self, item_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        let item = state
+            .tasks
+            .values_mut()
+            .filter_map(|task| task.get_mut("checklists")?.as_array_mut())
+            .flatten()
+            .flat_map(|checklist| checklist["items"].as_array_mut().unwrap())
+            .find(|item| item["id"] == item_id)
+            .expect("No such checklist item");
+        item["resolved"] = json!(true);
+    }
+
+    /// The docs made so far, each with its "parent" and "pages" (as the payloads they were made with)
+    pub fn docs(&self) -> Vec<Value> {
+        self.state.lock().unwrap().docs.clone()
+    }
+
+    /// The task with the given ID, as ClickUp would return it
+    pub fn task(&self, id: &str) -> Option<Value> {
+        self.state.lock().unwrap().tasks.get(id).cloned()
+    }
+
+    /// All tasks on the server
+    pub fn tasks(&self) -> Vec<Value> {
+        self.state.lock().unwrap().tasks.values().cloned().collect()
+    }
+
+    /// Every request received so far, as "METHOD /path" (without the /api/v2 prefix)
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Number of requests of the given kind received so far, e.g. "PUT /task"
+    pub fn count_requests(&self, prefix: &str) -> usize {
+        self.requests()
+            .iter()
+            .filter(|request| request.starts_with(prefix))
+            .count()
+    }
+}
+
+impl Drop for FakeClickUp {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+impl FakeState {
+    /// The space with the given ID
+    fn space_mut(&mut self, id: &str) -> &mut Value {
+        self.spaces
+            .iter_mut()
+            .find(|space| space["id"] == id)
+            .expect("No such space")
+    }
+
+    /// The folder with the given ID, in whichever space it's in
+    fn folder_mut(&mut self, id: &str) -> Option<&mut Value> {
+        self.spaces
+            .iter_mut()
+            .flat_map(|space| space["folders"].as_array_mut().unwrap().iter_mut())
+            .find(|folder| folder["id"] == id)
+    }
+
+    /// Handle a single request, returning (status, body, extra headers)
+    fn handle(
+        &mut self,
+        method: &Method,
+        url: &str,
+        body: &str,
+        authorised: bool,
+    ) -> (u16, Value, Vec<(String, String)>) {
+        let path = url
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("/api/v2");
+        self.requests.push(format!("{} {}", method, path));
+
+        self.rate_limit_remaining = self.rate_limit_remaining.saturating_sub(1);
+        let mut headers = vec![
+            ("X-RateLimit-Limit".to_string(), FAKE_RATE_LIMIT.to_string()),
+            (
+                "X-RateLimit-Remaining".to_string(),
+                self.rate_limit_remaining.to_string(),
+            ),
+            (
+                "X-RateLimit-Reset".to_string(),
+                (now_secs() + 60).to_string(),
+            ),
+        ];
+
+        if let Some(injected) = self.injected.pop_front() {
+            headers.retain(|(name, _)| !injected.headers.iter().any(|(n, _)| n == name));
+            headers.extend(injected.headers);
+            return (injected.status, injected.body, headers);
+        }
+
+        // Swapping an OAuth code for a token is how you get a token in the first place
+        if path == "/oauth/token" {
+            let (status, body) = if query_param(url, "client_secret")
+                == Some(FAKE_OAUTH_CLIENT_SECRET)
+                && query_param(url, "code") == Some(FAKE_OAUTH_CODE)
+            {
+                (200, json!({"access_token": FAKE_OAUTH_TOKEN}))
+            } else {
+                (400, json!({"err": "Code invalid", "ECODE": "OAUTH_014"}))
+            };
+            return (status, body, headers);
+        }
+
+        if !authorised {
+            return (
+                401,
+                json!({"err": "Token invalid", "ECODE": "OAUTH_025"}),
+                headers,
+            );
+        }
+
+        let payload: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let (status, body) = match (method, segments.as_slice()) {
+            (Method::Post, ["list", list_id, "task"]) => {
+                let id = self.insert_task(list_id, payload);
+                (200, self.tasks[&id].clone())
+            }
+            (Method::Get, ["list", list_id, "task"]) => {
+                let page = query_param(url, "page")
+                    .and_then(|page| page.parse::<usize>().ok())
+                    .unwrap_or(0);
+                let archived = query_param(url, "archived") == Some("true");
+                let mut tasks: Vec<&Value> = self
+                    .tasks
+                    .values()
+                    .filter(|task| task["list"]["id"] == *list_id && task["archived"] == archived)
+                    .collect();
+                tasks.sort_by_key(|task| task["id"].as_str().unwrap_or_default().to_string());
+                let page_tasks = tasks
+                    .chunks(FAKE_TASK_PAGE_SIZE)
+                    .nth(page)
+                    .unwrap_or_default();
+                (
+                    200,
+                    json!({
+                        "tasks": page_tasks,
+                        "last_page": (page + 1) * FAKE_TASK_PAGE_SIZE >= tasks.len(),
+                    }),
+                )
+            }
+            (Method::Get, ["task", id]) => match self.tasks.get(*id) {
+                Some(task) => {
+                    let mut task = task.clone();
+                    if query_param(url, "include_subtasks") == Some("true") {
+                        task["subtasks"] = json!(self.subtasks(id));
+                    }
+                    (200, task)
+                }
+                None => task_not_found(),
+            },
+            (Method::Put, ["task", id]) => match self.tasks.get_mut(*id) {
+                Some(task) => {
+                    merge_task_fields(task, &payload);
+                    (200, task.clone())
+                }
+                None => task_not_found(),
+            },
+            (Method::Delete, ["task", id]) => match self.tasks.remove(*id) {
+                Some(_) => (204, json!({})),
+                None => task_not_found(),
+            },
+            (Method::Post, ["task", id, "comment"]) if self.tasks.contains_key(*id) => {
+                self.comments
+                    .entry(id.to_string())
+                    .or_default()
+                    .push(payload);
+                (200, json!({"id": "1", "date": 0}))
+            }
+            (Method::Post, ["task", _, "comment"]) => task_not_found(),
+            (Method::Post, ["task", id, "checklist"]) => {
+                self.next_id += 1;
+                let checklist = json!({"id": format!("checklist{}", self.next_id), "name": payload["name"], "items": []});
+                match self.tasks.get_mut(*id) {
+                    Some(task) => {
+                        task["checklists"]
+                            .as_array_mut()
+                            .unwrap()
+                            .push(checklist.clone());
+                        (200, json!({ "checklist": checklist }))
+                    }
+                    None => task_not_found(),
+                }
+            }
+            (Method::Post, ["checklist", checklist_id, "checklist_item"]) => {
+                self.next_id += 1;
+                let item = json!({"id": format!("item{}", self.next_id), "name": payload["name"], "resolved": false});
+                match self.checklist_mut(checklist_id) {
+                    Some(checklist) => {
+                        checklist["items"].as_array_mut().unwrap().push(item);
+                        (200, json!({ "checklist": checklist }))
+                    }
+                    None => (
+                        404,
+                        json!({"err": "Checklist not found", "ECODE": "CHECK_005"}),
+                    ),
+                }
+            }
+            (Method::Put, ["checklist", checklist_id, "checklist_item", item_id]) => {
+                let item = self.checklist_mut(checklist_id).and_then(|checklist| {
+                    checklist["items"]
+                        .as_array_mut()?
+                        .iter_mut()
+                        .find(|item| item["id"] == *item_id)
+                });
+                match item {
+                    Some(item) => {
+                        for (key, value) in payload.as_object().into_iter().flatten() {
+                            item[key] = value.clone();
+                        }
+                        (200, json!({}))
+                    }
+                    None => (
+                        404,
+                        json!({"err": "Checklist item not found", "ECODE": "CHECK_006"}),
+                    ),
+                }
+            }
+            (Method::Post, ["task", id, "tag", tag]) => match self.tasks.get_mut(*id) {
+                Some(task) => {
+                    let tag = percent_decode(tag);
+                    let tags = task["tags"].as_array_mut().unwrap();
+                    if !tags.iter().any(|existing| existing["name"] == tag) {
+                        tags.push(json!({"name": tag}));
+                    }
+                    (200, json!({}))
+                }
+                None => task_not_found(),
+            },
+            (Method::Delete, ["task", id, "tag", tag]) => match self.tasks.get_mut(*id) {
+                Some(task) => {
+                    let tag = percent_decode(tag);
+                    task["tags"]
+                        .as_array_mut()
+                        .unwrap()
+                        .retain(|existing| existing["name"] != tag);
+                    (200, json!({}))
+                }
+                None => task_not_found(),
+            },
+            // Moving tasks is only in the v3 API
+            (Method::Put, ["api", "v3", "workspaces", _, "tasks", id, "home_list", list_id]) => {
+                match self.tasks.get_mut(*id) {
+                    Some(task) => {
+                        task["list"] = json!({"id": list_id});
+                        (200, json!({}))
+                    }
+                    None => task_not_found(),
+                }
+            }
+            // Docs are only in the v3 API too
+            (Method::Post, ["api", "v3", "workspaces", _, "docs"]) => {
+                self.next_id += 1;
+                let id = format!("doc{}", self.next_id);
+                self.docs.push(json!({
+                    "id": id,
+                    "name": payload["name"],
+                    "parent": payload["parent"],
+                    "pages": [],
+                }));
+                (200, json!({ "id": id, "name": payload["name"] }))
+            }
+            (Method::Post, ["api", "v3", "workspaces", _, "docs", doc_id, "pages"]) => {
+                match self.docs.iter_mut().find(|doc| doc["id"] == *doc_id) {
+                    Some(doc) => {
+                        doc["pages"].as_array_mut().unwrap().push(payload.clone());
+                        (200, json!({ "id": format!("{}-page", doc_id) }))
+                    }
+                    None => (404, json!({"err": "Doc not found", "ECODE": "DOC_001"})),
+                }
+            }
+            (Method::Get, ["team"]) => (
+                200,
+                json!({"teams": [{
+                    "id": "1",
+                    "name": "Fake workspace",
+                    "members": self.members.iter().map(|user| json!({"user": user})).collect::<Vec<_>>(),
+                }]}),
+            ),
+            (Method::Get, ["team", _, "taskTemplate"]) => {
+                let templates = if query_param(url, "page") == Some("0") {
+                    self.task_templates
+                        .iter()
+                        .map(|(id, (name, _))| json!({"id": id, "name": name}))
+                        .collect()
+                } else {
+                    vec![]
+                };
+                (200, json!({ "templates": templates }))
+            }
+            (Method::Post, ["list", list_id, "taskTemplate", template_id]) => {
+                match self.task_templates.get(*template_id) {
+                    Some((_, task)) => {
+                        let mut task = task.clone();
+                        task["name"] = payload["name"].clone();
+                        let id = self.insert_task(list_id, task);
+                        (200, json!({"id": id, "task": self.tasks[&id]}))
+                    }
+                    None => (
+                        404,
+                        json!({"err": "Template not found", "ECODE": "TMPL_001"}),
+                    ),
+                }
+            }
+            (Method::Get, ["team", _, "space"]) => (
+                200,
+                json!({"spaces": self.spaces.iter().map(|space| json!({"id": space["id"], "name": space["name"]})).collect::<Vec<_>>()}),
+            ),
+            (Method::Get, ["space", id, "folder"]) => {
+                match self.spaces.iter().find(|space| space["id"] == *id) {
+                    Some(space) => (200, json!({"folders": space["folders"]})),
+                    None => (404, json!({"err": "Space not found", "ECODE": "PROJ_005"})),
+                }
+            }
+            (Method::Get, ["folder", id, "list"]) => match self.folder_mut(id) {
+                Some(folder) => (200, json!({"lists": folder["lists"]})),
+                None => (
+                    404,
+                    json!({"err": "Folder not found", "ECODE": "FOLDER_002"}),
+                ),
+            },
+            (Method::Post, [parent @ ("space" | "folder"), id, "list"]) => {
+                self.next_id += 1;
+                let list = json!({"id": format!("list{}", self.next_id), "name": payload["name"]});
+                let parent = if *parent == "space" {
+                    self.spaces.iter_mut().find(|space| space["id"] == *id)
+                } else {
+                    self.folder_mut(id)
+                };
+                match parent {
+                    Some(parent) => {
+                        parent["lists"].as_array_mut().unwrap().push(list.clone());
+                        (200, list)
+                    }
+                    None => (404, json!({"err": "Not found", "ECODE": "PROJ_005"})),
+                }
+            }
+            (Method::Get, ["space", id, "tag"]) => {
+                match self.spaces.iter().find(|space| space["id"] == *id) {
+                    Some(space) => (200, json!({"tags": space["tags"]})),
+                    None => (404, json!({"err": "Space not found", "ECODE": "PROJ_005"})),
+                }
+            }
+            (Method::Post, ["space", id, "tag"]) => {
+                let space = self.space_mut(id);
+                space["tags"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(payload["tag"].clone());
+                (200, json!({}))
+            }
+            (Method::Put, ["space", id, "tag", name]) => {
+                let name = percent_decode(name);
+                let space = self.space_mut(id);
+                match space["tags"]
+                    .as_array_mut()
+                    .unwrap()
+                    .iter_mut()
+                    .find(|tag| tag["name"] == name)
+                {
+                    Some(tag) => {
+                        *tag = json!({"name": payload["tag"]["name"], "tag_fg": payload["tag"]["fg_color"], "tag_bg": payload["tag"]["bg_color"]});
+                        (200, json!({}))
+                    }
+                    None => (404, json!({"err": "Tag not found", "ECODE": "TAGS_002"})),
+                }
+            }
+            (Method::Post, ["list", list_id, "field"]) => {
+                self.next_id += 1;
+                let mut field = payload.clone();
+                field["id"] = json!(format!("field{}", self.next_id));
+                if let Some(options) = field
+                    .pointer_mut("/type_config/options")
+                    .and_then(Value::as_array_mut)
+                {
+                    for (i, option) in options.iter_mut().enumerate() {
+                        option["id"] = json!(format!("option{}", i));
+                    }
+                }
+                self.custom_fields
+                    .entry(list_id.to_string())
+                    .or_default()
+                    .push(field.clone());
+                (200, json!({"field": field}))
+            }
+            (Method::Get, ["space", id, "list"]) => {
+                match self.spaces.iter().find(|space| space["id"] == *id) {
+                    Some(space) => (200, json!({"lists": space["lists"]})),
+                    None => (404, json!({"err": "Space not found", "ECODE": "PROJ_005"})),
+                }
+            }
+            (Method::Get, ["list", _, "member"]) => (200, json!({"members": self.members})),
+            (Method::Get, ["list", list_id]) => {
+                let statuses = self
+                    .list_statuses
+                    .get(*list_id)
+                    .cloned()
+                    .unwrap_or_else(|| vec!["to do".to_string(), "complete".to_string()]);
+                (
+                    200,
+                    json!({
+                        "id": list_id,
+                        "statuses": statuses.iter().map(|status| json!({"status": status, "type": "custom"})).collect::<Vec<_>>(),
+                    }),
+                )
+            }
+            (Method::Get, ["list", list_id, "field"]) => (
+                200,
+                json!({"fields": self.custom_fields.get(*list_id).cloned().unwrap_or_default()}),
+            ),
+            (Method::Post, ["task", id, "field", field_id]) => match self.tasks.get_mut(*id) {
+                Some(task) => {
+                    set_custom_field(task, field_id, Some(payload["value"].clone()));
+                    (200, json!({}))
+                }
+                None => task_not_found(),
+            },
+            (Method::Delete, ["task", id, "field", field_id]) => match self.tasks.get_mut(*id) {
+                Some(task) => {
+                    set_custom_field(task, field_id, None);
+                    (200, json!({}))
+                }
+                None => task_not_found(),
+            },
+            _ => (404, json!({"err": "Route not found", "ECODE": "APP_001"})),
+        };
+        (status, body, headers)
+    }
+
+    /// The subtasks of a task, in the order they were made
+    fn subtasks(&self, parent_id: &str) -> Vec<Value> {
+        let mut subtasks: Vec<Value> = self
+            .tasks
+            .values()
+            .filter(|task| task["parent"] == parent_id)
+            .cloned()
+            .collect();
+        subtasks.sort_by_key(|task| {
+            task["id"].as_str().unwrap_or_default()[4..]
+                .parse::<u64>()
+                .unwrap_or_default()
+        });
+        subtasks
+    }
+
+    /// The checklist with the given ID, on whichever task it's on
+    fn checklist_mut(&mut self, checklist_id: &str) -> Option<&mut Value> {
+        self.tasks
+            .values_mut()
+            .filter_map(|task| task.get_mut("checklists")?.as_array_mut())
+            .flatten()
+            .find(|checklist| checklist["id"] == checklist_id)
+    }
+
+    fn insert_task(&mut self, list_id: &str, payload: Value) -> String {
+        self.next_id += 1;
+        let id = format!("fake{}", self.next_id);
+        let mut task = json!({
+            "id": id,
+            "name": "",
+            "description": null,
+            "tags": [],
+            "start_date": null,
+            "due_date": null,
+            "list": {"id": list_id},
+            "custom_fields": [],
+            "assignees": [],
+            "checklists": [],
+            "status": {"status": "to do"},
+            "archived": false,
+            "team_id": "1",
+        });
+        merge_task_fields(&mut task, &payload);
+        self.tasks.insert(id.clone(), task);
+        id
+    }
+}
+
+/// Apply the fields of a create/update payload to a stored task, converting them into the shape ClickUp responds with
+/// (dates become strings of milliseconds, tags become objects)
+fn merge_task_fields(task: &mut Value, payload: &Value) {
+    let Some(payload) = payload.as_object() else {
+        return;
+    };
+    let task: &mut Map<String, Value> = task.as_object_mut().unwrap();
+    for (key, value) in payload {
+        let value = match key.as_str() {
+            "start_date" | "due_date" => match value {
+                Value::Number(millis) => Value::String(millis.to_string()),
+                other => other.clone(),
+            },
+            // Assignees are a list of IDs when creating, and {add, rem} when updating
+            "assignees" => {
+                let mut ids: Vec<Value> = match value {
+                    Value::Array(ids) => ids.clone(),
+                    _ => task["assignees"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|assignee| assignee["id"].clone())
+                        .filter(|id| !value["rem"].as_array().unwrap().contains(id))
+                        .collect(),
+                };
+                for id in value["add"].as_array().into_iter().flatten() {
+                    if !ids.contains(id) {
+                        ids.push(id.clone());
+                    }
+                }
+                Value::Array(ids.into_iter().map(|id| json!({"id": id})).collect())
+            }
+            "status" => json!({
+                "status": value,
+                "type": if value == "complete" { "closed" } else { "open" },
+            }),
+            "tags" => Value::Array(
+                value
+                    .as_array()
+                    .map(|tags| tags.iter().map(|tag| json!({"name": tag})).collect())
+                    .unwrap_or_default(),
+            ),
+            _ => value.clone(),
+        };
+        task.insert(key.clone(), value);
+    }
+}
+
+/// Set (or with None, clear) the value of a custom field on a stored task
+fn set_custom_field(task: &mut Value, field_id: &str, value: Option<Value>) {
+    let fields = task["custom_fields"].as_array_mut().unwrap();
+    fields.retain(|field| field["id"] != field_id);
+    if let Some(value) = value {
+        fields.push(json!({"id": field_id, "value": value}));
+    }
+}
+
+fn custom_field_value(task: &Value, field_id: &str) -> Option<Value> {
+    task["custom_fields"]
+        .as_array()?
+        .iter()
+        .find(|field| field["id"] == field_id)
+        .map(|field| field["value"].clone())
+}
+
+/// Undo the percent encoding of a path segment (e.g. %20 for spaces in tag names)
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], segment.get(i + 1..i + 3)) {
+            (b'%', Some(hex)) if u8::from_str_radix(hex, 16).is_ok() => {
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).unwrap()
+}
+
+/// The value of a query parameter in a URL, if given
+fn query_param<'a>(url: &'a str, name: &str) -> Option<&'a str> {
+    url.split_once('?')?
+        .1
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn task_not_found() -> (u16, Value) {
+    (
+        404,
+        json!({"err": "Task not found, deleted", "ECODE": "ITEM_013"}),
+    )
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Make an event to sync
+/// ### Arguments
+/// * `uid` - iCal UID of the event
+/// * `summary` - Title of the event
+/// * `start` / `end` - Start and end times, in the format 2024-10-01 18:00
+pub fn event(uid: &str, summary: &str, start: &str, end: &str) -> ParsedEvent {
+    ParsedEvent {
+        uid: uid.to_string(),
+        start_time: Some(NaiveDateTime::parse_from_str(start, "%Y-%m-%d %H:%M").unwrap()),
+        end_time: Some(NaiveDateTime::parse_from_str(end, "%Y-%m-%d %H:%M").unwrap()),
+        summary: summary.to_string(),
+        description: format!("Description of {}", summary),
+        ..Default::default()
+    }
+}
+
+/// Set up the sync from a config file, checking it against the fake server
+/// ### Arguments
+/// * `clickup_api` - Client for the fake server
+/// * `config` - The config file, as TOML
+pub fn context(clickup_api: &ClickUpApiInstance, config: &str) -> SyncContext {
+    SyncContext::load(clickup_api, &parse_config(config).unwrap(), None).unwrap()
+}
+
+/// Start a fake server, with a client for it and the sync set up from a config file
+/// ### Arguments
+/// * `config` - The config file, as TOML
+pub fn setup(config: &str) -> (FakeClickUp, ClickUpApiInstance, SyncContext) {
+    setup_with(config, |_| {})
+}
+
+/// Like `setup`, but first adds whatever the config refers to (custom fields, members, templates) to the fake server
+/// ### Arguments
+/// * `config` - The config file, as TOML
+/// * `prepare` - Fills in the fake server before the config is checked against it
+pub fn setup_with(
+    config: &str,
+    prepare: impl FnOnce(&FakeClickUp),
+) -> (FakeClickUp, ClickUpApiInstance, SyncContext) {
+    let fake = FakeClickUp::start();
+    prepare(&fake);
+    let api = fake.client();
+    let context = context(&api, config);
+    (fake, api, context)
+}
+
+/// A mapping from an event to a task, as if the sync had just created it without recording anything else
+pub fn mapping(clickup_id: &str, calendar_id: &str) -> CalendarMapping {
+    CalendarMapping {
+        mapping_id: 1,
+        clickup_id: clickup_id.to_string(),
+        calendar_id: calendar_id.to_string(),
+        synced_assignees: "[]".to_string(),
+        tombstone: None,
+        last_synced: None,
+        list_id: FAKE_LIST_ID.to_string(),
+        user_deleted: false,
+        meeting_doc: None,
+        fingerprint: None,
+    }
+}
+
+/// Keeps mappings in memory, so the whole sync can be run without a database
+#[derive(Default)]
+pub struct MemoryStore {
+    pub mappings: Vec<CalendarMapping>,
+}
+
+impl MappingStore for MemoryStore {
+    fn load(&mut self) -> Result<Vec<CalendarMapping>, Box<dyn Error>> {
+        Ok(self.mappings.clone())
+    }
+
+    fn insert(
+        &mut self,
+        calendar_id: &str,
+        list_id: &str,
+        clickup_id: &str,
+        synced_assignees: &BTreeSet<i64>,
+        last_synced: Option<&SyncedFields>,
+        fingerprint: Option<&str>,
+    ) -> Result<CalendarMapping, Box<dyn Error>> {
+        let mapping = CalendarMapping {
+            mapping_id: self.mappings.len() as i32 + 1,
+            synced_assignees: assignees_to_json(synced_assignees),
+            last_synced: last_synced.map(synced_fields_to_json),
+            fingerprint: fingerprint.map(str::to_string),
+            list_id: list_id.to_string(),
+            ..mapping(clickup_id, calendar_id)
+        };
+        self.mappings.push(mapping.clone());
+        Ok(mapping)
+    }
+
+    fn update(&mut self, mapping: &CalendarMapping) -> Result<(), Box<dyn Error>> {
+        for existing in &mut self.mappings {
+            if existing.mapping_id == mapping.mapping_id {
+                *existing = mapping.clone();
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, mappings: &[CalendarMapping]) -> Result<(), Box<dyn Error>> {
+        self.mappings.retain(|existing| {
+            !mappings
+                .iter()
+                .any(|mapping| mapping.mapping_id == existing.mapping_id)
+        });
+        Ok(())
+    }
+}
+
+/// Numbers the temp databases made by this test binary, so tests running at once each get their own
+static NEXT_DATABASE: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// A database with the migrations run, which is thrown away when dropped: with SQLite a temp file, and with Postgres
+/// a schema of its own in the database at DATABASE_URL
+pub struct TempDatabase {
+    pub pool: clickup_ical_sync::db::DbPool,
+    #[cfg(feature = "sqlite")]
+    path: std::path::PathBuf,
+    #[cfg(not(feature = "sqlite"))]
+    database_url: String,
+    #[cfg(not(feature = "sqlite"))]
+    schema: String,
+}
+
+impl TempDatabase {
+    /// Make a new database to test against
+    /// ### Returns
+    /// The database, or None with Postgres if DATABASE_URL isn't set, in which case the test should be skipped
+    #[cfg(feature = "sqlite")]
+    pub fn new() -> Option<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "clickup-ical-sync-test-{}-{}.db",
+            std::process::id(),
+            NEXT_DATABASE.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+        ));
+        // Left over from an earlier test binary with the same process ID
+        let _ = std::fs::remove_file(&path);
+        let pool = Self::migrated_pool(path.to_str().unwrap());
+        Some(Self { pool, path })
+    }
+
+    /// Make a new database to test against
+    /// ### Returns
+    /// The database, or None with Postgres if DATABASE_URL isn't set, in which case the test should be skipped
+    #[cfg(not(feature = "sqlite"))]
+    pub fn new() -> Option<Self> {
+        use diesel::{connection::SimpleConnection, Connection};
+
+        let Ok(database_url) = std::env::var("DATABASE_URL") else {
+            eprintln!("DATABASE_URL isn't set, skipping the test against Postgres");
+            return None;
+        };
+        let schema = format!(
+            "clickup_ical_sync_test_{}_{}",
+            std::process::id(),
+            NEXT_DATABASE.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+        );
+        clickup_ical_sync::db::DbConnection::establish(&database_url)
+            .expect("Failed to connect to DATABASE_URL")
+            // Left over from an earlier test binary with the same process ID
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}",
+                schema
+            ))
+            .expect("Failed to make a schema for the test");
+
+        // Every connection only sees the test's schema, so the tables are made (and read) there
+        let separator = if database_url.contains('?') { '&' } else { '?' };
+        let pool = Self::migrated_pool(&format!(
+            "{}{}options=-csearch_path%3D{}",
+            database_url, separator, schema
+        ));
+        Some(Self {
+            pool,
+            database_url,
+            schema,
+        })
+    }
+
+    /// Connect to a database and run the migrations on it
+    fn migrated_pool(database_url: &str) -> clickup_ical_sync::db::DbPool {
+        let pool = clickup_ical_sync::db::connection_pool_for(database_url, 4)
+            .expect("Failed to open database");
+        clickup_ical_sync::db::run_migrations(&mut pool.get().unwrap())
+            .expect("Failed to run migrations");
+        pool
+    }
+
+    /// A connection to the database, for checking what's in it
+    pub fn connection(
+        &self,
+    ) -> diesel::r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<clickup_ical_sync::db::DbConnection>,
+    > {
+        self.pool.get().expect("Failed to connect to database")
+    }
+}
+
+impl Drop for TempDatabase {
+    #[cfg(feature = "sqlite")]
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    fn drop(&mut self) {
+        use diesel::{connection::SimpleConnection, Connection};
+
+        if let Ok(mut connection) =
+            clickup_ical_sync::db::DbConnection::establish(&self.database_url)
+        {
+            let _ = connection.batch_execute(&format!("DROP SCHEMA {} CASCADE", self.schema));
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Tests of filling in custom fields from events
+mod common;
+
+use clickup_ical_sync::{config::parse_config, sync::SyncContext};
+use common::{event, mapping, setup_with, FakeClickUp, FAKE_LIST_ID};
+use serde_json::json;
+
+/// Set up the fields on our committee's calendar list
+fn add_fields(fake: &FakeClickUp) {
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-loc", "name": "Location", "type": "short_text"}),
+    );
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-link", "name": "Sign-up Link", "type": "url"}),
+    );
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-type", "name": "Event Type", "type": "drop_down", "type_config": {
+            "options": [{"id": "opt-social", "name": "Social"}, {"id": "opt-talk", "name": "Talk"}]
+        }}),
+    );
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-teams", "name": "Teams", "type": "labels", "type_config": {
+            "options": [{"id": "lab-ents", "label": "Events"}, {"id": "lab-spons", "label": "Sponsorship"}]
+        }}),
+    );
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-start", "name": "Starts", "type": "date"}),
+    );
+}
+
+const CONFIG: &str = r#"
+[[custom_fields]]
+field = "Location"
+value = "{location}"
+
+[[custom_fields]]
+field = "f-link"
+value = "{url}"
+
+[[custom_fields]]
+field = "event type"
+value = "{categories}"
+
+[[custom_fields]]
+field = "Teams"
+value = "Events, Sponsorship"
+
+[[custom_fields]]
+field = "Starts"
+value = "{start}"
+"#;
+
+#[test]
+fn custom_fields_are_converted_and_set_on_create() {
+    let (fake, api, context) = setup_with(CONFIG, add_fields);
+
+    let mut talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    talk.location = Some("Huxley 308".to_string());
+    talk.url = Some("https://example.com/signup".to_string());
+    talk.categories = vec!["talk".to_string()];
+    let id = context.create_task(&api, &talk).unwrap();
+
+    assert_eq!(
+        fake.custom_field_value(&id, "f-loc"),
+        Some(json!("Huxley 308"))
+    );
+    assert_eq!(
+        fake.custom_field_value(&id, "f-link"),
+        Some(json!("https://example.com/signup"))
+    );
+    assert_eq!(
+        fake.custom_field_value(&id, "f-type"),
+        Some(json!("opt-talk"))
+    );
+    assert_eq!(
+        fake.custom_field_value(&id, "f-teams"),
+        Some(json!(["lab-ents", "lab-spons"]))
+    );
+    assert_eq!(
+        fake.custom_field_value(&id, "f-start"),
+        Some(json!(1727805600000i64))
+    );
+}
+
+#[test]
+fn custom_fields_are_kept_up_to_date_on_update() {
+    let (fake, api, context) = setup_with(CONFIG, add_fields);
+
+    let mut talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    talk.location = Some("Huxley 308".to_string());
+    talk.url = Some("https://example.com/signup".to_string());
+    let id = context.create_task(&api, &talk).unwrap();
+
+    // Room changes & the sign up link is removed
+    talk.location = Some("Huxley 311".to_string());
+    talk.url = None;
+    context
+        .update_task(&api, &mapping(&id, "uid-1"), &talk)
+        .unwrap();
+
+    assert_eq!(
+        fake.custom_field_value(&id, "f-loc"),
+        Some(json!("Huxley 311"))
+    );
+    assert_eq!(fake.custom_field_value(&id, "f-link"), None);
+}
+
+#[test]
+fn unknown_custom_field_is_an_error() {
+    let fake = FakeClickUp::start();
+    add_fields(&fake);
+    let api = fake.client();
+
+    let config = parse_config(
+        r#"
+        [[custom_fields]]
+        field = "Organiser"
+        value = "{organizer}"
+        "#,
+    )
+    .unwrap();
+
+    assert!(SyncContext::load(&api, &config, None).is_err());
+}
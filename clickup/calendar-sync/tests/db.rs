@@ -0,0 +1,395 @@
+//! Tests of keeping mappings, the audit log & the rate limit in the database
+//!
+//! With SQLite they run against a database in a temp file. Postgres needs a server, so they only run if DATABASE_URL
+//! is set (e.g. `DATABASE_URL=postgres://localhost/test cargo test`), each in a schema of its own that's dropped after.
+mod common;
+
+use std::collections::BTreeSet;
+
+use clickup_ical_sync::{
+    audit::{AuditEntry, AuditOperation, TaskSnapshot},
+    db::{
+        load_audit_records, mark_audit_records_undone, start_sync_run, tasks_changed_after,
+        DbRateLimit, DbStore, BATCH_SIZE, MIGRATIONS,
+    },
+    models::{AuditRecord, CalendarMapping, SyncRun},
+    rate_limit::SharedBucket,
+    schema::{
+        clickup_ical_mapping, clickup_oauth_token, clickup_rate_limit, sync_audit, sync_runs,
+    },
+    store::MappingStore,
+};
+use common::{TempDatabase, FAKE_LIST_ID};
+use diesel::prelude::*;
+use diesel_migrations::MigrationHarness;
+
+/// The mappings in the database, by event UID
+fn saved_mappings(db: &TempDatabase) -> Vec<CalendarMapping> {
+    clickup_ical_mapping::table
+        .order(clickup_ical_mapping::calendar_id)
+        .load(&mut db.connection())
+        .unwrap()
+}
+
+#[test]
+fn migrations_match_the_schema_and_revert() {
+    let Some(db) = TempDatabase::new() else {
+        return;
+    };
+    let mut connection = db.connection();
+
+    // Every table can be read into its model, so the migrations make the columns src/schema.rs expects
+    assert!(clickup_ical_mapping::table
+        .load::<CalendarMapping>(&mut connection)
+        .unwrap()
+        .is_empty());
+    assert!(sync_runs::table
+        .select(SyncRun::as_select())
+        .load(&mut connection)
+        .unwrap()
+        .is_empty());
+    assert!(sync_audit::table
+        .select(AuditRecord::as_select())
+        .load(&mut connection)
+        .unwrap()
+        .is_empty());
+    assert!(clickup_oauth_token::table
+        .select((
+            clickup_oauth_token::token_id,
+            clickup_oauth_token::encrypted_token,
+            clickup_oauth_token::created_at,
+        ))
+        .load::<(i32, String, chrono::NaiveDateTime)>(&mut connection)
+        .unwrap()
+        .is_empty());
+    assert!(clickup_rate_limit::table
+        .load::<(String, f64, i64)>(&mut connection)
+        .unwrap()
+        .is_empty());
+
+    connection.revert_all_migrations(MIGRATIONS).unwrap();
+    assert!(connection.has_pending_migration(MIGRATIONS).unwrap());
+    connection.run_pending_migrations(MIGRATIONS).unwrap();
+}
+
+#[test]
+fn mappings_are_inserted_updated_and_deleted() {
+    let Some(db) = TempDatabase::new() else {
+        return;
+    };
+    let mut store = DbStore::new(db.pool.clone(), FAKE_LIST_ID);
+    let assignees = BTreeSet::from([1, 2]);
+    let talk = store
+        .insert(
+            "uid-1",
+            FAKE_LIST_ID,
+            "task-1",
+            &assignees,
+            None,
+            Some("fp-1"),
+        )
+        .unwrap();
+    let social = store
+        .insert(
+            "uid-2",
+            FAKE_LIST_ID,
+            "task-2",
+            &BTreeSet::new(),
+            None,
+            None,
+        )
+        .unwrap();
+    // Nothing is written until the changes are flushed
+    assert!(saved_mappings(&db).is_empty());
+    store.flush().unwrap();
+
+    let saved = saved_mappings(&db);
+    assert_eq!(saved.len(), 2);
+    assert_eq!(saved[0].clickup_id, "task-1");
+    assert_eq!(saved[0].synced_assignees, "[1,2]");
+    assert_eq!(saved[0].fingerprint.as_deref(), Some("fp-1"));
+
+    // Saved again by event UID & list ID, so this updates the row rather than adding one
+    let talk = CalendarMapping {
+        clickup_id: "task-3".to_string(),
+        tombstone: Some("\"Delete\"".to_string()),
+        user_deleted: true,
+        meeting_doc: Some("doc-1".to_string()),
+        fingerprint: None,
+        ..talk
+    };
+    store.update(&talk).unwrap();
+    store.delete(&[social]).unwrap();
+    store.flush().unwrap();
+
+    let saved = DbStore::new(db.pool.clone(), FAKE_LIST_ID).load().unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].calendar_id, "uid-1");
+    assert_eq!(saved[0].clickup_id, "task-3");
+    assert_eq!(saved[0].tombstone, talk.tombstone);
+    assert!(saved[0].user_deleted);
+    assert_eq!(saved[0].meeting_doc.as_deref(), Some("doc-1"));
+    assert_eq!(saved[0].fingerprint, None);
+}
+
+#[test]
+fn mappings_from_before_lists_are_given_the_target_list() {
+    let Some(db) = TempDatabase::new() else {
+        return;
+    };
+    diesel::insert_into(clickup_ical_mapping::table)
+        .values((
+            clickup_ical_mapping::calendar_id.eq("uid-1"),
+            clickup_ical_mapping::clickup_id.eq("task-1"),
+        ))
+        .execute(&mut db.connection())
+        .unwrap();
+
+    let mappings = DbStore::new(db.pool.clone(), FAKE_LIST_ID).load().unwrap();
+    assert_eq!(mappings[0].list_id, FAKE_LIST_ID);
+}
+
+#[test]
+fn changes_are_saved_in_batches_and_when_the_store_is_dropped() {
+    let Some(db) = TempDatabase::new() else {
+        return;
+    };
+    let mut store = DbStore::new(db.pool.clone(), FAKE_LIST_ID);
+    let mut insert = |n: usize| {
+        store
+            .insert(
+                &format!("uid-{}", n),
+                FAKE_LIST_ID,
+                &format!("task-{}", n),
+                &BTreeSet::new(),
+                None,
+                None,
+            )
+            .unwrap();
+    };
+
+    for n in 1..BATCH_SIZE {
+        insert(n);
+    }
+    assert!(saved_mappings(&db).is_empty());
+    // A full batch is saved without waiting for a flush
+    insert(BATCH_SIZE);
+    assert_eq!(saved_mappings(&db).len(), BATCH_SIZE);
+    insert(BATCH_SIZE + 1);
+    assert_eq!(saved_mappings(&db).len(), BATCH_SIZE);
+
+    drop(store);
+    assert_eq!(saved_mappings(&db).len(), BATCH_SIZE + 1);
+}
+
+#[test]
+fn a_failed_flush_saves_nothing_from_the_batch() {
+    let Some(db) = TempDatabase::new() else {
+        return;
+    };
+    let mut store = DbStore::new(db.pool.clone(), FAKE_LIST_ID);
+    store
+        .insert(
+            "uid-1",
+            FAKE_LIST_ID,
+            "task-1",
+            &BTreeSet::new(),
+            None,
+            None,
+        )
+        .unwrap();
+    let social = store
+        .insert(
+            "uid-2",
+            FAKE_LIST_ID,
+            "task-2",
+            &BTreeSet::new(),
+            None,
+            None,
+        )
+        .unwrap();
+    store.flush().unwrap();
+
+    store.set_run(start_sync_run(&mut db.connection()).unwrap());
+    store.delete(&[social]).unwrap();
+    store
+        .insert(
+            "uid-3",
+            FAKE_LIST_ID,
+            "task-3",
+            &BTreeSet::new(),
+            None,
+            None,
+        )
+        .unwrap();
+    store
+        .record(AuditEntry::new(
+            "uid-3",
+            FAKE_LIST_ID,
+            "task-3",
+            AuditOperation::Create {
+                after: TaskSnapshot::default(),
+            },
+        ))
+        .unwrap();
+    // Task IDs are unique, so this fails when the batch is saved
+    store
+        .insert(
+            "uid-4",
+            FAKE_LIST_ID,
+            "task-1",
+            &BTreeSet::new(),
+            None,
+            None,
+        )
+        .unwrap();
+    assert!(store.flush().is_err());
+
+    // The delete, the other insert & the audit log entry were all rolled back with it
+    let saved = saved_mappings(&db);
+    assert_eq!(
+        saved
+            .iter()
+            .map(|mapping| mapping.calendar_id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["uid-1", "uid-2"]
+    );
+    assert_eq!(
+        sync_audit::table
+            .count()
+            .get_result::<i64>(&mut db.connection())
+            .unwrap(),
+        0
+    );
+}
+
+/// Record a change to a task in the audit log against the given run
+fn record(store: &mut DbStore, run: i32, task_id: &str) {
+    store.set_run(run);
+    store
+        .record(AuditEntry::new(
+            "uid-1",
+            FAKE_LIST_ID,
+            task_id,
+            AuditOperation::Create {
+                after: TaskSnapshot::default(),
+            },
+        ))
+        .unwrap();
+    store.flush().unwrap();
+}
+
+#[test]
+fn audit_log_is_loaded_by_run_and_marked_undone() {
+    let Some(db) = TempDatabase::new() else {
+        return;
+    };
+    let mut connection = db.connection();
+    let mut store = DbStore::new(db.pool.clone(), FAKE_LIST_ID);
+    let first = start_sync_run(&mut connection).unwrap();
+    let second = start_sync_run(&mut connection).unwrap();
+    record(&mut store, first, "task-1");
+    record(&mut store, first, "task-2");
+    record(&mut store, second, "task-2");
+    record(&mut store, second, "task-3");
+    record(&mut store, second, "task-3");
+
+    // Only the run's own changes, oldest first
+    let records = load_audit_records(&mut connection, first).unwrap();
+    assert_eq!(
+        records
+            .iter()
+            .map(|record| record.task_id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["task-1", "task-2"]
+    );
+    assert!(records[0].audit_id < records[1].audit_id);
+    assert!(records.iter().all(|record| record.run_id == first));
+    assert_eq!(
+        tasks_changed_after(&mut connection, first).unwrap(),
+        ["task-2".to_string(), "task-3".to_string()].into()
+    );
+    assert!(tasks_changed_after(&mut connection, second)
+        .unwrap()
+        .is_empty());
+
+    // Once undone, changes are neither undone again nor stop earlier runs' changes to the same task being undone
+    let later = load_audit_records(&mut connection, second).unwrap();
+    mark_audit_records_undone(&mut connection, &[later[0].audit_id]).unwrap();
+    assert_eq!(
+        tasks_changed_after(&mut connection, first).unwrap(),
+        ["task-3".to_string()].into()
+    );
+    assert_eq!(
+        load_audit_records(&mut connection, second)
+            .unwrap()
+            .iter()
+            .map(|record| record.audit_id)
+            .collect::<Vec<_>>(),
+        vec![later[1].audit_id, later[2].audit_id]
+    );
+}
+
+#[test]
+fn changes_are_marked_undone_when_the_store_is_flushed() {
+    let Some(db) = TempDatabase::new() else {
+        return;
+    };
+    let mut store = DbStore::new(db.pool.clone(), FAKE_LIST_ID);
+    let run = start_sync_run(&mut db.connection()).unwrap();
+    record(&mut store, run, "task-1");
+    let audit_id = load_audit_records(&mut db.connection(), run).unwrap()[0].audit_id;
+
+    store.mark_undone(audit_id).unwrap();
+    assert_eq!(
+        load_audit_records(&mut db.connection(), run).unwrap().len(),
+        1
+    );
+    store.flush().unwrap();
+    assert!(load_audit_records(&mut db.connection(), run)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn rate_limit_is_shared_through_the_database() {
+    let Some(db) = TempDatabase::new() else {
+        return;
+    };
+    let mut first = DbRateLimit::new(db.pool.clone());
+    let mut second = DbRateLimit::new(db.pool.clone());
+
+    // A bucket of 2 requests a minute, shared by both
+    assert_eq!(first.try_take("token", 2).unwrap(), None);
+    assert_eq!(second.try_take("token", 2).unwrap(), None);
+    let wait = first
+        .try_take("token", 2)
+        .unwrap()
+        .expect("The bucket should be empty");
+    assert!(wait.as_secs() > 0 && wait.as_secs() <= 30);
+    // Other tokens have their own bucket
+    assert_eq!(second.try_take("other", 2).unwrap(), None);
+}
+
+#[test]
+fn rate_limit_is_taken_once_per_token_by_processes_at_once() {
+    let Some(db) = TempDatabase::new() else {
+        return;
+    };
+
+    // Each thread stands in for another process, all reading & writing the bucket at the same time
+    let waits = std::thread::scope(|scope| {
+        let takers: Vec<_> = (0..8)
+            .map(|_| {
+                let mut bucket = DbRateLimit::new(db.pool.clone());
+                scope.spawn(move || bucket.try_take("token", 5).unwrap())
+            })
+            .collect();
+        takers
+            .into_iter()
+            .map(|taker| taker.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    // Postgres locks the bucket while it's read (and SQLite the whole database), so none of the 5 tokens is taken twice
+    assert_eq!(waits.iter().filter(|wait| wait.is_none()).count(), 5);
+}
@@ -0,0 +1,111 @@
+//! Tests of what happens to tasks when their events disappear from the calendar
+mod common;
+
+use clickup_ical_sync::{
+    clickup::ClickUpApiInstance,
+    config::{parse_config, DeletionPolicy},
+    sync::SyncContext,
+};
+use common::{event, setup, setup_with, FakeClickUp, FAKE_LIST_ID};
+use serde_json::json;
+
+/// Create a task with the given deletion policy, then retire it
+/// ### Returns
+/// The fake server, client, task ID & the tombstone returned by `retire_task`
+fn create_and_retire(
+    config: &str,
+) -> (
+    FakeClickUp,
+    ClickUpApiInstance,
+    String,
+    Option<DeletionPolicy>,
+) {
+    let (fake, api, context) = setup_with(config, |fake| {
+        fake.set_list_statuses(FAKE_LIST_ID, &["to do", "Cancelled", "complete"]);
+    });
+
+    let id = context
+        .create_task(
+            &api,
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .unwrap();
+    let tombstone = context
+        .retire_task(&api, &id)
+        .expect("Failed to retire task");
+    (fake, api, id, tombstone)
+}
+
+#[test]
+fn delete_policy_deletes_task_and_drops_mapping() {
+    let (fake, _, id, tombstone) = create_and_retire("");
+
+    assert!(fake.task(&id).is_none());
+    assert_eq!(tombstone, None);
+}
+
+#[test]
+fn archive_policy_archives_and_restores() {
+    let (fake, api, id, tombstone) = create_and_retire("[deletion]\npolicy = \"archive\"");
+    assert_eq!(fake.task(&id).unwrap()["archived"], true);
+
+    api.restore_task(
+        &id,
+        FAKE_LIST_ID,
+        &tombstone.expect("Mapping should be tombstoned"),
+    )
+    .unwrap();
+    assert_eq!(fake.task(&id).unwrap()["archived"], false);
+}
+
+#[test]
+fn status_policy_sets_status_and_restores_open_status() {
+    let (fake, api, id, tombstone) =
+        create_and_retire("[deletion]\npolicy = \"status\"\nstatus = \"cancelled\"");
+    assert_eq!(fake.task(&id).unwrap()["status"]["status"], "Cancelled");
+
+    api.restore_task(&id, FAKE_LIST_ID, &tombstone.unwrap())
+        .unwrap();
+    assert_eq!(fake.task(&id).unwrap()["status"]["status"], "to do");
+}
+
+#[test]
+fn tag_policy_tags_and_restores() {
+    let (fake, api, id, tombstone) =
+        create_and_retire("[deletion]\npolicy = \"tag\"\ntag = \"event cancelled\"");
+    assert_eq!(
+        fake.task(&id).unwrap()["tags"],
+        json!([{"name": "event cancelled"}])
+    );
+
+    api.restore_task(&id, FAKE_LIST_ID, &tombstone.unwrap())
+        .unwrap();
+    assert_eq!(fake.task(&id).unwrap()["tags"], json!([]));
+}
+
+#[test]
+fn move_policy_moves_to_graveyard_and_back() {
+    let (fake, api, id, tombstone) =
+        create_and_retire("[deletion]\npolicy = \"move\"\nlist_id = \"666\"");
+    assert_eq!(fake.task(&id).unwrap()["list"]["id"], "666");
+
+    api.restore_task(&id, FAKE_LIST_ID, &tombstone.unwrap())
+        .unwrap();
+    assert_eq!(fake.task(&id).unwrap()["list"]["id"], FAKE_LIST_ID);
+}
+
+#[test]
+fn task_deleted_by_hand_drops_mapping() {
+    let (_fake, api, context) = setup("[deletion]\npolicy = \"archive\"");
+
+    assert_eq!(context.retire_task(&api, "gone").unwrap(), None);
+}
+
+#[test]
+fn unknown_cancelled_status_is_an_error() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let config = parse_config("[deletion]\npolicy = \"status\"\nstatus = \"cancelled\"").unwrap();
+
+    assert!(SyncContext::load(&api, &config, None).is_err());
+}
@@ -0,0 +1,88 @@
+//! Tests of keeping the parts of the description people write in ClickUp
+mod common;
+
+use clickup_ical_sync::{
+    models::{synced_fields_to_json, CalendarMapping},
+    sync::SyncContext,
+};
+use common::{context, event, mapping, setup, FakeClickUp, FAKE_LIST_ID};
+use serde_json::json;
+
+const CONFIG: &str = r#"
+[description]
+managed_block = true
+
+[ownership]
+detect_drift = true
+"#;
+
+#[test]
+fn description_is_written_between_markers_and_notes_are_kept() {
+    let (fake, api, context) = setup(CONFIG);
+
+    let mut talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    let id = context.create_task(&api, &talk).unwrap();
+    assert_eq!(
+        fake.task(&id).unwrap()["description"],
+        "--- synced from calendar ---\nDescription of Talk\n--- end of synced section ---"
+    );
+
+    // Someone adds notes around the synced section
+    fake.edit_task(&id, json!({"description": "Room booked!\n--- synced from calendar ---\nDescription of Talk\n--- end of synced section ---\nBring snacks"}));
+
+    talk.description = "Now with pizza".to_string();
+    let mapping = CalendarMapping {
+        last_synced: Some(synced_fields_to_json(&context.synced_fields_for(&event(
+            "uid-1",
+            "Talk",
+            "2024-10-01 18:00",
+            "2024-10-01 19:00",
+        )))),
+        ..mapping(&id, "uid-1")
+    };
+    let synced = context.update_task(&api, &mapping, &talk).unwrap();
+
+    // Notes aren't drift, so the synced section is still updated
+    assert_eq!(
+        fake.task(&id).unwrap()["description"],
+        "Room booked!\n--- synced from calendar ---\nNow with pizza\n--- end of synced section ---\nBring snacks"
+    );
+    assert_eq!(synced.description, "Now with pizza");
+}
+
+#[test]
+fn description_without_markers_is_replaced() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    let id = SyncContext::new(FAKE_LIST_ID)
+        .create_task(&api, &talk)
+        .unwrap();
+
+    // Turned on after the task was created
+    context(&api, CONFIG)
+        .update_task(&api, &mapping(&id, "uid-1"), &talk)
+        .unwrap();
+
+    assert_eq!(
+        fake.task(&id).unwrap()["description"],
+        "--- synced from calendar ---\nDescription of Talk\n--- end of synced section ---"
+    );
+}
+
+#[test]
+fn description_is_written_whole_by_default() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let id = SyncContext::new(FAKE_LIST_ID)
+        .create_task(
+            &api,
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .unwrap();
+
+    assert_eq!(
+        fake.task(&id).unwrap()["description"],
+        "Description of Talk"
+    );
+}
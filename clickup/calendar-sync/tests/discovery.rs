@@ -0,0 +1,75 @@
+//! Tests of browsing the workspace to find list IDs etc.
+mod common;
+
+use clickup_ical_sync::discovery::{discover, render_tree};
+use common::{FakeClickUp, FAKE_LIST_ID};
+use serde_json::json;
+
+/// A workspace with a list in a folder and a list directly in the space
+fn setup() -> FakeClickUp {
+    let fake = FakeClickUp::start();
+    fake.add_member(1, "chair", "chair@docsoc.co.uk");
+    fake.add_space("s1", "Committee");
+    fake.add_folder("s1", "f1", "Events");
+    fake.add_list("s1", Some("f1"), FAKE_LIST_ID, "Calendar");
+    fake.add_list("s1", None, "902", "Sponsorship");
+    fake.set_list_statuses(FAKE_LIST_ID, &["to do", "upcoming", "complete"]);
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-loc", "name": "Location", "type": "short_text"}),
+    );
+    fake
+}
+
+#[test]
+fn discovery_finds_every_list_with_its_details() {
+    let fake = setup();
+    let workspaces = discover(&fake.client()).expect("Failed to browse ClickUp");
+
+    assert_eq!(workspaces.len(), 1);
+    let space = &workspaces[0].spaces[0];
+    assert_eq!(space.name, "Committee");
+    let calendar = &space.folders[0].lists[0];
+    assert_eq!(calendar.id, FAKE_LIST_ID);
+    assert_eq!(
+        calendar
+            .statuses
+            .iter()
+            .map(|status| status.status.as_str())
+            .collect::<Vec<_>>(),
+        vec!["to do", "upcoming", "complete"]
+    );
+    assert_eq!(calendar.custom_fields[0].id, "f-loc");
+    assert_eq!(calendar.members[0].id, 1);
+    assert_eq!(space.lists[0].name, "Sponsorship");
+}
+
+#[test]
+fn discovery_renders_as_a_tree_and_json() {
+    let fake = setup();
+    let workspaces = discover(&fake.client()).unwrap();
+
+    assert_eq!(
+        render_tree(&workspaces),
+        "Workspace Fake workspace [1]
+  Space Committee [s1]
+    Folder Events [f1]
+      List Calendar [901]
+        Statuses: to do, upcoming, complete
+        Custom fields:
+          Location (short_text) [f-loc]
+        Members:
+          chair <chair@docsoc.co.uk> [1]
+    List Sponsorship [902]
+      Statuses: to do, complete
+      Members:
+        chair <chair@docsoc.co.uk> [1]
+"
+    );
+
+    let json = serde_json::to_value(&workspaces).unwrap();
+    assert_eq!(
+        json[0]["spaces"][0]["folders"][0]["lists"][0]["custom_fields"][0]["type"],
+        "short_text"
+    );
+}
@@ -0,0 +1,394 @@
+//! Tests of what's recorded about each sync run, and the status command's view of it
+mod common;
+
+use std::{collections::BTreeSet, error::Error};
+
+use chrono::{Duration, NaiveDateTime};
+use clickup_ical_sync::{
+    history::{format_duration, outcome, render_runs, staleness, RunOutcome},
+    models::{CalendarMapping, SyncRun},
+    ownership::SyncedFields,
+    store::MappingStore,
+    sync::{sync_events, SyncContext, SyncStats},
+};
+use common::{event, FakeClickUp, MemoryStore, FAKE_LIST_ID};
+use serde_json::json;
+
+fn time(time: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+/// A run that started at the given time, and took a minute unless it's unfinished
+fn run(run_id: i32, started_at: &str, finished: bool, error: Option<&str>) -> SyncRun {
+    let started_at = time(started_at);
+    SyncRun {
+        run_id,
+        started_at,
+        finished_at: finished.then(|| started_at + Duration::minutes(1)),
+        source: Some("DoCSoc Private".to_string()),
+        created: 1,
+        updated: 2,
+        deleted: 0,
+        skipped: 40,
+        failed: 0,
+        requests: 3,
+        error: error.map(str::to_string),
+    }
+}
+
+/// Keeps mappings in memory, but fails every time they're saved
+#[derive(Default)]
+struct FailingStore {
+    inner: MemoryStore,
+}
+
+impl MappingStore for FailingStore {
+    fn load(&mut self) -> Result<Vec<CalendarMapping>, Box<dyn Error>> {
+        self.inner.load()
+    }
+
+    fn insert(
+        &mut self,
+        calendar_id: &str,
+        list_id: &str,
+        clickup_id: &str,
+        synced_assignees: &BTreeSet<i64>,
+        last_synced: Option<&SyncedFields>,
+        fingerprint: Option<&str>,
+    ) -> Result<CalendarMapping, Box<dyn Error>> {
+        self.inner.insert(
+            calendar_id,
+            list_id,
+            clickup_id,
+            synced_assignees,
+            last_synced,
+            fingerprint,
+        )
+    }
+
+    fn update(&mut self, mapping: &CalendarMapping) -> Result<(), Box<dyn Error>> {
+        self.inner.update(mapping)
+    }
+
+    fn delete(&mut self, mappings: &[CalendarMapping]) -> Result<(), Box<dyn Error>> {
+        self.inner.delete(mappings)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        Err("Database is locked".into())
+    }
+}
+
+#[test]
+fn sync_counts_what_it_did() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    let mut store = MemoryStore::default();
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    let social = event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00");
+    let quiz = event("uid-3", "Quiz", "2024-10-03 18:00", "2024-10-03 20:00");
+
+    fake.fail_next(500, json!({ "err": "Internal error" }));
+    let stats = sync_events(
+        &api,
+        &context,
+        &mut store,
+        vec![talk.clone(), social.clone(), quiz.clone()],
+    )
+    .unwrap();
+    assert_eq!(
+        stats,
+        SyncStats {
+            created: 2,
+            failed: 1,
+            requests: fake.requests().len() as u32,
+            ..Default::default()
+        }
+    );
+
+    // The failed event is tried again, the quiz is cancelled, and the social moves
+    let mut moved_social = social;
+    moved_social.end_time = moved_social
+        .end_time
+        .map(|end| end + chrono::Duration::hours(1));
+    let stats = sync_events(
+        &api,
+        &context,
+        &mut store,
+        vec![talk.clone(), moved_social.clone()],
+    )
+    .unwrap();
+    assert_eq!(
+        (
+            stats.created,
+            stats.updated,
+            stats.deleted,
+            stats.skipped,
+            stats.failed
+        ),
+        (1, 1, 1, 0, 0)
+    );
+
+    let stats = sync_events(&api, &context, &mut store, vec![talk, moved_social]).unwrap();
+    assert_eq!(
+        (
+            stats.created,
+            stats.updated,
+            stats.deleted,
+            stats.skipped,
+            stats.failed
+        ),
+        (0, 0, 0, 2, 0)
+    );
+}
+
+#[test]
+fn failed_sync_still_counts_what_it_did() {
+    let fake = FakeClickUp::start();
+    let mut store = FailingStore::default();
+    let events = vec![
+        event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00"),
+    ];
+
+    // The tasks are made before the mappings fail to save
+    let (stats, err) = sync_events(
+        &fake.client(),
+        &SyncContext::new(FAKE_LIST_ID),
+        &mut store,
+        events,
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "Database is locked");
+    assert_eq!(
+        stats,
+        SyncStats {
+            created: 2,
+            requests: 2,
+            ..Default::default()
+        }
+    );
+    assert_eq!(fake.tasks().len(), 2);
+}
+
+#[test]
+fn outcome_comes_from_finish_time_and_error() {
+    assert_eq!(
+        outcome(&run(1, "2024-10-01 10:00:00", false, None)),
+        RunOutcome::Unfinished
+    );
+    assert_eq!(
+        outcome(&run(1, "2024-10-01 10:00:00", true, None)),
+        RunOutcome::Succeeded
+    );
+    assert_eq!(
+        outcome(&run(1, "2024-10-01 10:00:00", true, Some("No calendar"))),
+        RunOutcome::Failed("No calendar".to_string())
+    );
+}
+
+#[test]
+fn runs_are_rendered_with_errors_underneath() {
+    let rendered = render_runs(&[
+        run(3, "2024-10-01 12:00:00", false, None),
+        run(
+            2,
+            "2024-10-01 11:00:00",
+            true,
+            Some("Failed to fetch iCal file"),
+        ),
+        run(1, "2024-10-01 10:00:00", true, None),
+    ]);
+    let lines = rendered.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("   Run  Started (UTC)"));
+    assert!(lines[1].contains("2024-10-01 12:00:00         -  unfinished"));
+    assert!(lines[2].contains("2024-10-01 11:00:00     1m 0s  FAILED"));
+    assert_eq!(lines[3].trim(), "Failed to fetch iCal file");
+    assert!(lines[4].contains("ok"));
+    assert!(lines[4].ends_with("3  DoCSoc Private"));
+}
+
+#[test]
+fn sync_is_stale_when_it_has_not_succeeded_recently() {
+    let last_success = run(7, "2024-10-01 10:00:00", true, None);
+    let stale_after = Duration::hours(24);
+
+    assert_eq!(
+        staleness(
+            Some(&last_success),
+            time("2024-10-02 09:00:00"),
+            stale_after
+        ),
+        None
+    );
+    assert_eq!(
+        staleness(
+            Some(&last_success),
+            time("2024-10-03 12:01:00"),
+            stale_after
+        ),
+        Some(
+            "The last successful run (run 7) finished 2d 2h ago, more than the 1d 0h allowed"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        staleness(None, time("2024-10-02 09:00:00"), stale_after),
+        Some("The sync has never run successfully".to_string())
+    );
+}
+
+#[test]
+fn sync_is_stale_only_once_the_threshold_has_passed() {
+    // Finished at 10:01
+    let last_success = run(7, "2024-10-01 10:00:00", true, None);
+    let stale_after = Duration::hours(24);
+
+    assert_eq!(
+        staleness(
+            Some(&last_success),
+            time("2024-10-02 10:01:00"),
+            stale_after
+        ),
+        None
+    );
+    assert_eq!(
+        staleness(
+            Some(&last_success),
+            time("2024-10-02 10:01:01"),
+            stale_after
+        ),
+        Some(
+            "The last successful run (run 7) finished 1d 0h ago, more than the 1d 0h allowed"
+                .to_string()
+        )
+    );
+    // e.g. the clock on the machine running the status check is behind the database's
+    assert_eq!(
+        staleness(
+            Some(&last_success),
+            time("2024-10-01 09:00:00"),
+            stale_after
+        ),
+        None
+    );
+    assert_eq!(
+        staleness(
+            Some(&last_success),
+            time("2024-10-01 10:32:00"),
+            Duration::minutes(30)
+        ),
+        Some(
+            "The last successful run (run 7) finished 31m 0s ago, more than the 30m 0s allowed"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn durations_are_shown_to_the_two_biggest_units() {
+    assert_eq!(format_duration(Duration::seconds(42)), "42s");
+    assert_eq!(format_duration(Duration::seconds(125)), "2m 5s");
+    assert_eq!(format_duration(Duration::minutes(185)), "3h 5m");
+    assert_eq!(format_duration(Duration::hours(50)), "2d 2h");
+}
+
+#[test]
+fn runs_are_recorded_in_the_database() {
+    use clickup_ical_sync::db::{finish_sync_run, recent_sync_runs, start_sync_run};
+
+    let Some(db) = common::TempDatabase::new() else {
+        return;
+    };
+    let mut connection = db.connection();
+    let run_id = start_sync_run(&mut connection).unwrap();
+
+    // Recorded as soon as it starts, so a run that's killed still shows up
+    let runs = recent_sync_runs(&mut connection, 10).unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].run_id, run_id);
+    assert_eq!(outcome(&runs[0]), RunOutcome::Unfinished);
+
+    let stats = SyncStats {
+        created: 1,
+        updated: 2,
+        deleted: 3,
+        skipped: 4,
+        failed: 5,
+        requests: 6,
+    };
+    finish_sync_run(
+        &mut connection,
+        run_id,
+        Some("DoCSoc Private"),
+        &stats,
+        Some("Failed to sync events: Database is locked"),
+    )
+    .unwrap();
+
+    let run = &recent_sync_runs(&mut connection, 10).unwrap()[0];
+    assert!(run.finished_at.unwrap() >= run.started_at);
+    assert_eq!(run.source.as_deref(), Some("DoCSoc Private"));
+    assert_eq!(
+        (
+            run.created,
+            run.updated,
+            run.deleted,
+            run.skipped,
+            run.failed,
+            run.requests
+        ),
+        (1, 2, 3, 4, 5, 6)
+    );
+    assert_eq!(
+        outcome(run),
+        RunOutcome::Failed("Failed to sync events: Database is locked".to_string())
+    );
+}
+
+#[test]
+fn recent_runs_are_newest_first_and_the_last_success_skips_the_rest() {
+    use clickup_ical_sync::db::{
+        finish_sync_run, last_successful_sync_run, recent_sync_runs, start_sync_run,
+    };
+
+    let Some(db) = common::TempDatabase::new() else {
+        return;
+    };
+    let mut connection = db.connection();
+    assert!(last_successful_sync_run(&mut connection).unwrap().is_none());
+
+    let mut finish = |error: Option<&str>| {
+        let run_id = start_sync_run(&mut connection).unwrap();
+        finish_sync_run(&mut connection, run_id, None, &SyncStats::default(), error).unwrap();
+        run_id
+    };
+    let first = finish(None);
+    let second = finish(None);
+    let failed = finish(Some("Failed to fetch iCal file"));
+    // Killed before it could record how it went
+    let unfinished = start_sync_run(&mut connection).unwrap();
+
+    let runs = recent_sync_runs(&mut connection, 10).unwrap();
+    assert_eq!(
+        runs.iter().map(|run| run.run_id).collect::<Vec<_>>(),
+        vec![unfinished, failed, second, first]
+    );
+    assert_eq!(
+        recent_sync_runs(&mut connection, 2)
+            .unwrap()
+            .iter()
+            .map(|run| run.run_id)
+            .collect::<Vec<_>>(),
+        vec![unfinished, failed]
+    );
+    assert_eq!(
+        last_successful_sync_run(&mut connection)
+            .unwrap()
+            .unwrap()
+            .run_id,
+        second
+    );
+}
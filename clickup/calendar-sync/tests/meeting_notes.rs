@@ -0,0 +1,130 @@
+//! Tests of making meeting notes documents in ClickUp Docs for matching events
+mod common;
+
+use clickup_ical_sync::{
+    config::parse_config,
+    stateless::ClickUpStore,
+    sync::{sync_events, SyncContext},
+};
+use common::{event, setup, FakeClickUp, MemoryStore, FAKE_LIST_ID};
+use serde_json::json;
+
+const RULES: &str = r##"
+[[meeting_notes.rules]]
+category = "Meeting"
+title = "Minutes: {summary}"
+template = "# {summary}\n\nDate: {start}\nAttendees: {attendees}\n\n## Agenda\n"
+"##;
+
+#[test]
+fn matching_events_get_one_doc_linked_in_a_comment() {
+    let (fake, api, context) = setup(RULES);
+    let mut store = MemoryStore::default();
+
+    let mut meeting = event(
+        "uid-1",
+        "Committee meeting",
+        "2024-10-01 18:00",
+        "2024-10-01 19:00",
+    );
+    meeting.categories = vec!["meeting".to_string()];
+    meeting.attendees = vec![
+        "chair@docsoc.co.uk".to_string(),
+        "treasurer@docsoc.co.uk".to_string(),
+    ];
+    let social = event(
+        "uid-2",
+        "Pub social",
+        "2024-10-02 19:00",
+        "2024-10-02 23:00",
+    );
+    sync_events(&api, &context, &mut store, vec![meeting.clone(), social]).unwrap();
+
+    let docs = fake.docs();
+    assert_eq!(docs.len(), 1);
+    assert_eq!(docs[0]["name"], "Minutes: Committee meeting");
+    assert_eq!(docs[0]["parent"], json!({"id": FAKE_LIST_ID, "type": 6}));
+    let content = docs[0]["pages"][0]["content"].as_str().unwrap();
+    assert!(content.contains(&meeting.render("Date: {start}")));
+    assert!(content.contains("Attendees: chair@docsoc.co.uk, treasurer@docsoc.co.uk"));
+
+    // Linked from the task, and remembered on the mapping
+    let doc_id = docs[0]["id"].as_str().unwrap();
+    let mapping = &store.mappings[0];
+    assert_eq!(mapping.meeting_doc.as_deref(), Some(doc_id));
+    assert_eq!(
+        fake.comments(&mapping.clickup_id)[0]["comment_text"],
+        format!("Meeting notes: https://app.clickup.com/1/v/dc/{}", doc_id)
+    );
+    assert!(store.mappings[1].meeting_doc.is_none());
+
+    // Even when the meeting changes, it keeps the same document
+    meeting.location = Some("Huxley 311".to_string());
+    sync_events(&api, &context, &mut store, vec![meeting]).unwrap();
+    assert_eq!(fake.docs().len(), 1);
+}
+
+#[test]
+fn stateless_mode_finds_docs_from_the_link_field() {
+    let fake = FakeClickUp::start();
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-uid", "name": "iCal UID", "type": "short_text"}),
+    );
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-notes", "name": "Minutes", "type": "url"}),
+    );
+    let config = parse_config(&format!(
+        "[markers]\nstateless = true\nuid_field = \"iCal UID\"\n[meeting_notes]\nlink_field = \"Minutes\"\n{}",
+        RULES
+    ))
+    .unwrap();
+    let api = fake.client();
+    let context = SyncContext::load(&api, &config, None).expect("Failed to set up the sync");
+
+    let mut meeting = event(
+        "uid-1",
+        "Committee meeting",
+        "2024-10-01 18:00",
+        "2024-10-01 19:00",
+    );
+    meeting.categories = vec!["Meeting".to_string()];
+    for _ in 0..2 {
+        sync_events(
+            &api,
+            &context,
+            &mut ClickUpStore::new(&api, &context),
+            vec![meeting.clone()],
+        )
+        .unwrap();
+    }
+
+    let docs = fake.docs();
+    assert_eq!(docs.len(), 1);
+    let task = fake.tasks().remove(0);
+    assert_eq!(
+        fake.custom_field_value(task["id"].as_str().unwrap(), "f-notes"),
+        Some(json!(format!(
+            "https://app.clickup.com/1/v/dc/{}",
+            docs[0]["id"].as_str().unwrap()
+        )))
+    );
+}
+
+#[test]
+fn stateless_mode_needs_link_field() {
+    let fake = FakeClickUp::start();
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-uid", "name": "iCal UID", "type": "short_text"}),
+    );
+    let config = parse_config(&format!(
+        "[markers]\nstateless = true\nuid_field = \"iCal UID\"\n{}",
+        RULES
+    ))
+    .unwrap();
+    let api = fake.client();
+
+    assert!(SyncContext::load(&api, &config, None).is_err());
+}
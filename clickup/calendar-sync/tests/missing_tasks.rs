@@ -0,0 +1,100 @@
+//! Tests of handling tasks that were deleted by hand in ClickUp
+mod common;
+
+use clickup_ical_sync::sync::sync_events;
+use common::{event, setup, MemoryStore};
+
+#[test]
+fn deleted_tasks_are_unlinked_by_default() {
+    let (fake, api, context) = setup("");
+    let mut store = MemoryStore::default();
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    sync_events(&api, &context, &mut store, vec![talk.clone()]).unwrap();
+    fake.delete_task(&store.mappings[0].clickup_id);
+
+    // When the event next changes the update 404s, so the event is unlinked rather than failing every run
+    let mut talk = talk;
+    talk.description = "Now in Huxley 311".to_string();
+    sync_events(&api, &context, &mut store, vec![talk.clone()]).unwrap();
+    assert!(store.mappings[0].user_deleted);
+    let updates = fake.count_requests("PUT /task");
+    talk.description = "Now in Huxley 308".to_string();
+    sync_events(&api, &context, &mut store, vec![talk]).unwrap();
+    assert_eq!(fake.count_requests("PUT /task"), updates);
+    assert!(fake.tasks().is_empty());
+
+    // Once the event goes, so does the mapping, without trying to delete the task again
+    let deletes = fake.count_requests("DELETE /task");
+    sync_events(&api, &context, &mut store, vec![]).unwrap();
+    assert!(store.mappings.is_empty());
+    assert_eq!(fake.count_requests("DELETE /task"), deletes);
+}
+
+#[test]
+fn deleted_tasks_can_be_recreated() {
+    let (fake, api, context) = setup("[missing_tasks]\npolicy = \"recreate\"");
+    let mut store = MemoryStore::default();
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    sync_events(&api, &context, &mut store, vec![talk.clone()]).unwrap();
+    let old_id = store.mappings[0].clickup_id.clone();
+    fake.delete_task(&old_id);
+
+    let mut talk = talk;
+    talk.description = "Now in Huxley 311".to_string();
+    sync_events(&api, &context, &mut store, vec![talk]).unwrap();
+    let new_id = &store.mappings[0].clickup_id;
+    assert_ne!(new_id, &old_id);
+    assert_eq!(fake.task(new_id).unwrap()["name"], "Talk");
+    assert!(!store.mappings[0].user_deleted);
+}
+
+#[test]
+fn verification_finds_deleted_tasks_of_unchanged_events() {
+    let (fake, api, context) = setup("[missing_tasks]\npolicy = \"recreate\"\nverify = true");
+    let mut store = MemoryStore::default();
+    let events = vec![
+        event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00"),
+        event("uid-3", "Hackathon", "2024-10-03 10:00", "2024-10-03 18:00"),
+    ];
+    sync_events(&api, &context, &mut store, events.clone()).unwrap();
+    let deleted = store.mappings[1].clickup_id.clone();
+    fake.delete_task(&deleted);
+
+    let before = fake.requests().len();
+    sync_events(&api, &context, &mut store, events).unwrap();
+    let requests = fake.requests()[before..].to_vec();
+
+    // Only the missing task is fetched, and it's recreated without trying to update it first
+    assert_eq!(
+        requests
+            .iter()
+            .filter(|request| request.starts_with("GET /task/"))
+            .collect::<Vec<_>>(),
+        vec![&format!("GET /task/{}", deleted)]
+    );
+    assert!(!requests.contains(&format!("PUT /task/{}", deleted)));
+    assert_eq!(fake.tasks().len(), 3);
+}
+
+#[test]
+fn retiring_a_deleted_task_removes_the_mapping() {
+    let (fake, api, context) = setup("");
+    let mut store = MemoryStore::default();
+    sync_events(
+        &api,
+        &context,
+        &mut store,
+        vec![event(
+            "uid-1",
+            "Talk",
+            "2024-10-01 18:00",
+            "2024-10-01 19:00",
+        )],
+    )
+    .unwrap();
+    fake.delete_task(&store.mappings[0].clickup_id);
+
+    sync_events(&api, &context, &mut store, vec![]).unwrap();
+    assert!(store.mappings.is_empty());
+}
@@ -0,0 +1,103 @@
+//! Tests of protecting edits made to tasks in ClickUp
+mod common;
+
+use clickup_ical_sync::{
+    clickup::ClickUpApiInstance,
+    models::{synced_fields_to_json, CalendarMapping},
+    sync::SyncContext,
+};
+use common::{event, mapping, setup, FakeClickUp};
+use serde_json::json;
+
+/// Create a task for an event with the given ownership config, returning its mapping as the sync would store it
+fn create(
+    config: &str,
+) -> (
+    FakeClickUp,
+    ClickUpApiInstance,
+    SyncContext,
+    CalendarMapping,
+) {
+    let (fake, api, context) = setup(config);
+
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    let id = context.create_task(&api, &talk).unwrap();
+    let mapping = CalendarMapping {
+        last_synced: Some(synced_fields_to_json(&context.synced_fields_for(&talk))),
+        ..mapping(&id, "uid-1")
+    };
+    (fake, api, context, mapping)
+}
+
+#[test]
+fn on_create_fields_are_only_written_once() {
+    let (fake, api, context, mapping) = create("[ownership]\ndescription = \"on_create\"");
+
+    context
+        .update_task(
+            &api,
+            &mapping,
+            &event(
+                "uid-1",
+                "Talk (renamed)",
+                "2024-10-01 18:00",
+                "2024-10-01 19:00",
+            ),
+        )
+        .unwrap();
+
+    let task = fake.task(&mapping.clickup_id).unwrap();
+    assert_eq!(task["name"], "Talk (renamed)");
+    assert_eq!(task["description"], "Description of Talk");
+}
+
+#[test]
+fn never_owned_fields_are_not_written_on_create() {
+    let (fake, _, _, mapping) = create("[ownership]\ndescription = \"never\"\ndates = \"never\"");
+
+    let task = fake.task(&mapping.clickup_id).unwrap();
+    assert_eq!(task["name"], "Talk");
+    assert_eq!(task["description"], json!(null));
+    assert_eq!(task["start_date"], json!(null));
+}
+
+#[test]
+fn drift_is_kept_by_default() {
+    let (fake, api, context, mapping) = create("[ownership]\ndetect_drift = true");
+    fake.edit_task(
+        &mapping.clickup_id,
+        json!({"description": "Bring your laptop!"}),
+    );
+
+    let synced = context
+        .update_task(
+            &api,
+            &mapping,
+            &event("uid-1", "Talk", "2024-10-02 18:00", "2024-10-02 19:00"),
+        )
+        .unwrap();
+
+    let task = fake.task(&mapping.clickup_id).unwrap();
+    assert_eq!(task["description"], "Bring your laptop!");
+    assert_eq!(task["start_date"], "1727892000000");
+    // We didn't write the description, so we still remember what we wrote last time
+    assert_eq!(synced.description, "Description of Talk");
+    assert_eq!(synced.start_date, Some(1727892000000));
+}
+
+#[test]
+fn drift_can_be_overwritten() {
+    let (fake, api, context, mapping) =
+        create("[ownership]\ndetect_drift = true\non_conflict = \"overwrite\"");
+    fake.edit_task(&mapping.clickup_id, json!({"name": "Renamed by hand"}));
+
+    context
+        .update_task(
+            &api,
+            &mapping,
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .unwrap();
+
+    assert_eq!(fake.task(&mapping.clickup_id).unwrap()["name"], "Talk");
+}
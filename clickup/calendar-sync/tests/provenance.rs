@@ -0,0 +1,56 @@
+//! Tests of marking tasks as made by the sync
+mod common;
+
+use clickup_ical_sync::{docsoc_ical::calendar_name, sync::sync_events};
+use common::{event, mapping, setup, MemoryStore, FAKE_LIST_ID};
+use serde_json::json;
+
+const CONFIG: &str = r#"
+[provenance]
+footer = true
+marker_tag = "calendar-sync"
+calendar_name = "DoCSoc Private"
+"#;
+
+#[test]
+fn tasks_get_a_footer_and_the_marker_tag() {
+    let (fake, api, context) = setup(CONFIG);
+    let mut talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    talk.url = Some("https://example.com/talk".to_string());
+    let id = context.create_task(&api, &talk).unwrap();
+
+    let task = fake.task(&id).unwrap();
+    assert_eq!(
+        task["description"],
+        "Description of Talk\n\n---\n\
+         Synced from the DoCSoc Private calendar: https://example.com/talk\n\
+         Managed by calendar sync: change the event in the calendar, as edits to this task may be overwritten."
+    );
+    assert_eq!(task["tags"], json!([{"name": "calendar-sync"}]));
+}
+
+#[test]
+fn tasks_without_the_marker_are_never_deleted() {
+    let (fake, api, context) = setup(CONFIG);
+    let mut store = MemoryStore::default();
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    sync_events(&api, &context, &mut store, vec![talk]).unwrap();
+    let talk_id = store.mappings[0].clickup_id.clone();
+
+    // Someone's task got mapped to an event by mistake
+    let by_hand = fake.insert_task(FAKE_LIST_ID, json!({"name": "Committee dinner"}));
+    store.mappings.push(mapping(&by_hand, "uid-2"));
+
+    // Both events are gone, but only the task the sync made is deleted
+    sync_events(&api, &context, &mut store, vec![]).unwrap();
+    assert!(store.mappings.is_empty());
+    assert!(fake.task(&talk_id).is_none());
+    assert!(fake.task(&by_hand).is_some());
+}
+
+#[test]
+fn calendar_name_comes_from_the_ical() {
+    let ical = "BEGIN:VCALENDAR\nVERSION:2.0\nX-WR-CALNAME:DoCSoc Private\nEND:VCALENDAR\n";
+    assert_eq!(calendar_name(ical), Some("DoCSoc Private".to_string()));
+    assert_eq!(calendar_name("BEGIN:VCALENDAR\nEND:VCALENDAR\n"), None);
+}
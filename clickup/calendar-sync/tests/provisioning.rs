@@ -0,0 +1,113 @@
+//! Tests of setting up a list with the provision command
+mod common;
+
+use clickup_ical_sync::{clickup::SpaceTag, config::parse_config, provisioning::provision};
+use common::{FakeClickUp, FAKE_LIST_ID};
+use serde_json::json;
+
+const CONFIG: &str = r##"
+[provision]
+space_id = "s1"
+folder_id = "f1"
+list_name = "Events 2025/26"
+statuses = ["to do", "complete"]
+
+[[provision.tags]]
+name = "Cancelled"
+fg_color = "#ffffff"
+bg_color = "#e50000"
+
+[[provision.custom_fields]]
+name = "Location"
+type = "short_text"
+
+[[provision.custom_fields]]
+name = "Event Type"
+type = "drop_down"
+options = ["Social", "Talk"]
+"##;
+
+/// A space with an empty folder for this year's list
+fn setup() -> FakeClickUp {
+    let fake = FakeClickUp::start();
+    fake.add_space("s1", "Committee");
+    fake.add_folder("s1", "f1", "Events");
+    fake
+}
+
+/// Requests that change something
+fn writes(fake: &FakeClickUp) -> Vec<String> {
+    fake.requests()
+        .into_iter()
+        .filter(|request| !request.starts_with("GET"))
+        .collect()
+}
+
+#[test]
+fn provisioning_sets_up_a_new_list_and_is_idempotent() {
+    let fake = setup();
+    let api = fake.client();
+    let config = parse_config(CONFIG).unwrap().provision.unwrap();
+
+    let report = provision(&api, &config).expect("Failed to provision");
+    assert_eq!(report.changes.len(), 4);
+    assert!(report.problems.is_empty());
+    assert_eq!(
+        fake.space_tags("s1"),
+        vec![json!({"name": "cancelled", "tag_fg": "#ffffff", "tag_bg": "#e50000"})]
+    );
+    let fields = api.get_custom_fields(&report.list_id).unwrap();
+    assert_eq!(fields.len(), 2);
+    assert!(fields[1].option_id("talk").is_some());
+
+    // Everything's there now, so the second run finds the same list & changes nothing
+    let writes_before = writes(&fake).len();
+    let again = provision(&api, &config).unwrap();
+    assert_eq!(again.list_id, report.list_id);
+    assert!(again.changes.is_empty());
+    assert!(again.problems.is_empty());
+    assert_eq!(writes(&fake).len(), writes_before);
+}
+
+#[test]
+fn provisioning_fixes_what_it_can_and_reports_the_rest() {
+    let fake = setup();
+    fake.add_list("s1", Some("f1"), FAKE_LIST_ID, "Events 2025/26");
+    fake.set_list_statuses(FAKE_LIST_ID, &["to do", "Complete"]);
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-loc", "name": "location", "type": "url"}),
+    );
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-type", "name": "Event Type", "type": "drop_down", "type_config": {
+            "options": [{"id": "opt-social", "name": "Social"}]
+        }}),
+    );
+    let api = fake.client();
+    api.create_space_tag(
+        "s1",
+        &SpaceTag {
+            name: "cancelled".to_string(),
+            tag_fg: Some("#000000".to_string()),
+            tag_bg: Some("#E50000".to_string()),
+        },
+    )
+    .unwrap();
+
+    let mut config = parse_config(CONFIG).unwrap().provision.unwrap();
+    config.statuses.push("cancelled".to_string());
+    let report = provision(&api, &config).unwrap();
+
+    assert_eq!(report.list_id, FAKE_LIST_ID);
+    assert_eq!(
+        report.changes,
+        vec!["Changed the colours of tag \"cancelled\""]
+    );
+    assert_eq!(
+        fake.space_tags("s1"),
+        vec![json!({"name": "cancelled", "tag_fg": "#ffffff", "tag_bg": "#e50000"})]
+    );
+    // Missing status, wrong field type & missing dropdown option can't be fixed through the API
+    assert_eq!(report.problems.len(), 3, "{:?}", report.problems);
+}
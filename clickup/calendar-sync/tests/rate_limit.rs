@@ -0,0 +1,115 @@
+//! Tests of sharing the ClickUp rate limit between processes
+mod common;
+
+use std::{cell::Cell, error::Error, rc::Rc, time::Duration};
+
+use clickup_ical_sync::{
+    config::{parse_config, RateLimitConfig},
+    rate_limit::{Bucket, FileRateLimit, SharedBucket},
+    sync::SyncContext,
+};
+use common::{event, FakeClickUp, FAKE_LIST_ID};
+
+/// A shared bucket that never runs out, counting the tokens taken from it (or failing, if asked)
+struct CountingBucket {
+    taken: Rc<Cell<u32>>,
+    fail: bool,
+}
+
+impl SharedBucket for CountingBucket {
+    fn try_take(&mut self, _key: &str, _limit: u32) -> Result<Option<Duration>, Box<dyn Error>> {
+        self.taken.set(self.taken.get() + 1);
+        if self.fail {
+            return Err("database is down".into());
+        }
+        Ok(None)
+    }
+}
+
+#[test]
+fn buckets_refill_at_the_rate_limit() {
+    let mut bucket = Bucket::full(60, 0);
+    for _ in 0..60 {
+        assert_eq!(bucket.take(60, 0), None);
+    }
+    // 60 a minute is one a second
+    assert_eq!(bucket.take(60, 0), Some(Duration::from_secs(1)));
+    assert_eq!(bucket.take(60, 500), Some(Duration::from_millis(500)));
+    assert_eq!(bucket.take(60, 1000), None);
+
+    // A minute's rest only refills the bucket to the limit
+    bucket.take(60, 120_000);
+    assert_eq!(bucket.tokens, 59.0);
+}
+
+#[test]
+fn file_buckets_are_shared_between_processes() {
+    let path = std::env::temp_dir().join(format!("clickup-rate-limit-test-{}", std::process::id()));
+    let mut first = FileRateLimit::new(path.clone());
+    let mut second = FileRateLimit::new(path.clone());
+
+    for _ in 0..3 {
+        assert_eq!(first.try_take("token-a", 3).unwrap(), None);
+    }
+    assert!(second.try_take("token-a", 3).unwrap().is_some());
+    // Other tokens have their own limits
+    assert_eq!(second.try_take("token-b", 3).unwrap(), None);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn requests_draw_from_the_shared_bucket() {
+    let fake = FakeClickUp::start();
+    let mut api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    let taken = Rc::new(Cell::new(0));
+    api.share_rate_limit(Box::new(CountingBucket {
+        taken: taken.clone(),
+        fail: false,
+    }));
+
+    context
+        .create_task(
+            &api,
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .unwrap();
+    assert_eq!(taken.get() as usize, fake.requests().len());
+}
+
+#[test]
+fn failing_shared_bucket_falls_back_to_own_limit() {
+    let fake = FakeClickUp::start();
+    let mut api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    let taken = Rc::new(Cell::new(0));
+    api.share_rate_limit(Box::new(CountingBucket {
+        taken: taken.clone(),
+        fail: true,
+    }));
+
+    context
+        .create_task(
+            &api,
+            &event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        )
+        .unwrap();
+    context
+        .create_task(
+            &api,
+            &event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00"),
+        )
+        .unwrap();
+    // Only tried once, rather than failing again for every request
+    assert_eq!(taken.get(), 1);
+    assert_eq!(fake.tasks().len(), 2);
+}
+
+#[test]
+fn database_store_is_still_read_under_its_old_name() {
+    for store in ["database", "postgres"] {
+        let config = parse_config(&format!("[rate_limit]\nstore = \"{}\"", store)).unwrap();
+        assert_eq!(config.rate_limit, RateLimitConfig::Database);
+    }
+}
@@ -0,0 +1,120 @@
+//! Tests of routing events to more than one list
+mod common;
+
+use clickup_ical_sync::{
+    docsoc_ical::ParsedEvent,
+    sync::{sync_events, SyncContext},
+};
+use common::{event, setup, FakeClickUp, MemoryStore, FAKE_LIST_ID};
+
+/// The sponsorship team's list
+const SPONSORSHIP_LIST_ID: &str = "902";
+
+/// Sends everything to the main list, and careers events to the sponsorship list too
+const CONFIG: &str = r#"
+[[routes]]
+list_id = "901"
+
+[[routes]]
+list_id = "902"
+category = "Careers"
+"#;
+
+/// IDs of the lists that have a task called `name`, sorted
+fn lists_with_task(fake: &FakeClickUp, name: &str) -> Vec<String> {
+    let mut lists: Vec<String> = fake
+        .tasks()
+        .iter()
+        .filter(|task| task["name"] == name)
+        .map(|task| task["list"]["id"].as_str().unwrap().to_string())
+        .collect();
+    lists.sort();
+    lists
+}
+
+fn careers_fair() -> ParsedEvent {
+    let mut careers = event(
+        "uid-1",
+        "Careers Fair",
+        "2024-10-01 10:00",
+        "2024-10-01 16:00",
+    );
+    careers.categories = vec!["careers".to_string()];
+    careers
+}
+
+#[test]
+fn events_get_a_task_in_every_matching_list() {
+    let (fake, api, context) = setup(CONFIG);
+    let mut store = MemoryStore::default();
+    let events = vec![
+        careers_fair(),
+        event("uid-2", "Talk", "2024-10-02 18:00", "2024-10-02 19:00"),
+    ];
+
+    sync_events(&api, &context, &mut store, events.clone()).unwrap();
+    assert_eq!(
+        lists_with_task(&fake, "Careers Fair"),
+        vec![FAKE_LIST_ID, SPONSORSHIP_LIST_ID]
+    );
+    assert_eq!(lists_with_task(&fake, "Talk"), vec![FAKE_LIST_ID]);
+    assert_eq!(store.mappings.len(), 3);
+
+    // Each task is updated, not created again
+    let mut events = events;
+    events[0].summary = "Careers Fair 2024".to_string();
+    sync_events(&api, &context, &mut store, events).unwrap();
+    assert_eq!(fake.tasks().len(), 3);
+    assert_eq!(
+        lists_with_task(&fake, "Careers Fair 2024"),
+        vec![FAKE_LIST_ID, SPONSORSHIP_LIST_ID]
+    );
+}
+
+#[test]
+fn changing_an_events_routes_only_changes_tasks_in_those_lists() {
+    let (fake, api, context) = setup(CONFIG);
+    let mut store = MemoryStore::default();
+    let mut careers = careers_fair();
+    sync_events(&api, &context, &mut store, vec![careers.clone()]).unwrap();
+    let main_task = store
+        .mappings
+        .iter()
+        .find(|mapping| mapping.list_id == FAKE_LIST_ID)
+        .unwrap()
+        .clickup_id
+        .clone();
+
+    // No longer a careers event, so only the sponsorship list's task goes
+    careers.categories = vec![];
+    sync_events(&api, &context, &mut store, vec![careers.clone()]).unwrap();
+    assert_eq!(lists_with_task(&fake, "Careers Fair"), vec![FAKE_LIST_ID]);
+    assert!(fake.task(&main_task).is_some());
+    assert_eq!(store.mappings.len(), 1);
+
+    // And comes back when it is one again
+    careers.categories = vec!["Careers".to_string()];
+    sync_events(&api, &context, &mut store, vec![careers]).unwrap();
+    assert_eq!(
+        lists_with_task(&fake, "Careers Fair"),
+        vec![FAKE_LIST_ID, SPONSORSHIP_LIST_ID]
+    );
+    assert!(fake.task(&main_task).is_some());
+}
+
+#[test]
+fn without_routes_everything_goes_to_the_target_list() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+
+    sync_events(
+        &api,
+        &context,
+        &mut MemoryStore::default(),
+        vec![careers_fair()],
+    )
+    .unwrap();
+    assert_eq!(lists_with_task(&fake, "Careers Fair"), vec![FAKE_LIST_ID]);
+    assert_eq!(context.list_ids(), vec![FAKE_LIST_ID]);
+}
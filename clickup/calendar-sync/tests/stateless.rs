@@ -0,0 +1,163 @@
+//! Tests of running the sync without a database, keeping the mappings on the tasks
+mod common;
+
+use clickup_ical_sync::{
+    clickup::ClickUpApiInstance,
+    config::parse_config,
+    docsoc_ical::ParsedEvent,
+    stateless::ClickUpStore,
+    store::MappingStore,
+    sync::{sync_events, SyncContext},
+};
+use common::{event, setup_with, FakeClickUp, FAKE_LIST_ID};
+use serde_json::json;
+
+/// Add the marker fields to the fake list
+fn add_marker_fields(fake: &FakeClickUp) {
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-uid", "name": "iCal UID", "type": "short_text"}),
+    );
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-fp", "name": "Fingerprint", "type": "short_text"}),
+    );
+}
+
+/// A client & sync set up for stateless mode with the given extra config
+fn setup(extra_config: &str) -> (FakeClickUp, ClickUpApiInstance, SyncContext) {
+    setup_with(
+        &format!(
+            "[markers]\nstateless = true\nuid_field = \"iCal UID\"\nfingerprint_field = \"Fingerprint\"\n{}",
+            extra_config
+        ),
+        add_marker_fields,
+    )
+}
+
+/// Run the sync as a fresh process would, rebuilding the mappings from ClickUp
+fn run(api: &ClickUpApiInstance, context: &SyncContext, events: &[ParsedEvent]) {
+    sync_events(
+        api,
+        context,
+        &mut ClickUpStore::new(api, context),
+        events.to_vec(),
+    )
+    .expect("Sync failed");
+}
+
+#[test]
+fn tasks_are_found_again_and_only_updated_when_events_change() {
+    let (fake, api, context) = setup("");
+    let mut events = vec![
+        event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+        event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00"),
+    ];
+
+    run(&api, &context, &events);
+    assert_eq!(fake.tasks().len(), 2);
+
+    // Nothing changed, so nothing is written
+    run(&api, &context, &events);
+    assert_eq!(fake.tasks().len(), 2);
+    assert_eq!(fake.count_requests("PUT /task"), 0);
+
+    // One event changes, and the other is deleted
+    events[0].summary = "Talk (moved)".to_string();
+    events.pop();
+    run(&api, &context, &events);
+    let tasks = fake.tasks();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0]["name"], "Talk (moved)");
+    assert_eq!(fake.count_requests("PUT /task"), 1);
+}
+
+#[test]
+fn retired_tasks_are_found_from_the_deletion_policy() {
+    let (fake, api, context) = setup("[deletion]\npolicy = \"tag\"\ntag = \"cancelled\"");
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+
+    run(&api, &context, std::slice::from_ref(&talk));
+    let id = fake.tasks()[0]["id"].as_str().unwrap().to_string();
+
+    // Retired once, not again on the next run
+    run(&api, &context, &[]);
+    run(&api, &context, &[]);
+    assert_eq!(
+        fake.task(&id).unwrap()["tags"],
+        json!([{"name": "cancelled"}])
+    );
+    assert_eq!(fake.count_requests("POST /task"), 1);
+
+    // Back in the calendar, so restored rather than recreated
+    run(&api, &context, &[talk]);
+    assert_eq!(fake.tasks().len(), 1);
+    assert_eq!(fake.task(&id).unwrap()["tags"], json!([]));
+}
+
+#[test]
+fn stateless_mode_needs_uid_field() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let config = parse_config("[markers]\nstateless = true").unwrap();
+
+    assert!(SyncContext::load(&api, &config, None).is_err());
+}
+
+#[test]
+fn stateless_mode_rejects_the_unlink_missing_tasks_policy() {
+    let fake = FakeClickUp::start();
+    add_marker_fields(&fake);
+    let api = fake.client();
+    let config = parse_config(
+        "[markers]\nstateless = true\nuid_field = \"iCal UID\"\n[missing_tasks]\npolicy = \"unlink\"",
+    )
+    .unwrap();
+
+    assert!(SyncContext::load(&api, &config, None).is_err());
+}
+
+#[test]
+fn tasks_deleted_by_hand_are_recreated_in_stateless_mode() {
+    let (fake, api, context) = setup("");
+    let events = vec![event(
+        "uid-1",
+        "Talk",
+        "2024-10-01 18:00",
+        "2024-10-01 19:00",
+    )];
+    run(&api, &context, &events);
+    let id = fake.tasks()[0]["id"].as_str().unwrap().to_string();
+
+    fake.delete_task(&id);
+    run(&api, &context, &events);
+
+    assert_eq!(fake.tasks().len(), 1);
+    assert_ne!(fake.tasks()[0]["id"], json!(id));
+}
+
+#[test]
+fn subtasks_with_a_copied_uid_are_ignored() {
+    let (fake, api, context) = setup("[deletion]\npolicy = \"archive\"");
+    let talk = event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00");
+    run(&api, &context, &[talk]);
+    let id = fake.tasks()[0]["id"].as_str().unwrap().to_string();
+    // e.g. added from a template that copies the parent's custom fields
+    let subtask_id = fake.insert_task(
+        FAKE_LIST_ID,
+        json!({
+            "name": "Book room",
+            "parent": id,
+            "custom_fields": [{"id": "f-uid", "value": "uid-1"}],
+        }),
+    );
+
+    // Once the talk is retired, the subtask isn't mistaken for a task that still needs retiring
+    run(&api, &context, &[]);
+    run(&api, &context, &[]);
+    assert_eq!(fake.task(&id).unwrap()["archived"], true);
+    assert_eq!(fake.task(&subtask_id).unwrap()["archived"], false);
+    let mappings = ClickUpStore::new(&api, &context).load().unwrap();
+    assert_eq!(mappings.len(), 1);
+    assert_eq!(mappings[0].clickup_id, id);
+}
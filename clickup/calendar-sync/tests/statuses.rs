@@ -0,0 +1,74 @@
+//! Tests of setting task statuses from the event lifecycle
+mod common;
+
+use chrono::{Duration, Utc};
+use clickup_ical_sync::{config::parse_config, docsoc_ical::EVENT_DATE_FORMAT, sync::SyncContext};
+use common::{event, setup_with, FakeClickUp, FAKE_LIST_ID};
+
+const CONFIG: &str = r#"
+[[statuses]]
+status = "Done"
+when = "ended"
+
+[[statuses]]
+status = "in progress"
+when = "started"
+
+[[statuses]]
+status = "this week"
+when = "starts_within"
+days = 7
+
+[[statuses]]
+status = "upcoming"
+when = "always"
+"#;
+
+/// An event starting `start_in_hours` from now, lasting 2 hours
+fn event_in(uid: &str, start_in_hours: i64) -> clickup_ical_sync::docsoc_ical::ParsedEvent {
+    let start = Utc::now().naive_utc() + Duration::hours(start_in_hours);
+    let end = start + Duration::hours(2);
+    event(
+        uid,
+        uid,
+        &start.format(EVENT_DATE_FORMAT).to_string(),
+        &end.format(EVENT_DATE_FORMAT).to_string(),
+    )
+}
+
+#[test]
+fn status_follows_event_lifecycle() {
+    let (fake, api, context) = setup_with(CONFIG, |fake| {
+        fake.set_list_statuses(
+            FAKE_LIST_ID,
+            &["upcoming", "this week", "in progress", "done"],
+        );
+    });
+
+    for (uid, start_in_hours, status) in [
+        ("past", -48, "done"),
+        ("now", -1, "in progress"),
+        ("soon", 24 * 3, "this week"),
+        ("later", 24 * 30, "upcoming"),
+    ] {
+        let id = context
+            .create_task(&api, &event_in(uid, start_in_hours))
+            .unwrap();
+        assert_eq!(
+            fake.task(&id).unwrap()["status"]["status"],
+            status,
+            "{}",
+            uid
+        );
+    }
+}
+
+#[test]
+fn unknown_status_is_an_error() {
+    let fake = FakeClickUp::start();
+    fake.set_list_statuses(FAKE_LIST_ID, &["to do", "complete"]);
+    let api = fake.client();
+
+    let err = SyncContext::load(&api, &parse_config(CONFIG).unwrap(), None).unwrap_err();
+    assert!(err.to_string().contains("Done"));
+}
@@ -0,0 +1,101 @@
+//! Tests of how the sync uses the mapping store
+mod common;
+
+use std::{collections::BTreeSet, error::Error};
+
+use clickup_ical_sync::{
+    models::CalendarMapping,
+    ownership::SyncedFields,
+    store::MappingStore,
+    sync::{sync_events, SyncContext},
+};
+use common::{event, FakeClickUp, MemoryStore, FAKE_LIST_ID};
+
+/// A store that records when it's loaded & flushed, and how many changes were made in between
+#[derive(Default)]
+struct RecordingStore {
+    inner: MemoryStore,
+    /// "load", or "flush N" where N is the number of changes since the last flush
+    calls: Vec<String>,
+    changes: usize,
+}
+
+impl MappingStore for RecordingStore {
+    fn load(&mut self) -> Result<Vec<CalendarMapping>, Box<dyn Error>> {
+        self.calls.push("load".to_string());
+        self.inner.load()
+    }
+
+    fn insert(
+        &mut self,
+        calendar_id: &str,
+        list_id: &str,
+        clickup_id: &str,
+        synced_assignees: &BTreeSet<i64>,
+        last_synced: Option<&SyncedFields>,
+        fingerprint: Option<&str>,
+    ) -> Result<CalendarMapping, Box<dyn Error>> {
+        self.changes += 1;
+        self.inner.insert(
+            calendar_id,
+            list_id,
+            clickup_id,
+            synced_assignees,
+            last_synced,
+            fingerprint,
+        )
+    }
+
+    fn update(&mut self, mapping: &CalendarMapping) -> Result<(), Box<dyn Error>> {
+        self.changes += 1;
+        self.inner.update(mapping)
+    }
+
+    fn delete(&mut self, mappings: &[CalendarMapping]) -> Result<(), Box<dyn Error>> {
+        self.changes += mappings.len();
+        self.inner.delete(mappings)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.calls.push(format!("flush {}", self.changes));
+        self.changes = 0;
+        Ok(())
+    }
+}
+
+#[test]
+fn mappings_are_loaded_once_and_flushed_after_each_step() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let context = SyncContext::new(FAKE_LIST_ID);
+    let mut store = RecordingStore::default();
+    sync_events(
+        &api,
+        &context,
+        &mut store,
+        vec![
+            event("uid-1", "Talk", "2024-10-01 18:00", "2024-10-01 19:00"),
+            event("uid-2", "Social", "2024-10-02 18:00", "2024-10-02 21:00"),
+        ],
+    )
+    .unwrap();
+    // Both created, then nothing to delete or update
+    assert_eq!(store.calls, vec!["load", "flush 2", "flush 0", "flush 0"]);
+
+    store.calls.clear();
+    sync_events(
+        &api,
+        &context,
+        &mut store,
+        vec![event(
+            "uid-1",
+            "Talk",
+            "2024-10-01 18:00",
+            "2024-10-01 20:00",
+        )],
+    )
+    .unwrap();
+    // The social is deleted, then the talk updated
+    assert_eq!(store.calls, vec!["load", "flush 0", "flush 1", "flush 1"]);
+    assert_eq!(fake.tasks().len(), 1);
+}
@@ -0,0 +1,124 @@
+//! Tests of creating tasks from ClickUp task templates
+mod common;
+
+use clickup_ical_sync::{config::parse_config, sync::SyncContext};
+use common::{event, setup_with, FakeClickUp, FAKE_LIST_ID};
+use serde_json::json;
+
+/// Add the "Social" & "Talk" task templates, and a location field, to the fake ClickUp
+fn add_templates(fake: &FakeClickUp) {
+    fake.add_task_template(
+        "t-social",
+        "Social",
+        json!({
+            "description": "Budget: £50",
+            "tags": ["social"],
+            "assignees": [5],
+            "custom_fields": [{"id": "f-budget", "value": "50"}],
+        }),
+    );
+    fake.add_task_template("t-talk", "Talk", json!({"tags": ["talk"]}));
+    fake.add_custom_field(
+        FAKE_LIST_ID,
+        json!({"id": "f-loc", "name": "Location", "type": "short_text"}),
+    );
+}
+
+#[test]
+fn calendar_fields_are_written_over_the_template() {
+    let (fake, api, context) = setup_with(
+        r#"
+[[custom_fields]]
+field = "Location"
+value = "{location}"
+
+[task_template]
+id = "t-social"
+"#,
+        add_templates,
+    );
+    let mut social = event(
+        "uid-1",
+        "Games night",
+        "2024-10-15 18:00",
+        "2024-10-15 21:00",
+    );
+    social.location = Some("SCR".to_string());
+    let id = context.create_task(&api, &social).unwrap();
+
+    assert!(fake.requests().contains(&format!(
+        "POST /list/{}/taskTemplate/t-social",
+        FAKE_LIST_ID
+    )));
+    let task = fake.task(&id).unwrap();
+    assert_eq!(task["name"], "Games night");
+    assert_eq!(task["description"], "Description of Games night");
+    assert_eq!(task["start_date"], "1729015200000");
+    // Everything else from the template is kept
+    assert_eq!(task["tags"], json!([{"name": "social"}]));
+    assert_eq!(fake.assignees(&id), vec![5]);
+    assert_eq!(fake.custom_field_value(&id, "f-budget"), Some(json!("50")));
+    assert_eq!(fake.custom_field_value(&id, "f-loc"), Some(json!("SCR")));
+}
+
+#[test]
+fn template_description_is_kept_with_a_managed_block() {
+    let (fake, api, context) = setup_with(
+        r#"
+[description]
+managed_block = true
+
+[task_template]
+id = "t-talk"
+lists = { "901" = "t-social" }
+"#,
+        add_templates,
+    );
+    let id = context
+        .create_task(
+            &api,
+            &event(
+                "uid-1",
+                "Games night",
+                "2024-10-15 18:00",
+                "2024-10-15 21:00",
+            ),
+        )
+        .unwrap();
+
+    // The template for the list is used rather than the default
+    let task = fake.task(&id).unwrap();
+    assert_eq!(task["tags"], json!([{"name": "social"}]));
+    assert_eq!(
+        task["description"],
+        "Budget: £50\n\n--- synced from calendar ---\nDescription of Games night\n--- end of synced section ---"
+    );
+}
+
+#[test]
+fn workspaces_are_fetched_once_for_templates_and_assignees() {
+    let (fake, _api, _context) = setup_with(
+        "[assignees]\ndefault = [\"5\"]\n[task_template]\nid = \"t-talk\"",
+        |fake| {
+            add_templates(fake);
+            fake.add_member(5, "events", "events@docsoc.co.uk");
+        },
+    );
+
+    assert_eq!(
+        fake.requests()
+            .iter()
+            .filter(|request| *request == "GET /team")
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn unknown_task_template_is_an_error() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let config = parse_config("[task_template]\nid = \"t-missing\"").unwrap();
+    let err = SyncContext::load(&api, &config, None).unwrap_err();
+    assert!(err.to_string().contains("t-missing"));
+}
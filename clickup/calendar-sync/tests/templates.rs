@@ -0,0 +1,168 @@
+//! Tests of adding subtasks & checklist items from templates to the tasks for matching events
+mod common;
+
+use chrono::{Duration, NaiveDateTime};
+use clickup_ical_sync::{
+    config::parse_config,
+    sync::{sync_events, SyncContext},
+};
+use common::{event, setup, FakeClickUp, MemoryStore};
+use serde_json::{json, Value};
+
+const CONFIG: &str = r#"
+[[templates]]
+category = "Social"
+
+[[templates.subtasks]]
+name = "Book room"
+due = "-14d"
+
+[[templates.subtasks]]
+name = "Order food"
+due = "-7d"
+
+[[templates.checklist]]
+name = "Post on socials"
+due = "-2d"
+
+[[templates]]
+summary = "(?i)talk"
+
+[[templates.subtasks]]
+name = "Write event report"
+due = "+3d"
+from = "end"
+"#;
+
+/// Milliseconds since the epoch of a time like 2024-10-01 18:00, as ClickUp sends dates
+fn millis(time: &str) -> Value {
+    json!(NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M")
+        .unwrap()
+        .and_utc()
+        .timestamp_millis()
+        .to_string())
+}
+
+/// (name, due date) of each subtask of a task
+fn subtasks(fake: &FakeClickUp, task_id: &str) -> Vec<(String, Value)> {
+    fake.subtasks(task_id)
+        .into_iter()
+        .map(|subtask| {
+            (
+                subtask["name"].as_str().unwrap().to_string(),
+                subtask["due_date"].clone(),
+            )
+        })
+        .collect()
+}
+
+/// ID of the task for an event
+fn task_for(store: &MemoryStore, uid: &str) -> String {
+    store
+        .mappings
+        .iter()
+        .find(|mapping| mapping.calendar_id == uid)
+        .unwrap()
+        .clickup_id
+        .clone()
+}
+
+#[test]
+fn matching_events_get_items_due_relative_to_the_event() {
+    let (fake, api, context) = setup(CONFIG);
+    let mut store = MemoryStore::default();
+    let mut social = event(
+        "uid-1",
+        "Games night",
+        "2024-10-15 18:00",
+        "2024-10-15 21:00",
+    );
+    social.categories = vec!["social".to_string()];
+    let talk = event("uid-2", "Tech talk", "2024-10-16 18:00", "2024-10-16 19:00");
+    let other = event("uid-3", "AGM", "2024-10-17 18:00", "2024-10-17 19:00");
+    sync_events(
+        &api,
+        &context,
+        &mut store,
+        vec![social.clone(), talk.clone(), other.clone()],
+    )
+    .unwrap();
+
+    assert_eq!(
+        subtasks(&fake, &task_for(&store, "uid-1")),
+        vec![
+            ("Book room".to_string(), millis("2024-10-01 18:00")),
+            ("Order food".to_string(), millis("2024-10-08 18:00")),
+        ]
+    );
+    let items = fake.checklist_items(&task_for(&store, "uid-1"));
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["name"], "Post on socials (by Sun 13 Oct)");
+    assert_eq!(
+        subtasks(&fake, &task_for(&store, "uid-2")),
+        vec![("Write event report".to_string(), millis("2024-10-19 19:00"))]
+    );
+    assert!(subtasks(&fake, &task_for(&store, "uid-3")).is_empty());
+    assert!(fake.checklist_items(&task_for(&store, "uid-3")).is_empty());
+
+    // Nothing is added twice when the event changes
+    social.description = "Now with pizza".to_string();
+    sync_events(&api, &context, &mut store, vec![social, talk, other]).unwrap();
+    assert_eq!(subtasks(&fake, &task_for(&store, "uid-1")).len(), 2);
+    assert_eq!(fake.checklist_items(&task_for(&store, "uid-1")).len(), 1);
+}
+
+#[test]
+fn items_move_with_the_event_unless_done() {
+    let (fake, api, context) = setup(CONFIG);
+    let mut store = MemoryStore::default();
+    let mut social = event(
+        "uid-1",
+        "Games night",
+        "2024-10-15 18:00",
+        "2024-10-15 21:00",
+    );
+    social.categories = vec!["Social".to_string()];
+    sync_events(&api, &context, &mut store, vec![social.clone()]).unwrap();
+    let task_id = store.mappings[0].clickup_id.clone();
+
+    // The room is booked, but the food isn't ordered yet
+    let book_room = fake.subtasks(&task_id)[0]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    fake.edit_task(&book_room, json!({"status": "complete"}));
+
+    social.start_time = Some(social.start_time.unwrap() + Duration::days(7));
+    social.end_time = Some(social.end_time.unwrap() + Duration::days(7));
+    sync_events(&api, &context, &mut store, vec![social.clone()]).unwrap();
+    assert_eq!(
+        subtasks(&fake, &task_id),
+        vec![
+            ("Book room".to_string(), millis("2024-10-01 18:00")),
+            ("Order food".to_string(), millis("2024-10-15 18:00")),
+        ]
+    );
+    let items = fake.checklist_items(&task_id);
+    assert_eq!(items[0]["name"], "Post on socials (by Sun 20 Oct)");
+
+    // Ticked off checklist items keep their name
+    fake.resolve_checklist_item(items[0]["id"].as_str().unwrap());
+    social.start_time = Some(social.start_time.unwrap() + Duration::days(1));
+    social.end_time = Some(social.end_time.unwrap() + Duration::days(1));
+    sync_events(&api, &context, &mut store, vec![social]).unwrap();
+    let items = fake.checklist_items(&task_id);
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["name"], "Post on socials (by Sun 20 Oct)");
+}
+
+#[test]
+fn invalid_due_dates_are_rejected() {
+    let fake = FakeClickUp::start();
+    let api = fake.client();
+    let config = parse_config(
+        "[[templates]]\n[[templates.subtasks]]\nname = \"Book room\"\ndue = \"two weeks before\"",
+    )
+    .unwrap();
+    assert!(SyncContext::load(&api, &config, None).is_err());
+}